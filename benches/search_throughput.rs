@@ -0,0 +1,211 @@
+//! `HYPPO_BENCH_SONGS`, `HYPPO_BENCH_SECONDS` and `HYPPO_BENCH_CLIENTS` (comma separated) size
+//! the run, `HYPPO_BENCH_BASELINE` names another server binary to measure next to this one.
+
+mod song_infos {
+    tonic::include_proto!("song_infos");
+}
+
+use rusqlite::{params, Connection};
+use song_infos::song_infos_service_client::SongInfosServiceClient;
+use song_infos::Request as SongInfosRequest;
+use std::env;
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tonic::transport::Channel;
+
+const DEFAULT_SONGS: u64 = 20_000;
+const DEFAULT_SECONDS: u64 = 5;
+const DEFAULT_CLIENTS: &str = "1,4,16,64";
+
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn setting<T: FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+fn create_library(root: &Path, songs: u64) {
+    let files = root.join("files");
+    std::fs::create_dir_all(&files).unwrap();
+    let mut connection = Connection::open(root.join("files_database.sqlite")).unwrap();
+    connection
+        .execute_batch(
+            "create table Songs
+                (
+                    Name       text not null,
+                    Artist     text not null,
+                    Image_path text,
+                    File_path  text not null,
+                    primary key (Name, Artist)
+                );",
+        )
+        .unwrap();
+    let transaction = connection.transaction().unwrap();
+    {
+        let mut insert_song_statement = transaction
+            .prepare("insert into Songs (Name, Artist, File_path) values (?1, ?2, ?3)")
+            .unwrap();
+        for index in 0..songs {
+            let name = format!("Song {}", index);
+            let artist = format!("Artist {}", index % 500);
+            let file_path = files.join(format!("{} - {}.mp3", artist, name));
+            insert_song_statement
+                .execute(params![name, artist, file_path.to_str().unwrap()])
+                .unwrap();
+        }
+    }
+    transaction.commit().unwrap();
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn start_server(binary: &str, root: &Path, port: u16) -> Server {
+    let root = format!("{}/", root.display());
+    let child = Command::new(binary)
+        .args(["-p", &port.to_string(), "-f", &root, "-u", "false", "-e"])
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("Could not start the server");
+    Server(child)
+}
+
+async fn connect(port: u16) -> SongInfosServiceClient<Channel> {
+    let address = format!("http://127.0.0.1:{}", port);
+    for _ in 0..100 {
+        if let Ok(client) = SongInfosServiceClient::connect(address.clone()).await {
+            return client;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Server did not start listening on port {}", port);
+}
+
+// Keywords found in the later half of the songs, so each search reads through much of the table
+async fn search(client: &mut SongInfosServiceClient<Channel>, songs: u64, index: u64) {
+    let request = SongInfosRequest {
+        name: format!("Song {}", songs / 2 + index % (songs / 2)),
+        user: String::from("bench"),
+        min_rating: 0,
+        favourites_only: false,
+        sort_by_rating: false,
+    };
+    let mut stream = client.get_by_name(request).await.unwrap().into_inner();
+    while stream.message().await.unwrap().is_some() {}
+}
+
+async fn measure(
+    client: &SongInfosServiceClient<Channel>,
+    clients: u64,
+    songs: u64,
+    duration: Duration,
+) -> f64 {
+    let searches = Arc::new(AtomicU64::new(0));
+    let running = Arc::new(AtomicBool::new(true));
+    let tasks: Vec<_> = (0..clients)
+        .map(|offset| {
+            let mut client = client.clone();
+            let searches = Arc::clone(&searches);
+            let running = Arc::clone(&running);
+            tokio::spawn(async move {
+                let mut index = offset * 7919;
+                while running.load(Ordering::Relaxed) {
+                    search(&mut client, songs, index).await;
+                    searches.fetch_add(1, Ordering::Relaxed);
+                    index += 1;
+                }
+            })
+        })
+        .collect();
+
+    let started = Instant::now();
+    tokio::time::sleep(duration).await;
+    let counted = searches.load(Ordering::Relaxed);
+    let elapsed = started.elapsed();
+    running.store(false, Ordering::Relaxed);
+    for task in tasks {
+        task.await.unwrap();
+    }
+    counted as f64 / elapsed.as_secs_f64()
+}
+
+async fn run(binary: &str, songs: u64, steps: &[u64], duration: Duration) -> Vec<f64> {
+    let root = tempfile::tempdir().unwrap();
+    create_library(root.path(), songs);
+    let port = free_port();
+    let _server = start_server(binary, root.path(), port);
+    let client = connect(port).await;
+
+    measure(&client, 1, songs, Duration::from_millis(500)).await;
+
+    let mut results = Vec::new();
+    for clients in steps {
+        results.push(measure(&client, *clients, songs, duration).await);
+    }
+    results
+}
+
+#[tokio::main]
+async fn main() {
+    let songs = setting("HYPPO_BENCH_SONGS", DEFAULT_SONGS).max(2);
+    let duration = Duration::from_secs(setting("HYPPO_BENCH_SECONDS", DEFAULT_SECONDS));
+    let steps: Vec<u64> = setting("HYPPO_BENCH_CLIENTS", String::from(DEFAULT_CLIENTS))
+        .split(',')
+        .filter_map(|clients| clients.trim().parse().ok())
+        .collect();
+    let baseline = env::var("HYPPO_BENCH_BASELINE")
+        .ok()
+        .filter(|binary| !binary.is_empty());
+
+    let results = run(
+        env!("CARGO_BIN_EXE_hyppo_tunes_server"),
+        songs,
+        &steps,
+        duration,
+    )
+    .await;
+    let baseline_results = match &baseline {
+        Some(binary) => run(binary, songs, &steps, duration).await,
+        None => Vec::new(),
+    };
+
+    println!("search throughput, {} songs, searches/s", songs);
+    match &baseline {
+        Some(binary) => println!(
+            "{:>7} {:>10} {:>10}  (baseline {})",
+            "clients", "server", "baseline", binary
+        ),
+        None => println!("{:>7} {:>10}", "clients", "server"),
+    }
+    for (index, clients) in steps.iter().enumerate() {
+        match baseline_results.get(index) {
+            Some(baseline_result) => println!(
+                "{:>7} {:>10.1} {:>10.1}  ({:+.0}%)",
+                clients,
+                results[index],
+                baseline_result,
+                (results[index] / baseline_result - 1.0) * 100.0
+            ),
+            None => println!("{:>7} {:>10.1}", clients, results[index]),
+        }
+    }
+}
@@ -3,4 +3,30 @@ fn main() {
         .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
     tonic_build::compile_protos("proto/song_infos.proto",)
         .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
+    tonic_build::compile_protos("proto/ratings.proto",)
+        .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
+    tonic_build::compile_protos("proto/plays.proto",)
+        .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
+    tonic_build::compile_protos("proto/playback_state.proto",)
+        .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
+    tonic_build::compile_protos("proto/remote_control.proto",)
+        .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
+    tonic_build::compile_protos("proto/listen_together.proto",)
+        .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
+    tonic_build::compile_protos("proto/upload.proto",)
+        .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
+    tonic_build::compile_protos("proto/library.proto",)
+        .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
+    tonic_build::compile_protos("proto/tags.proto",)
+        .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
+    tonic_build::compile_protos("proto/overrides.proto",)
+        .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
+    tonic_build::compile_protos("proto/trash.proto",)
+        .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
+    tonic_build::compile_protos("proto/watcher.proto",)
+        .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
+    tonic_build::compile_protos("proto/rescan.proto",)
+        .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
+    tonic_build::compile_protos("proto/scan_issues.proto",)
+        .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
 }
\ No newline at end of file
@@ -1,4 +1,11 @@
+use crate::core::utils::filename_templates::{
+    FilenameTemplate, LibraryLayout, DEFAULT_LAYOUT, DEFAULT_TEMPLATE,
+};
+use crate::core::utils::ignore_rules::IgnoreRules;
+use crate::core::utils::library_roots::{LibraryRoot, LibraryRoots};
+use crate::core::utils::safe_paths::SymlinkPolicy;
 use core::fmt;
+use itertools::Itertools;
 use std::path::Path;
 use std::{env, process};
 
@@ -12,7 +19,7 @@ fn create_config() -> Config {
         Ok(config) => config,
         Err(e) => {
             eprintln!("Problem passing arguments:\n{}", e);
-            eprintln!("Usage: music-server-rs -p [port: 0-65536] -f [music folder path] -u(Optional) -e(Optional)");
+            eprintln!("Usage: music-server-rs -p [port: 0-65536] -f [music folder path] -u(Optional) -e(Optional) -a(Optional) -m(Optional) -x(Optional) -t(Optional) -l(Optional) -i(Optional) -z(Optional) -s(Optional) -g(Optional) -r(Optional) -n(Optional) -d(Optional) -k(Optional)");
             eprintln!(
                 "Where -p represents the port on which the server will be started, default is 8980"
             );
@@ -21,6 +28,28 @@ fn create_config() -> Config {
                 "Where -u sets whether to automatically update the server data, default is true"
             );
             eprintln!("Where -e sets whether to start the server locally, default is false");
+            eprintln!(
+                "Where -a sets the comma separated audio formats accepted by the server, default is mp3"
+            );
+            eprintln!(
+                "Where -m sets the maximum size of an uploaded file in megabytes, default is 200"
+            );
+            eprintln!("Where -t adds a filename template like \"{{track}} - {{title}}\", tried in the given order, default is \"{}\"", DEFAULT_TEMPLATE);
+            eprintln!("Where -l lists which filename template matches each file and exits");
+            eprintln!("Where -r checks the files folder, lists the files that could not be added with the reason and exits");
+            eprintln!("Where -z sets what happens to imported archives: keep, delete or archive, default is keep");
+            eprintln!(
+                "Where -s sets the maximum extracted size of an archive in megabytes, default is 2048"
+            );
+            eprintln!("Where -i sets the layout songs dropped into the inbox are filed by, default is \"{}\"", DEFAULT_LAYOUT);
+            eprintln!(
+                "Where -g sets for how many days songs whose file is missing are kept, default is 30"
+            );
+            eprintln!("Where -n adds a gitignore-style pattern like \"*.part\" for files the scanner skips in every folder, next to the ones of .hyppoignore files");
+            eprintln!("Where -d adds a library root like \"usb=/media/usb/music\", scanned and watched next to the files folder");
+            eprintln!("Where -d also takes roots like \"cloud=s3://bucket/music\" kept in S3-compatible object storage, signed in with AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY, AWS_REGION and AWS_ENDPOINT_URL for servers like MinIO");
+            eprintln!("Library management requests need the token set in HYPPO_ADMIN_TOKEN, and are refused when it is not set");
+            eprintln!("Where -k sets which symlinks songs and covers are served through: inside the library roots, all or none, default is inside");
             process::exit(1);
         }
     };
@@ -31,18 +60,61 @@ pub fn init_config() {
     println!(
         "Port: {}\n\
         File system root: {}\n\
-        Update database automatically: {}",
-        CONFIG.port, CONFIG.file_system_root, CONFIG.update_automatically
+        Update database automatically: {}\n\
+        Audio formats: {}\n\
+        Library roots: {}\n\
+        Filename templates: {}",
+        CONFIG.port,
+        CONFIG.file_system_root,
+        CONFIG.update_automatically,
+        CONFIG.audio_formats.join(", "),
+        CONFIG.library_roots,
+        CONFIG.filename_templates.iter().join(", ")
     );
 }
 
+pub enum ArchiveHandling {
+    Keep,
+    Delete,
+    Archive,
+}
+
+impl fmt::Display for ArchiveHandling {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArchiveHandling::Keep => write!(f, "keep"),
+            ArchiveHandling::Delete => write!(f, "delete"),
+            ArchiveHandling::Archive => write!(f, "archive"),
+        }
+    }
+}
+
 pub struct Config {
     pub update_automatically: bool,
     pub start_locally: bool,
     pub port: u16,
     pub file_system_root: String,
     pub files_folder_path: String,
+    pub library_roots: LibraryRoots,
     pub files_database_path: String,
+    pub uploads_folder_path: String,
+    pub covers_folder_path: String,
+    pub inbox_folder_path: String,
+    pub rejected_folder_path: String,
+    pub archives_folder_path: String,
+    pub audio_formats: Vec<String>,
+    pub max_upload_size: u64,
+    pub upload_expiry_ms: i64,
+    pub filename_templates: Vec<FilenameTemplate>,
+    pub report_templates: bool,
+    pub report_scan_issues: bool,
+    pub library_layout: LibraryLayout,
+    pub archive_handling: ArchiveHandling,
+    pub max_archive_size: u64,
+    pub missing_grace_ms: i64,
+    pub ignore_rules: IgnoreRules,
+    pub symlink_policy: SymlinkPolicy,
+    pub admin_token: Option<String>,
 }
 
 impl Config {
@@ -51,26 +123,44 @@ impl Config {
         let mut file_system_root = find_current_dir();
         let mut update_automatically = true;
         let mut start_locally = false;
+        let mut report_templates = false;
+        let mut report_scan_issues = false;
+
+        let mut audio_formats = String::from("mp3");
+        let mut max_upload_size: Option<String> = Some(String::from("200"));
+        let mut upload_expiry: Option<String> = Some(String::from("24"));
+        let mut templates: Vec<String> = Vec::new();
+        let mut layout = String::from(DEFAULT_LAYOUT);
+        let mut archive_handling = String::from("keep");
+        let mut max_archive_size: Option<String> = Some(String::from("2048"));
+        let mut missing_grace: Option<String> = Some(String::from("30"));
+        let mut ignore_patterns: Vec<String> = Vec::new();
+        let mut roots: Vec<String> = Vec::new();
+        let mut symlink_policy = String::from("inside");
 
         for i in 1..arguments.len() {
             let argument: &str = &arguments[i];
+            let has_value = i + 1 < arguments.len() && !arguments[i + 1].starts_with('-');
             match argument {
                 "-e" => start_locally = true,
-                "-u" => {
-                    if i + 1 < arguments.len() && !arguments[i + 1].starts_with('-') {
-                        update_automatically = arguments[i + 1].parse().unwrap_or(true)
-                    }
-                }
-                "-p" => {
-                    if i + 1 < arguments.len() && !arguments[i + 1].starts_with('-') {
-                        port = Some(arguments[i + 1].clone());
-                    }
-                }
-                "-f" => {
-                    if i + 1 < arguments.len() && !arguments[i + 1].starts_with('-') {
-                        file_system_root = Some(arguments[i + 1].clone());
-                    }
+                "-l" => report_templates = true,
+                "-r" => report_scan_issues = true,
+                "-u" if has_value => {
+                    update_automatically = arguments[i + 1].parse().unwrap_or(true)
                 }
+                "-p" if has_value => port = Some(arguments[i + 1].clone()),
+                "-f" if has_value => file_system_root = Some(arguments[i + 1].clone()),
+                "-a" if has_value => audio_formats = arguments[i + 1].clone(),
+                "-m" if has_value => max_upload_size = Some(arguments[i + 1].clone()),
+                "-x" if has_value => upload_expiry = Some(arguments[i + 1].clone()),
+                "-t" if has_value => templates.push(arguments[i + 1].clone()),
+                "-i" if has_value => layout = arguments[i + 1].clone(),
+                "-z" if has_value => archive_handling = arguments[i + 1].clone(),
+                "-s" if has_value => max_archive_size = Some(arguments[i + 1].clone()),
+                "-g" if has_value => missing_grace = Some(arguments[i + 1].clone()),
+                "-n" if has_value => ignore_patterns.push(arguments[i + 1].clone()),
+                "-d" if has_value => roots.push(arguments[i + 1].clone()),
+                "-k" if has_value => symlink_policy = arguments[i + 1].clone(),
                 _ => {}
             }
         }
@@ -95,9 +185,98 @@ impl Config {
         // Files folder path
         let files_folder_path = format!("{}files/", file_system_root);
 
+        // Library roots
+        let roots = roots
+            .iter()
+            .map(|root| LibraryRoot::parse(root))
+            .collect::<Result<Vec<LibraryRoot>, String>>()?;
+        let library_roots = LibraryRoots::new(&files_folder_path, roots)?;
+        library_roots.mount_buckets()?;
+
         // Files database path
         let files_database_path = format!("{}files_database.sqlite", file_system_root);
 
+        // Uploads folder path
+        let uploads_folder_path = format!("{}uploads/", file_system_root);
+
+        // Covers folder path
+        let covers_folder_path = format!("{}covers/", file_system_root);
+
+        // Inbox folder paths
+        let inbox_folder_path = format!("{}inbox/", file_system_root);
+        let rejected_folder_path = format!("{}rejected/", file_system_root);
+
+        // Archives folder path
+        let archives_folder_path = format!("{}archives/", file_system_root);
+
+        // Audio formats
+        let audio_formats: Vec<String> = audio_formats
+            .split(',')
+            .map(|format| format.trim().trim_start_matches('.').to_lowercase())
+            .filter(|format| !format.is_empty())
+            .collect();
+        if audio_formats.is_empty() {
+            return Err(String::from("No audio formats specified"));
+        }
+
+        // Max upload size
+        let max_upload_size = match max_upload_size.and_then(|size| size.parse::<u64>().ok()) {
+            None => return Err(String::from("Max upload size illegal")),
+            Some(size) => size * 1024 * 1024,
+        };
+
+        // Upload expiry
+        let upload_expiry_ms = match upload_expiry.and_then(|hours| hours.parse::<i64>().ok()) {
+            Some(hours) if hours > 0 => hours * 60 * 60 * 1000,
+            _ => return Err(String::from("Upload expiry illegal")),
+        };
+
+        // Filename templates
+        if templates.is_empty() {
+            templates.push(String::from(DEFAULT_TEMPLATE));
+        }
+        let filename_templates = templates
+            .iter()
+            .map(|template| FilenameTemplate::parse(template))
+            .collect::<Result<Vec<FilenameTemplate>, String>>()?;
+
+        // Library layout
+        let library_layout = LibraryLayout::parse(&layout)?;
+
+        // Archive handling
+        let archive_handling = match archive_handling.as_str() {
+            "keep" => ArchiveHandling::Keep,
+            "delete" => ArchiveHandling::Delete,
+            "archive" => ArchiveHandling::Archive,
+            _ => return Err(String::from("Archive handling illegal")),
+        };
+
+        // Max archive size
+        let max_archive_size = match max_archive_size
+            .and_then(|size| size.parse::<u64>().ok())
+            .and_then(|size| size.checked_mul(1024 * 1024))
+        {
+            None => return Err(String::from("Max archive size illegal")),
+            Some(size) => size,
+        };
+
+        // Missing songs grace period
+        let missing_grace_ms = match missing_grace.and_then(|days| days.parse::<i64>().ok()) {
+            Some(days) if days > 0 => days * 24 * 60 * 60 * 1000,
+            _ => return Err(String::from("Missing songs grace period illegal")),
+        };
+
+        // Ignore rules
+        let ignore_rules = IgnoreRules::parse(&ignore_patterns);
+
+        // Symlinks served through
+        let symlink_policy = SymlinkPolicy::parse(&symlink_policy)?;
+
+        // Admin token, from the environment so it does not show in the process list
+        let admin_token = env::var("HYPPO_ADMIN_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty());
+
         // Create config
         let config = Config {
             update_automatically,
@@ -105,7 +284,26 @@ impl Config {
             port,
             file_system_root,
             files_folder_path,
+            library_roots,
             files_database_path,
+            uploads_folder_path,
+            covers_folder_path,
+            inbox_folder_path,
+            rejected_folder_path,
+            archives_folder_path,
+            audio_formats,
+            max_upload_size,
+            upload_expiry_ms,
+            filename_templates,
+            report_templates,
+            report_scan_issues,
+            library_layout,
+            archive_handling,
+            max_archive_size,
+            missing_grace_ms,
+            ignore_rules,
+            symlink_policy,
+            admin_token,
         };
         Ok(config)
     }
@@ -126,13 +324,47 @@ impl fmt::Display for Config {
             "Port: {}\n\
                File system root: {}\n\
                Files folder path: {}\n\
+               Library roots: {}\n\
                Files database path: {}\n\
+               Uploads folder path: {}\n\
+               Covers folder path: {}\n\
+               Inbox folder path: {}\n\
+               Rejected folder path: {}\n\
+               Archives folder path: {}\n\
+               Audio formats: {}\n\
+               Max upload size: {}\n\
+               Upload expiry: {}ms\n\
+               Filename templates: {}\n\
+               Library layout: {}\n\
+               Archive handling: {}\n\
+               Max archive size: {}\n\
+               Missing songs grace period: {}ms\n\
+               Ignore patterns: {}\n\
+               Symlink policy: {}\n\
+               Admin token set: {}\n\
                Update database automatically: {}\n\
                Run for emulator: {}",
             self.port,
             self.file_system_root,
             self.files_folder_path,
+            self.library_roots,
             self.files_database_path,
+            self.uploads_folder_path,
+            self.covers_folder_path,
+            self.inbox_folder_path,
+            self.rejected_folder_path,
+            self.archives_folder_path,
+            self.audio_formats.join(", "),
+            self.max_upload_size,
+            self.upload_expiry_ms,
+            self.filename_templates.iter().join(", "),
+            self.library_layout,
+            self.archive_handling,
+            self.max_archive_size,
+            self.missing_grace_ms,
+            self.ignore_rules,
+            self.symlink_policy,
+            self.admin_token.is_some(),
             self.update_automatically,
             self.start_locally
         )
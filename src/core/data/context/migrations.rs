@@ -0,0 +1,430 @@
+use rusqlite::{params, Connection, Transaction};
+use std::error::Error;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+struct Migration {
+    description: &'static str,
+    apply: fn(&Transaction) -> rusqlite::Result<()>,
+}
+
+// Append only, the position of a step is the version it upgrades to
+const MIGRATIONS: [Migration; 10] = [
+    Migration {
+        description: "Songs",
+        apply: create_songs,
+    },
+    Migration {
+        description: "Ratings and favourites",
+        apply: create_ratings,
+    },
+    Migration {
+        description: "Play history",
+        apply: create_plays,
+    },
+    Migration {
+        description: "Playback states",
+        apply: create_playback_states,
+    },
+    Migration {
+        description: "Upload sessions",
+        apply: create_upload_sessions,
+    },
+    Migration {
+        description: "Song details and tag edits",
+        apply: create_tag_edits,
+    },
+    Migration {
+        description: "File identities and metadata overrides",
+        apply: create_metadata_overrides,
+    },
+    Migration {
+        description: "File path and identity indexes",
+        apply: create_file_indexes,
+    },
+    Migration {
+        description: "Missing songs",
+        apply: add_missing_since,
+    },
+    Migration {
+        description: "File stamps and scan issues",
+        apply: create_scan_issues,
+    },
+];
+
+pub const SCHEMA_VERSION: usize = MIGRATIONS.len();
+
+pub fn migrate(connection: &mut Connection, db_path: &str) -> Result<(), Box<dyn Error>> {
+    let version: i64 = connection.query_row("pragma user_version", [], |row| row.get(0))?;
+    let version = version.max(0) as usize;
+    if version > SCHEMA_VERSION {
+        return Err(format!(
+            "Database schema version {} is newer than the version {} of this server",
+            version, SCHEMA_VERSION
+        )
+        .into());
+    }
+    if version == SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    let is_empty: bool =
+        connection.query_row("select count(*) = 0 from sqlite_master", [], |row| {
+            row.get(0)
+        })?;
+    if !is_empty {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_millis())
+            .unwrap_or(0);
+        let backup_path = format!("{}.v{}-{}.backup", db_path, version, now);
+        connection.execute("vacuum into ?1", params![backup_path])?;
+        println!("Database backed up to {}", backup_path);
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = connection.transaction()?;
+        (migration.apply)(&transaction)
+            .and_then(|_| {
+                transaction.execute_batch(&format!("pragma user_version = {};", index + 1))
+            })
+            .and_then(|_| transaction.commit())
+            .map_err(|e| {
+                format!(
+                    "Database migration to version {} ({}) failed:\n{}",
+                    index + 1,
+                    migration.description,
+                    e
+                )
+            })?;
+    }
+    println!(
+        "Database upgraded from version {} to {}",
+        version, SCHEMA_VERSION
+    );
+    Ok(())
+}
+
+fn has_column(transaction: &Transaction, table: &str, column: &str) -> rusqlite::Result<bool> {
+    let mut statement = transaction.prepare(&format!("pragma table_info({})", table))?;
+    let mut names = statement.query_map([], |row| row.get::<_, String>(1))?;
+    Ok(names.any(|name| matches!(name, Ok(name) if name.eq_ignore_ascii_case(column))))
+}
+
+fn add_column(
+    transaction: &Transaction,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    if !has_column(transaction, table, column)? {
+        transaction.execute(
+            &format!("alter table {} add column {} {}", table, column, definition),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+fn create_songs(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute(
+        "create table if not exists Songs
+            (
+                Name       text not null,
+                Artist     text not null,
+                Image_path text,
+                File_path  text not null,
+                primary key (Name, Artist)
+            );",
+        [],
+    )?;
+    Ok(())
+}
+
+fn create_ratings(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute(
+        "create table if not exists Ratings
+            (
+                User      text    not null,
+                Name      text    not null,
+                Artist    text    not null,
+                Rating    integer check (Rating between 1 and 5),
+                Favourite integer not null default 0,
+                primary key (User, Name, Artist),
+                foreign key (Name, Artist) references Songs (Name, Artist)
+                    on update cascade on delete cascade
+            );",
+        [],
+    )?;
+    Ok(())
+}
+
+fn create_plays(transaction: &Transaction) -> rusqlite::Result<()> {
+    add_column(
+        transaction,
+        "Songs",
+        "Play_count",
+        "integer not null default 0",
+    )?;
+    transaction.execute(
+        "create table if not exists Plays
+            (
+                User        text    not null,
+                Name        text    not null,
+                Artist      text    not null,
+                Started_at  integer not null,
+                Listened_ms integer not null,
+                Completed   integer not null,
+                foreign key (Name, Artist) references Songs (Name, Artist)
+                    on update cascade on delete cascade
+            );",
+        [],
+    )?;
+    transaction.execute(
+        "create index if not exists Plays_by_user on Plays (User, Started_at);",
+        [],
+    )?;
+    Ok(())
+}
+
+fn create_playback_states(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute(
+        "create table if not exists PlaybackStates
+            (
+                User        text    not null primary key,
+                Device      text    not null,
+                Queue_index integer not null,
+                Position_ms integer not null,
+                Playing     integer not null,
+                Updated_at  integer not null
+            );",
+        [],
+    )?;
+    transaction.execute(
+        "create table if not exists PlaybackQueues
+            (
+                User     text    not null,
+                Position integer not null,
+                Name     text    not null,
+                Artist   text    not null,
+                primary key (User, Position),
+                foreign key (User) references PlaybackStates (User) on delete cascade,
+                foreign key (Name, Artist) references Songs (Name, Artist)
+                    on update cascade on delete cascade
+            );",
+        [],
+    )?;
+    Ok(())
+}
+
+fn create_upload_sessions(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute(
+        "create table if not exists UploadSessions
+            (
+                Id         text    not null primary key,
+                Name       text    not null,
+                Artist     text    not null,
+                Format     text    not null,
+                Size       integer not null,
+                Sha256     text    not null,
+                Offset     integer not null default 0,
+                Expires_at integer not null
+            );",
+        [],
+    )?;
+    Ok(())
+}
+
+fn create_tag_edits(transaction: &Transaction) -> rusqlite::Result<()> {
+    add_column(transaction, "Songs", "Album", "text")?;
+    add_column(transaction, "Songs", "Genre", "text")?;
+    add_column(transaction, "Songs", "Year", "integer")?;
+    add_column(transaction, "Songs", "Track", "integer")?;
+    transaction.execute(
+        "create table if not exists TagEdits
+            (
+                Id         integer not null primary key autoincrement,
+                Name       text    not null,
+                Artist     text    not null,
+                Edited_at  integer not null,
+                Title      text,
+                Tag_artist text,
+                Album      text,
+                Genre      text,
+                Year       integer,
+                Track      integer,
+                Cover_mime text,
+                Cover      blob,
+                foreign key (Name, Artist) references Songs (Name, Artist)
+                    on update cascade on delete cascade
+            );",
+        [],
+    )?;
+    Ok(())
+}
+
+fn create_metadata_overrides(transaction: &Transaction) -> rusqlite::Result<()> {
+    add_column(transaction, "Songs", "File_id", "text")?;
+    transaction.execute(
+        "create table if not exists MetadataOverrides
+            (
+                File_id   text not null primary key,
+                File_path text not null,
+                Title     text,
+                Artist    text,
+                Album     text,
+                Genre     text,
+                Year      integer,
+                Track     integer
+            );",
+        [],
+    )?;
+    Ok(())
+}
+
+fn create_file_indexes(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute(
+        "create index if not exists Songs_by_file_path on Songs (File_path);",
+        [],
+    )?;
+    transaction.execute(
+        "create index if not exists Songs_by_file_id on Songs (File_id);",
+        [],
+    )?;
+    Ok(())
+}
+
+fn add_missing_since(transaction: &Transaction) -> rusqlite::Result<()> {
+    add_column(transaction, "Songs", "Missing_since", "integer")
+}
+
+fn create_scan_issues(transaction: &Transaction) -> rusqlite::Result<()> {
+    add_column(transaction, "Songs", "File_size", "integer")?;
+    add_column(transaction, "Songs", "File_mtime", "integer")?;
+    transaction.execute(
+        "create table if not exists ScanIssues
+            (
+                File_path text    not null primary key,
+                Reason    text    not null,
+                Detail    text    not null,
+                Found_at  integer not null
+            );",
+        [],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    fn version(connection: &Connection) -> usize {
+        connection
+            .query_row("pragma user_version", [], |row| row.get::<_, i64>(0))
+            .unwrap() as usize
+    }
+
+    fn backups(folder: &Path) -> Vec<String> {
+        fs::read_dir(folder)
+            .unwrap()
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".backup"))
+            .collect()
+    }
+
+    fn legacy_database(folder: &Path) -> (Connection, String) {
+        let db_path = folder.join("files_database.sqlite");
+        let db_path = db_path.to_str().unwrap().to_string();
+        let connection = Connection::open(&db_path).unwrap();
+        connection
+            .execute_batch(
+                "create table Songs
+                    (
+                        Name       text not null,
+                        Artist     text not null,
+                        Image_path text,
+                        File_path  text not null,
+                        primary key (Name, Artist)
+                    );
+                insert into Songs (Name, Artist, File_path) values ('One', 'Metallica', 'one.mp3');",
+            )
+            .unwrap();
+        (connection, db_path)
+    }
+
+    #[test]
+    fn new_database_is_created_at_the_latest_version() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate(&mut connection, ":memory:").unwrap();
+
+        assert_eq!(version(&connection), SCHEMA_VERSION);
+        let transaction = connection.transaction().unwrap();
+        assert!(has_column(&transaction, "Songs", "File_mtime").unwrap());
+        assert!(has_column(&transaction, "ScanIssues", "Reason").unwrap());
+    }
+
+    #[test]
+    fn database_from_before_versioning_keeps_its_songs_and_is_backed_up() {
+        let folder = tempfile::tempdir().unwrap();
+        let (mut connection, db_path) = legacy_database(folder.path());
+        migrate(&mut connection, &db_path).unwrap();
+
+        assert_eq!(version(&connection), SCHEMA_VERSION);
+        let song: (String, i64, Option<i64>) = connection
+            .query_row(
+                "select File_path, Play_count, Missing_since from Songs where Name = 'One'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(song, (String::from("one.mp3"), 0, None));
+
+        let backups = backups(folder.path());
+        assert_eq!(backups.len(), 1);
+        let backup = Connection::open(folder.path().join(&backups[0])).unwrap();
+        assert_eq!(version(&backup), 0);
+        let songs: i64 = backup
+            .query_row("select count(*) from Songs", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(songs, 1);
+    }
+
+    #[test]
+    fn migrated_database_is_left_as_it_is() {
+        let folder = tempfile::tempdir().unwrap();
+        let (mut connection, db_path) = legacy_database(folder.path());
+        migrate(&mut connection, &db_path).unwrap();
+        migrate(&mut connection, &db_path).unwrap();
+
+        assert_eq!(version(&connection), SCHEMA_VERSION);
+        assert_eq!(backups(folder.path()).len(), 1);
+    }
+
+    #[test]
+    fn failed_step_is_rolled_back() {
+        let folder = tempfile::tempdir().unwrap();
+        let (mut connection, db_path) = legacy_database(folder.path());
+        connection
+            .execute_batch("create table Plays_by_user (Id integer);")
+            .unwrap();
+
+        assert!(migrate(&mut connection, &db_path).is_err());
+        assert_eq!(version(&connection), 2);
+        let transaction = connection.transaction().unwrap();
+        assert!(has_column(&transaction, "Ratings", "Rating").unwrap());
+        assert!(!has_column(&transaction, "Songs", "Play_count").unwrap());
+    }
+
+    #[test]
+    fn newer_database_is_refused() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(&format!("pragma user_version = {};", SCHEMA_VERSION + 1))
+            .unwrap();
+
+        assert!(migrate(&mut connection, ":memory:").is_err());
+        assert_eq!(version(&connection), SCHEMA_VERSION + 1);
+    }
+}
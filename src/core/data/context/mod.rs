@@ -1 +1,2 @@
+pub mod migrations;
 pub mod songs_system_db_context;
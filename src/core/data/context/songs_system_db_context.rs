@@ -1,39 +1,96 @@
+use crate::core::data::context::migrations::migrate;
+use crate::core::data::entity::metadata_override::MetadataOverride;
+use crate::core::data::entity::play_event::PlayEvent;
+use crate::core::data::entity::playback_state::{PlaybackState, PlaybackStateSave};
+use crate::core::data::entity::queued_song::QueuedSong;
+use crate::core::data::entity::rating_filter::RatingFilter;
+use crate::core::data::entity::scan_issue::ScanIssue;
 use crate::core::data::entity::song::Song;
 use crate::core::data::entity::song_info::SongInfo;
+use crate::core::data::entity::song_plays::SongPlays;
+use crate::core::data::entity::song_rating::SongRating;
+use crate::core::data::entity::tag_edit::TagEdit;
+use crate::core::data::entity::trashed_song::TrashedSong;
+use crate::core::data::entity::upload_session::UploadSession;
+use crate::core::utils::audio_checks::IssueReason;
+use crate::core::utils::audio_tags::{AudioTags, Cover};
+use crate::core::utils::file_identity::FileStamp;
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::params;
-use std::sync::{Arc, Mutex};
+use rusqlite::{params, Connection, Params};
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
 
+const MAX_TAG_EDITS: i64 = 20;
+
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const READERS: u32 = 8;
+
+type DbError = Box<dyn std::error::Error + Send + Sync>;
+type WriteJob = Box<dyn FnOnce(&mut Connection) + Send>;
+
+// Writes are queued to a single writer thread, so they never fail on each other's locks
 pub struct SongsSystemDbContext {
-    connection_pool: Arc<Mutex<Pool<SqliteConnectionManager>>>,
+    readers: Pool<SqliteConnectionManager>,
+    writer: mpsc::Sender<WriteJob>,
 }
 
 impl SongsSystemDbContext {
     pub fn new(db_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let sqlite_connection_manager = SqliteConnectionManager::file(db_path);
-        let sqlite_pool = r2d2::Pool::new(sqlite_connection_manager)?;
-        let connection = sqlite_pool.get()?;
+        let mut connection = Connection::open(db_path)?;
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+        connection.query_row("PRAGMA journal_mode = WAL;", [], |_| Ok(()))?;
+        connection.execute_batch("PRAGMA foreign_keys = ON; PRAGMA synchronous = NORMAL;")?;
+        migrate(&mut connection, db_path)?;
+        // The view is created again, so databases created before a change to it get the change
+        connection.execute("drop view if exists SongInfos;", [])?;
         connection.execute(
-            "create table if not exists Songs
-                (
-                    Name       text not null,
-                    Artist     text not null,
-                    Image_path text,
-                    File_path  text not null,
-                    primary key (Name, Artist)
-                );",
+            "CREATE VIEW SongInfos as
+                 select Artist || Name as Keyword, Name, Artist, Image_path, File_path
+                 from Songs
+                 where Missing_since is null;",
             [],
         )?;
-        connection.execute(
-            "CREATE VIEW if not exists SongInfos as
-                 select Artist || Name as Keyword, Name, Artist, Image_path
-                 from Songs;",
-            [],
-        )?;
-        let connection_pool = Arc::new(Mutex::new(sqlite_pool));
 
-        Ok(SongsSystemDbContext { connection_pool })
+        let (writer, jobs) = mpsc::channel::<WriteJob>();
+        thread::Builder::new()
+            .name(String::from("database-writer"))
+            .spawn(move || {
+                for job in jobs {
+                    job(&mut connection);
+                }
+            })?;
+
+        let reader_manager = SqliteConnectionManager::file(db_path).with_init(|connection| {
+            connection.busy_timeout(BUSY_TIMEOUT)?;
+            connection.execute_batch("PRAGMA foreign_keys = ON; PRAGMA query_only = ON;")
+        });
+        let readers = Pool::builder().max_size(READERS).build(reader_manager)?;
+
+        Ok(SongsSystemDbContext { readers, writer })
+    }
+
+    fn write<T, F>(&self, job: F) -> Result<T, Box<dyn std::error::Error>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, DbError> + Send + 'static,
+    {
+        let (result_sender, result) = mpsc::sync_channel(1);
+        self.writer
+            .send(Box::new(move |connection| {
+                let output = panic::catch_unwind(AssertUnwindSafe(|| job(connection)))
+                    .unwrap_or_else(|_| Err("Database write panicked".into()));
+                let _ = result_sender.send(output);
+            }))
+            .map_err(|_| "Database writer stopped")?;
+        match result.recv() {
+            Ok(result) => result.map_err(|e| e as Box<dyn std::error::Error>),
+            Err(_) => Err("Database writer stopped".into()),
+        }
     }
 
     pub fn select_song_file_path(
@@ -41,15 +98,11 @@ impl SongsSystemDbContext {
         name: &str,
         artist: &str,
     ) -> Result<String, Box<dyn std::error::Error>> {
-        let pool = Arc::clone(&self.connection_pool);
-        let pool_lock = match pool.lock() {
-            Ok(lock) => lock,
-            Err(_) => return Err("Database pool poisoned".into()),
-        };
-        let connection = pool_lock.get()?;
+        let connection = self.readers.get()?;
 
         let mut select_file_path_statement = connection.prepare_cached(
-            "select File_path from Songs where Artist like ?1 and Name like ?2 LIMIT 1",
+            "select File_path from Songs \
+            where Artist like ?1 and Name like ?2 and Missing_since is null LIMIT 1",
         )?;
 
         let mut iterator = select_file_path_statement
@@ -69,28 +122,43 @@ impl SongsSystemDbContext {
     pub fn select_song_infos(
         &self,
         keyword: &str,
+        user: &str,
+        filter: &RatingFilter,
         count: usize,
     ) -> Result<Vec<SongInfo>, Box<dyn std::error::Error>> {
-        let pool = Arc::clone(&self.connection_pool);
-
-        let pool_lock = match pool.lock() {
-            Ok(lock) => lock,
-            Err(_) => return Err("Database pool poisoned".into()),
-        };
-
-        let connection = pool_lock.get()?;
+        let connection = self.readers.get()?;
 
-        let mut select_song_infos_statement = connection
-            .prepare_cached("select Name,Artist,Image_path from SongInfos where Keyword like ?1")?;
+        let mut select_song_infos_statement = connection.prepare_cached(
+            "select SongInfos.Name, SongInfos.Artist, SongInfos.Image_path, SongInfos.File_path, \
+                    Ratings.Rating, coalesce(Ratings.Favourite, 0) \
+            from SongInfos \
+            left join Ratings on Ratings.Name = SongInfos.Name \
+                and Ratings.Artist = SongInfos.Artist and Ratings.User = ?2 \
+            where SongInfos.Keyword like ?1 \
+                and coalesce(Ratings.Rating, 0) >= ?3 \
+                and (not ?4 or coalesce(Ratings.Favourite, 0)) \
+            order by case when ?5 then coalesce(Ratings.Rating, 0) else 0 end desc",
+        )?;
 
-        let params = params![format!("%{}%", keyword)];
+        let params = params![
+            format!("%{}%", keyword),
+            user,
+            filter.min_rating,
+            filter.favourites_only,
+            filter.sort_by_rating
+        ];
 
         let iterator = select_song_infos_statement
             .query_map(params, |row| {
                 let name: String = row.get(0)?;
                 let artist: String = row.get(1)?;
                 let image_path: Option<String> = row.get(2)?;
-                Ok(SongInfo::new(name, artist, image_path))
+                let file_path: String = row.get(3)?;
+                let rating: Option<u8> = row.get(4)?;
+                let favourite: bool = row.get(5)?;
+                Ok(SongInfo::new(
+                    name, artist, image_path, file_path, rating, favourite,
+                ))
             })?
             .take(count);
 
@@ -103,24 +171,88 @@ impl SongsSystemDbContext {
         Ok(output)
     }
 
-    pub fn select_all_songs(&self) -> Result<Vec<Song>, Box<dyn std::error::Error>> {
-        let pool = Arc::clone(&self.connection_pool);
-        let pool_lock = match pool.lock() {
-            Ok(lock) => lock,
-            Err(_) => return Err("Database pool poisoned".into()),
-        };
-        let connection = pool_lock.get()?;
-        let mut select_songs_statement =
-            connection.prepare_cached("select Name,Artist,File_path from Songs")?;
+    pub fn delete_song(&self, song: Song) -> Result<(), Box<dyn std::error::Error>> {
+        self.write(move |connection| {
+            let mut delete_song_statement = connection.prepare_cached(
+                "delete from Songs where (Name like ?1 and Artist like ?2) or File_path = ?3",
+            )?;
+            delete_song_statement.execute(params![song.name, song.artist, song.file_path])?;
+
+            Ok(())
+        })
+    }
+
+    pub fn update_song_missing(
+        &self,
+        file_path: &str,
+        missing_since: i64,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let file_path = file_path.to_owned();
+        self.write(move |connection| {
+            let mut update_missing_statement = connection.prepare_cached(
+                "update Songs set Missing_since = ?2 where File_path = ?1 and Missing_since is null",
+            )?;
+            let changed = update_missing_statement.execute(params![file_path, missing_since])?;
+
+            Ok(changed)
+        })
+    }
+
+    pub fn update_folder_missing(
+        &self,
+        path_prefix: &str,
+        missing_since: i64,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let path_prefix = path_prefix.to_owned();
+        self.write(move |connection| {
+            let mut update_missing_statement = connection.prepare_cached(
+                "update Songs set Missing_since = ?2 \
+                where substr(File_path, 1, length(?1)) = ?1 and Missing_since is null",
+            )?;
+            let changed = update_missing_statement.execute(params![path_prefix, missing_since])?;
+
+            Ok(changed)
+        })
+    }
+
+    pub fn select_missing_songs(&self) -> Result<Vec<TrashedSong>, Box<dyn std::error::Error>> {
+        self.select_missing_songs_by(
+            "select Name, Artist, File_path, Missing_since from Songs \
+            where Missing_since is not null \
+            order by Missing_since desc, Artist, Name",
+            params![],
+        )
+    }
+
+    pub fn select_missing_song(
+        &self,
+        name: &str,
+        artist: &str,
+    ) -> Result<Option<TrashedSong>, Box<dyn std::error::Error>> {
+        let songs = self.select_missing_songs_by(
+            "select Name, Artist, File_path, Missing_since from Songs \
+            where Name = ?1 and Artist = ?2 and Missing_since is not null",
+            params![name, artist],
+        )?;
+        Ok(songs.into_iter().next())
+    }
+
+    fn select_missing_songs_by<P: Params>(
+        &self,
+        sql: &str,
+        params: P,
+    ) -> Result<Vec<TrashedSong>, Box<dyn std::error::Error>> {
+        let connection = self.readers.get()?;
 
-        let params = params![];
+        let mut select_missing_statement = connection.prepare_cached(sql)?;
 
-        let output = select_songs_statement
+        let output = select_missing_statement
             .query_map(params, |row| {
                 let name: String = row.get(0)?;
                 let artist: String = row.get(1)?;
                 let file_path: String = row.get(2)?;
-                Ok(Song::new(name, artist, None, file_path))
+                let missing_since: i64 = row.get(3)?;
+                Ok(TrashedSong::new(name, artist, file_path, missing_since))
             })?
             .flatten()
             .collect();
@@ -128,44 +260,1238 @@ impl SongsSystemDbContext {
         Ok(output)
     }
 
-    pub fn delete_song(&self, song: Song) -> Result<(), Box<dyn std::error::Error>> {
-        let pool = self.connection_pool.clone();
-        let pool_lock = match pool.lock() {
-            Ok(lock) => lock,
-            Err(_) => return Err("Database pool poisoned".into()),
-        };
-        let connection = pool_lock.get()?;
+    pub fn delete_missing_songs(
+        &self,
+        missing_before: i64,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        self.write(move |connection| {
+            let mut delete_missing_statement =
+                connection.prepare_cached("delete from Songs where Missing_since < ?1")?;
+            let deleted = delete_missing_statement.execute(params![missing_before])?;
 
-        let mut delete_song_statement =
-            connection.prepare_cached("delete from Songs where Name like ?1 and Artist like ?2")?;
-        delete_song_statement.execute(params![song.name, song.artist])?;
+            Ok(deleted)
+        })
+    }
 
-        Ok(())
+    pub fn select_song(
+        &self,
+        name: &str,
+        artist: &str,
+    ) -> Result<Option<Song>, Box<dyn std::error::Error>> {
+        self.select_song_by(
+            "select Name, Artist, Image_path, File_path from Songs \
+            where Name = ?1 and Artist = ?2 and Missing_since is null",
+            params![name, artist],
+        )
+    }
+
+    pub fn select_song_by_file_path(
+        &self,
+        file_path: &str,
+    ) -> Result<Option<Song>, Box<dyn std::error::Error>> {
+        self.select_song_by(
+            "select Name, Artist, Image_path, File_path from Songs where File_path = ?1 limit 1",
+            params![file_path],
+        )
+    }
+
+    fn select_song_by<P: Params>(
+        &self,
+        sql: &str,
+        params: P,
+    ) -> Result<Option<Song>, Box<dyn std::error::Error>> {
+        let connection = self.readers.get()?;
+
+        let mut select_song_statement = connection.prepare_cached(sql)?;
+
+        let mut iterator = select_song_statement.query_map(params, |row| {
+            let name: String = row.get(0)?;
+            let artist: String = row.get(1)?;
+            let image_path: Option<String> = row.get(2)?;
+            let file_path: String = row.get(3)?;
+            Ok(Song::new(name, artist, image_path, file_path))
+        })?;
+
+        match iterator.next() {
+            None => Ok(None),
+            Some(song) => Ok(Some(song?)),
+        }
+    }
+
+    pub fn select_file_id(
+        &self,
+        file_path: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let connection = self.readers.get()?;
+
+        let mut select_file_id_statement = connection.prepare_cached(
+            "select File_id from Songs where File_path = ?1 and File_id is not null limit 1",
+        )?;
+        let mut iterator =
+            select_file_id_statement.query_map(params![file_path], |row| row.get(0))?;
+
+        match iterator.next() {
+            None => Ok(None),
+            Some(file_id) => Ok(Some(file_id?)),
+        }
     }
 
-    pub fn insert_song(&self, song: Song) -> Result<(), Box<dyn std::error::Error>> {
-        let pool = self.connection_pool.clone();
+    pub fn update_folder_path(
+        &self,
+        from_prefix: &str,
+        to_prefix: &str,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let from_prefix = from_prefix.to_owned();
+        let to_prefix = to_prefix.to_owned();
+        self.write(move |connection| {
+            let mut update_path_statement = connection.prepare_cached(
+                "update Songs set File_path = ?2 || substr(File_path, length(?1) + 1) \
+                where substr(File_path, 1, length(?1)) = ?1",
+            )?;
+            let changed = update_path_statement.execute(params![from_prefix, to_prefix])?;
+
+            Ok(changed)
+        })
+    }
+
+    pub fn update_song(
+        &self,
+        name: &str,
+        artist: &str,
+        song: &Song,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let name = name.to_owned();
+        let artist = artist.to_owned();
+        let song = song.clone();
+        self.write(move |connection| {
+            let mut update_song_statement = connection.prepare_cached(
+                "update Songs set Name = ?3, Artist = ?4, File_path = ?5 \
+                where Name = ?1 and Artist = ?2",
+            )?;
+            let changed = update_song_statement.execute(params![
+                name,
+                artist,
+                song.name,
+                song.artist,
+                song.file_path
+            ])?;
+
+            Ok(changed > 0)
+        })
+    }
+
+    pub fn insert_song(&self, song: Song) -> Result<bool, Box<dyn std::error::Error>> {
+        let relinked = self.insert_songs(&[song])?;
+        Ok(relinked > 0)
+    }
 
-        let pool_lock = match pool.lock() {
-            Ok(lock) => lock,
-            Err(_) => return Err("Database pool poisoned".into()),
+    pub fn insert_songs(&self, songs: &[Song]) -> Result<usize, Box<dyn std::error::Error>> {
+        let songs = songs.to_vec();
+        self.write(move |connection| {
+            let transaction = connection.transaction()?;
+
+            let mut relinked = 0;
+            for song in &songs {
+                if SongsSystemDbContext::insert_song_with(&transaction, song)? {
+                    relinked += 1;
+                }
+            }
+
+            transaction.commit()?;
+            Ok(relinked)
+        })
+    }
+
+    fn insert_song_with(connection: &Connection, song: &Song) -> Result<bool, DbError> {
+        let relinked = match &song.file_id {
+            Some(file_id) => {
+                let mut relink_song_statement = connection.prepare_cached(
+                    "update or ignore Songs set Name = ?1, Artist = ?2, File_path = ?3 \
+                    where rowid = (select rowid from Songs \
+                        where File_id = ?4 and Missing_since is not null \
+                        order by Missing_since desc limit 1) \
+                    and not exists (select 1 from Songs where File_path = ?3)",
+                )?;
+                relink_song_statement.execute(params![
+                    song.name,
+                    song.artist,
+                    song.file_path,
+                    file_id
+                ])? > 0
+            }
+            None => false,
         };
 
-        let connection = pool_lock.get()?;
+        let mut rename_song_statement = connection.prepare_cached(
+            "update or ignore Songs set Name = ?1, Artist = ?2 \
+            where File_path = ?3 and (Name != ?1 or Artist != ?2)",
+        )?;
+        rename_song_statement.execute(params![song.name, song.artist, song.file_path])?;
 
-        let mut insert_song_statement = connection.prepare_cached(
-            "insert into Songs (Name, Artist, Image_path, File_path) \
-            values (?1, ?2, ?3, ?4) \
+        let stamp = song.file_stamp.as_ref();
+        let mut upsert_song_statement = connection.prepare_cached(
+            "insert into Songs (Name, Artist, Image_path, File_path, File_id, File_size, File_mtime) \
+            values (?1, ?2, ?3, ?4, ?5, ?6, ?7) \
             on conflict(Name,Artist) \
-            do update set Image_path=?3, File_path=?4",
+            do update set Image_path=coalesce(?3, Image_path), File_path=?4, Missing_since=null, \
+                File_id=coalesce(?5, File_id), File_size=coalesce(?6, File_size), \
+                File_mtime=coalesce(?7, File_mtime)",
         )?;
-        insert_song_statement.execute(params![
+        upsert_song_statement.execute(params![
             song.name,
             song.artist,
             song.image_path,
-            song.file_path
+            song.file_path,
+            song.file_id,
+            stamp.map(|stamp| stamp.size as i64),
+            stamp.map(|stamp| stamp.modified_at)
+        ])?;
+
+        let mut delete_leftovers_statement = connection.prepare_cached(
+            "delete from Songs where File_path = ?3 and (Name != ?1 or Artist != ?2)",
+        )?;
+        delete_leftovers_statement.execute(params![song.name, song.artist, song.file_path])?;
+
+        if let Some(file_id) = &song.file_id {
+            // Overrides follow the file they were set for, as it is renamed or moved
+            let mut update_override_path_statement = connection.prepare_cached(
+                "update MetadataOverrides set File_path = ?2 where File_id = ?1 and File_path != ?2",
+            )?;
+            update_override_path_statement.execute(params![file_id, song.file_path])?;
+
+            // Files re-tagged by other programs get a new identity, their override is found by path
+            let mut rekey_override_statement = connection.prepare_cached(
+                "update or ignore MetadataOverrides set File_id = ?1 \
+                where File_path = ?2 and File_id != ?1 \
+                and not exists (select 1 from MetadataOverrides where File_id = ?1) \
+                and File_id not in \
+                (select File_id from Songs where File_path != ?2 and File_id is not null)",
+            )?;
+            rekey_override_statement.execute(params![file_id, song.file_path])?;
+        }
+
+        if let Some(parsed) = &song.parsed {
+            let mut update_parsed_statement = connection.prepare_cached(
+                "update Songs set Album = coalesce(Album, ?3), Genre = coalesce(Genre, ?4), \
+                Year = coalesce(Year, ?5), Track = coalesce(Track, ?6) \
+                where Name = ?1 and Artist = ?2",
+            )?;
+            update_parsed_statement.execute(params![
+                song.name,
+                song.artist,
+                parsed.album,
+                parsed.genre,
+                parsed.year,
+                parsed.track
+            ])?;
+        }
+        if let Some(overrides) = &song.overrides {
+            let mut update_overrides_statement = connection.prepare_cached(
+                "update Songs set Album = coalesce(?3, Album), Genre = coalesce(?4, Genre), \
+                Year = coalesce(?5, Year), Track = coalesce(?6, Track) \
+                where Name = ?1 and Artist = ?2",
+            )?;
+            update_overrides_statement.execute(params![
+                song.name,
+                song.artist,
+                overrides.album,
+                overrides.genre,
+                overrides.year,
+                overrides.track
+            ])?;
+        }
+
+        Ok(relinked)
+    }
+
+    pub fn select_file_stamps(
+        &self,
+        path_prefix: &str,
+    ) -> Result<HashMap<String, Option<FileStamp>>, Box<dyn std::error::Error>> {
+        let connection = self.readers.get()?;
+
+        let mut select_stamps_statement = connection.prepare_cached(
+            "select File_path, File_size, File_mtime from Songs \
+            where substr(File_path, 1, length(?1)) = ?1 and Missing_since is null",
+        )?;
+
+        let output = select_stamps_statement
+            .query_map(params![path_prefix], |row| {
+                let file_path: String = row.get(0)?;
+                let size: Option<i64> = row.get(1)?;
+                let modified_at: Option<i64> = row.get(2)?;
+                let stamp = size.zip(modified_at).map(|(size, modified_at)| FileStamp {
+                    size: size as u64,
+                    modified_at,
+                });
+                Ok((file_path, stamp))
+            })?
+            .flatten()
+            .collect();
+
+        Ok(output)
+    }
+
+    pub fn update_songs_missing(
+        &self,
+        file_paths: &[String],
+        missing_since: i64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let file_paths = file_paths.to_vec();
+        self.write(move |connection| {
+            let transaction = connection.transaction()?;
+
+            {
+                let mut update_missing_statement = transaction.prepare_cached(
+                    "update Songs set Missing_since = ?2 where File_path = ?1 and Missing_since is null",
+                )?;
+                for file_path in file_paths {
+                    update_missing_statement.execute(params![file_path, missing_since])?;
+                }
+            }
+
+            transaction.commit()?;
+            Ok(())
+        })
+    }
+
+    pub fn update_song_details(
+        &self,
+        name: &str,
+        artist: &str,
+        details: &AudioTags,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let name = name.to_owned();
+        let artist = artist.to_owned();
+        let details = details.clone();
+        self.write(move |connection| {
+            let mut update_details_statement = connection.prepare_cached(
+                "update Songs set Album = ?3, Genre = ?4, Year = ?5, Track = ?6 \
+                where Name = ?1 and Artist = ?2",
+            )?;
+            update_details_statement.execute(params![
+                name,
+                artist,
+                details.album,
+                details.genre,
+                details.year,
+                details.track
+            ])?;
+
+            Ok(())
+        })
+    }
+
+    pub fn select_song_ratings(
+        &self,
+        user: &str,
+        filter: &RatingFilter,
+    ) -> Result<Vec<SongRating>, Box<dyn std::error::Error>> {
+        let connection = self.readers.get()?;
+
+        let mut select_ratings_statement = connection.prepare_cached(
+            "select Ratings.Name, Ratings.Artist, Ratings.Rating, Ratings.Favourite from Ratings \
+            join Songs on Songs.Name = Ratings.Name and Songs.Artist = Ratings.Artist \
+            where Ratings.User = ?1 and Songs.Missing_since is null \
+                and coalesce(Ratings.Rating, 0) >= ?2 \
+                and (not ?3 or Ratings.Favourite) \
+            order by case when ?4 then coalesce(Ratings.Rating, 0) else 0 end desc, \
+                Ratings.Artist, Ratings.Name",
+        )?;
+
+        let params = params![
+            user,
+            filter.min_rating,
+            filter.favourites_only,
+            filter.sort_by_rating
+        ];
+
+        let output = select_ratings_statement
+            .query_map(params, |row| {
+                let name: String = row.get(0)?;
+                let artist: String = row.get(1)?;
+                let rating: Option<u8> = row.get(2)?;
+                let favourite: bool = row.get(3)?;
+                Ok(SongRating::new(name, artist, rating, favourite))
+            })?
+            .flatten()
+            .collect();
+
+        Ok(output)
+    }
+
+    pub fn upsert_rating(
+        &self,
+        user: &str,
+        name: &str,
+        artist: &str,
+        rating: u8,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let user = user.to_owned();
+        let name = name.to_owned();
+        let artist = artist.to_owned();
+        self.write(move |connection| {
+            let mut upsert_rating_statement = connection.prepare_cached(
+                "insert into Ratings (User, Name, Artist, Rating) \
+                select ?1, Name, Artist, ?4 from Songs where Name like ?2 and Artist like ?3 limit 1 \
+                on conflict(User, Name, Artist) \
+                do update set Rating=?4",
+            )?;
+            let changed = upsert_rating_statement.execute(params![user, name, artist, rating])?;
+
+            if changed == 0 {
+                return Err("Could not find song to rate".into());
+            }
+            Ok(())
+        })
+    }
+
+    pub fn upsert_favourite(
+        &self,
+        user: &str,
+        name: &str,
+        artist: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let user = user.to_owned();
+        let name = name.to_owned();
+        let artist = artist.to_owned();
+        self.write(move |connection| {
+            let mut upsert_favourite_statement = connection.prepare_cached(
+                "insert into Ratings (User, Name, Artist, Favourite) \
+                select ?1, Name, Artist, 1 from Songs where Name like ?2 and Artist like ?3 limit 1 \
+                on conflict(User, Name, Artist) \
+                do update set Favourite=1",
+            )?;
+            let changed = upsert_favourite_statement.execute(params![user, name, artist])?;
+
+            if changed == 0 {
+                return Err("Could not find song to mark as favourite".into());
+            }
+            Ok(())
+        })
+    }
+
+    pub fn clear_rating(
+        &self,
+        user: &str,
+        name: &str,
+        artist: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let user = user.to_owned();
+        let name = name.to_owned();
+        let artist = artist.to_owned();
+        self.write(move |connection| {
+            let mut clear_rating_statement = connection.prepare_cached(
+                "update Ratings set Rating = null \
+                where User = ?1 and Name like ?2 and Artist like ?3",
+            )?;
+            clear_rating_statement.execute(params![user, name, artist])?;
+
+            Self::delete_empty_ratings(connection)
+        })
+    }
+
+    pub fn clear_favourite(
+        &self,
+        user: &str,
+        name: &str,
+        artist: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let user = user.to_owned();
+        let name = name.to_owned();
+        let artist = artist.to_owned();
+        self.write(move |connection| {
+            let mut clear_favourite_statement = connection.prepare_cached(
+                "update Ratings set Favourite = 0 \
+                where User = ?1 and Name like ?2 and Artist like ?3",
+            )?;
+            clear_favourite_statement.execute(params![user, name, artist])?;
+
+            Self::delete_empty_ratings(connection)
+        })
+    }
+
+    pub fn insert_play(&self, play: PlayEvent) -> Result<(), Box<dyn std::error::Error>> {
+        self.write(move |connection| {
+            let transaction = connection.transaction()?;
+
+            let changed = transaction.execute(
+                "insert into Plays (User, Name, Artist, Started_at, Listened_ms, Completed) \
+                select ?1, Name, Artist, ?4, ?5, ?6 from Songs \
+                where Name like ?2 and Artist like ?3 limit 1",
+                params![
+                    play.user,
+                    play.name,
+                    play.artist,
+                    play.started_at,
+                    play.listened_ms as i64,
+                    play.completed
+                ],
+            )?;
+
+            if changed == 0 {
+                return Err("Could not find played song".into());
+            }
+
+            // Only completed plays count, skips are kept in the history alone
+            if play.completed {
+                transaction.execute(
+                    "update Songs set Play_count = Play_count + 1 \
+                    where (Name, Artist) = (select Name, Artist from Plays where rowid = ?1)",
+                    params![transaction.last_insert_rowid()],
+                )?;
+            }
+
+            transaction.commit()?;
+            Ok(())
+        })
+    }
+
+    pub fn select_recently_played(
+        &self,
+        user: &str,
+        since: i64,
+        count: usize,
+    ) -> Result<Vec<SongPlays>, Box<dyn std::error::Error>> {
+        self.select_song_plays(
+            "select Plays.Name, Plays.Artist, \
+            sum(Plays.Completed and Plays.Started_at >= ?3), max(Plays.Started_at) \
+            from Plays \
+            join Songs on Songs.Name = Plays.Name and Songs.Artist = Plays.Artist \
+            where (?1 = '' or Plays.User = ?1) and Songs.Missing_since is null \
+            group by Plays.Name, Plays.Artist \
+            order by max(Started_at) desc \
+            limit ?2",
+            params![user, count as i64, since],
+        )
+    }
+
+    pub fn select_most_played(
+        &self,
+        user: &str,
+        from: i64,
+        to: i64,
+        count: usize,
+    ) -> Result<Vec<SongPlays>, Box<dyn std::error::Error>> {
+        self.select_song_plays(
+            "select Plays.Name, Plays.Artist, sum(Plays.Completed), max(Plays.Started_at) \
+            from Plays \
+            join Songs on Songs.Name = Plays.Name and Songs.Artist = Plays.Artist \
+            where (?1 = '' or Plays.User = ?1) and Songs.Missing_since is null \
+                and Plays.Started_at >= ?2 and Plays.Started_at < ?3 \
+            group by Plays.Name, Plays.Artist \
+            having sum(Plays.Completed) > 0 \
+            order by sum(Plays.Completed) desc, max(Plays.Started_at) desc \
+            limit ?4",
+            params![user, from, to, count as i64],
+        )
+    }
+
+    pub fn select_never_played(
+        &self,
+        user: &str,
+        count: usize,
+    ) -> Result<Vec<SongPlays>, Box<dyn std::error::Error>> {
+        self.select_song_plays(
+            "select Name, Artist, 0, null from Songs \
+            where Missing_since is null and (Play_count = 0 or (?1 != '' and not exists ( \
+                select 1 from Plays where Plays.Name = Songs.Name and Plays.Artist = Songs.Artist \
+                    and Plays.User = ?1 and Plays.Completed))) \
+            order by Artist, Name \
+            limit ?2",
+            params![user, count as i64],
+        )
+    }
+
+    fn select_song_plays<P: Params>(
+        &self,
+        sql: &str,
+        params: P,
+    ) -> Result<Vec<SongPlays>, Box<dyn std::error::Error>> {
+        let connection = self.readers.get()?;
+
+        let mut select_plays_statement = connection.prepare_cached(sql)?;
+
+        let output = select_plays_statement
+            .query_map(params, |row| {
+                let name: String = row.get(0)?;
+                let artist: String = row.get(1)?;
+                let play_count: i64 = row.get(2)?;
+                let last_played_at: Option<i64> = row.get(3)?;
+                Ok(SongPlays::new(
+                    name,
+                    artist,
+                    play_count as u64,
+                    last_played_at,
+                ))
+            })?
+            .flatten()
+            .collect();
+
+        Ok(output)
+    }
+
+    pub fn upsert_playback_state(
+        &self,
+        state: &PlaybackState,
+    ) -> Result<PlaybackStateSave, Box<dyn std::error::Error>> {
+        let state = state.clone();
+        self.write(move |connection| {
+            let transaction = connection.transaction()?;
+
+            // Last writer wins, older states are ignored
+            let changed = transaction.execute(
+                "insert into PlaybackStates (User, Device, Queue_index, Position_ms, Playing, Updated_at) \
+                values (?1, ?2, ?3, ?4, ?5, ?6) \
+                on conflict(User) \
+                do update set Device=excluded.Device, Queue_index=excluded.Queue_index, \
+                    Position_ms=excluded.Position_ms, Playing=excluded.Playing, \
+                    Updated_at=excluded.Updated_at \
+                where excluded.Updated_at > PlaybackStates.Updated_at",
+                params![
+                    state.user,
+                    state.device,
+                    state.index,
+                    state.position_ms as i64,
+                    state.playing,
+                    state.updated_at
+                ],
+            )?;
+
+            if changed == 0 {
+                return Ok(PlaybackStateSave::Stale);
+            }
+
+            transaction.execute(
+                "delete from PlaybackQueues where User = ?1",
+                params![state.user],
+            )?;
+            {
+                let mut insert_queued_song_statement = transaction.prepare_cached(
+                    "insert into PlaybackQueues (User, Position, Name, Artist) \
+                    select ?1, ?2, Name, Artist from Songs where Name like ?3 and Artist like ?4 limit 1",
+                )?;
+                for (position, song) in state.queue.iter().enumerate() {
+                    let inserted = insert_queued_song_statement.execute(params![
+                        state.user,
+                        position as i64,
+                        song.name,
+                        song.artist
+                    ])?;
+                    if inserted == 0 {
+                        return Ok(PlaybackStateSave::UnknownSong);
+                    }
+                }
+            }
+
+            transaction.commit()?;
+            Ok(PlaybackStateSave::Saved)
+        })
+    }
+
+    pub fn select_playback_state(
+        &self,
+        user: &str,
+    ) -> Result<Option<PlaybackState>, Box<dyn std::error::Error>> {
+        let connection = self.readers.get()?;
+
+        let mut select_state_statement = connection.prepare_cached(
+            "select Device, Queue_index, Position_ms, Playing, Updated_at \
+            from PlaybackStates where User = ?1",
+        )?;
+        let mut states = select_state_statement.query_map(params![user], |row| {
+            let device: String = row.get(0)?;
+            let index: u32 = row.get(1)?;
+            let position_ms: i64 = row.get(2)?;
+            let playing: bool = row.get(3)?;
+            let updated_at: i64 = row.get(4)?;
+            Ok((device, index, position_ms as u64, playing, updated_at))
+        })?;
+        let (device, index, position_ms, playing, updated_at) = match states.next() {
+            None => return Ok(None),
+            Some(state) => state?,
+        };
+
+        let mut select_queue_statement = connection.prepare_cached(
+            "select Name, Artist from PlaybackQueues where User = ?1 order by Position",
+        )?;
+        let queue = select_queue_statement
+            .query_map(params![user], |row| {
+                let name: String = row.get(0)?;
+                let artist: String = row.get(1)?;
+                Ok(QueuedSong::new(name, artist))
+            })?
+            .flatten()
+            .collect();
+
+        Ok(Some(PlaybackState::new(
+            user.to_string(),
+            device,
+            queue,
+            index,
+            position_ms,
+            playing,
+            updated_at,
+        )))
+    }
+
+    pub fn insert_upload_session(
+        &self,
+        session: &UploadSession,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let session = session.clone();
+        self.write(move |connection| {
+            let mut insert_session_statement = connection.prepare_cached(
+                "insert into UploadSessions (Id, Name, Artist, Format, Size, Sha256, Offset, Expires_at) \
+                values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?;
+            insert_session_statement.execute(params![
+                session.id,
+                session.name,
+                session.artist,
+                session.format,
+                session.size as i64,
+                session.sha256,
+                session.offset as i64,
+                session.expires_at
+            ])?;
+
+            Ok(())
+        })
+    }
+
+    pub fn select_upload_session(
+        &self,
+        id: &str,
+    ) -> Result<Option<UploadSession>, Box<dyn std::error::Error>> {
+        let connection = self.readers.get()?;
+
+        let mut select_session_statement = connection.prepare_cached(
+            "select Id, Name, Artist, Format, Size, Sha256, Offset, Expires_at \
+            from UploadSessions where Id = ?1",
+        )?;
+
+        let mut iterator = select_session_statement.query_map(params![id], |row| {
+            let id: String = row.get(0)?;
+            let name: String = row.get(1)?;
+            let artist: String = row.get(2)?;
+            let format: String = row.get(3)?;
+            let size: i64 = row.get(4)?;
+            let sha256: String = row.get(5)?;
+            let offset: i64 = row.get(6)?;
+            let expires_at: i64 = row.get(7)?;
+            Ok(UploadSession::new(
+                id,
+                name,
+                artist,
+                format,
+                size as u64,
+                sha256,
+                offset as u64,
+                expires_at,
+            ))
+        })?;
+
+        match iterator.next() {
+            None => Ok(None),
+            Some(session) => Ok(Some(session?)),
+        }
+    }
+
+    pub fn update_upload_offset(
+        &self,
+        id: &str,
+        offset: u64,
+        expires_at: i64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let id = id.to_owned();
+        self.write(move |connection| {
+            let mut update_offset_statement = connection.prepare_cached(
+                "update UploadSessions set Offset = ?2, Expires_at = ?3 where Id = ?1",
+            )?;
+            update_offset_statement.execute(params![id, offset as i64, expires_at])?;
+
+            Ok(())
+        })
+    }
+
+    pub fn delete_upload_session(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let id = id.to_owned();
+        self.write(move |connection| {
+            let mut delete_session_statement =
+                connection.prepare_cached("delete from UploadSessions where Id = ?1")?;
+            delete_session_statement.execute(params![id])?;
+
+            Ok(())
+        })
+    }
+
+    pub fn select_upload_session_ids(
+        &self,
+        expired_before: i64,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let connection = self.readers.get()?;
+
+        let mut select_ids_statement =
+            connection.prepare_cached("select Id from UploadSessions where Expires_at < ?1")?;
+
+        let output = select_ids_statement
+            .query_map(params![expired_before], |row| row.get(0))?
+            .flatten()
+            .collect();
+
+        Ok(output)
+    }
+
+    pub fn update_song_tags(
+        &self,
+        name: &str,
+        artist: &str,
+        tags: &AudioTags,
+        image_path: Option<&str>,
+        previous: &AudioTags,
+        edited_at: i64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let name = name.to_owned();
+        let artist = artist.to_owned();
+        let tags = tags.clone();
+        let image_path = image_path.map(String::from);
+        let previous = previous.clone();
+        self.write(move |connection| {
+            let transaction = connection.transaction()?;
+
+            SongsSystemDbContext::update_tag_columns(
+                &transaction,
+                &name,
+                &artist,
+                &tags,
+                image_path.as_deref(),
+            )?;
+
+            let cover = previous.cover.as_ref();
+            transaction.execute(
+                "insert into TagEdits (Name, Artist, Edited_at, Title, Tag_artist, Album, Genre, \
+                Year, Track, Cover_mime, Cover) \
+                values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    name,
+                    artist,
+                    edited_at,
+                    previous.title,
+                    previous.artist,
+                    previous.album,
+                    previous.genre,
+                    previous.year,
+                    previous.track,
+                    cover.map(|cover| &cover.mime_type),
+                    cover.map(|cover| &cover.data)
+                ],
+            )?;
+            transaction.execute(
+                "delete from TagEdits where Name = ?1 and Artist = ?2 and Id not in \
+                (select Id from TagEdits where Name = ?1 and Artist = ?2 order by Id desc limit ?3)",
+                params![name, artist, MAX_TAG_EDITS],
+            )?;
+
+            transaction.commit()?;
+            Ok(())
+        })
+    }
+
+    pub fn undo_song_tags(
+        &self,
+        edit_id: i64,
+        name: &str,
+        artist: &str,
+        tags: &AudioTags,
+        image_path: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let name = name.to_owned();
+        let artist = artist.to_owned();
+        let tags = tags.clone();
+        let image_path = image_path.map(String::from);
+        self.write(move |connection| {
+            let transaction = connection.transaction()?;
+
+            let changed =
+                transaction.execute("delete from TagEdits where Id = ?1", params![edit_id])?;
+            if changed == 0 {
+                return Err("Tag edit was already undone".into());
+            }
+            SongsSystemDbContext::update_tag_columns(
+                &transaction,
+                &name,
+                &artist,
+                &tags,
+                image_path.as_deref(),
+            )?;
+
+            transaction.commit()?;
+            Ok(())
+        })
+    }
+
+    pub fn select_tag_edits(
+        &self,
+        name: &str,
+        artist: &str,
+        count: usize,
+    ) -> Result<Vec<TagEdit>, Box<dyn std::error::Error>> {
+        let connection = self.readers.get()?;
+
+        let mut select_edits_statement = connection.prepare_cached(
+            "select Id, Edited_at, Title, Tag_artist, Album, Genre, Year, Track, Cover_mime, Cover \
+            from TagEdits where Name = ?1 and Artist = ?2 \
+            order by Id desc limit ?3",
+        )?;
+
+        let output = select_edits_statement
+            .query_map(params![name, artist, count as i64], |row| {
+                let id: i64 = row.get(0)?;
+                let edited_at: i64 = row.get(1)?;
+                let cover_mime: Option<String> = row.get(8)?;
+                let cover: Option<Vec<u8>> = row.get(9)?;
+                let previous = AudioTags {
+                    title: row.get(2)?,
+                    artist: row.get(3)?,
+                    album: row.get(4)?,
+                    genre: row.get(5)?,
+                    year: row.get(6)?,
+                    track: row.get(7)?,
+                    cover: cover_mime
+                        .zip(cover)
+                        .map(|(mime_type, data)| Cover { mime_type, data }),
+                    ..AudioTags::default()
+                };
+                Ok(TagEdit::new(id, edited_at, previous))
+            })?
+            .flatten()
+            .collect();
+
+        Ok(output)
+    }
+
+    pub fn count_metadata_overrides(&self) -> Result<i64, Box<dyn std::error::Error>> {
+        let connection = self.readers.get()?;
+
+        let mut count_overrides_statement =
+            connection.prepare_cached("select count(*) from MetadataOverrides")?;
+        let count = count_overrides_statement.query_row([], |row| row.get(0))?;
+
+        Ok(count)
+    }
+
+    pub fn select_metadata_override(
+        &self,
+        file_id: &str,
+    ) -> Result<Option<MetadataOverride>, Box<dyn std::error::Error>> {
+        let overrides = self.select_metadata_overrides_by(
+            "select File_id, File_path, Title, Artist, Album, Genre, Year, Track \
+            from MetadataOverrides where File_id = ?1",
+            params![file_id],
+        )?;
+        Ok(overrides.into_iter().next())
+    }
+
+    pub fn select_metadata_override_at(
+        &self,
+        file_path: &str,
+    ) -> Result<Option<MetadataOverride>, Box<dyn std::error::Error>> {
+        // Not the override of a file that still lives elsewhere
+        let overrides = self.select_metadata_overrides_by(
+            "select File_id, File_path, Title, Artist, Album, Genre, Year, Track \
+            from MetadataOverrides where File_path = ?1 and File_id not in \
+            (select File_id from Songs where File_path != ?1 and File_id is not null)",
+            params![file_path],
+        )?;
+        Ok(overrides.into_iter().next())
+    }
+
+    pub fn select_metadata_overrides(
+        &self,
+    ) -> Result<Vec<MetadataOverride>, Box<dyn std::error::Error>> {
+        self.select_metadata_overrides_by(
+            "select File_id, File_path, Title, Artist, Album, Genre, Year, Track \
+            from MetadataOverrides order by File_path",
+            params![],
+        )
+    }
+
+    fn select_metadata_overrides_by<P: Params>(
+        &self,
+        sql: &str,
+        params: P,
+    ) -> Result<Vec<MetadataOverride>, Box<dyn std::error::Error>> {
+        let connection = self.readers.get()?;
+
+        let mut select_overrides_statement = connection.prepare_cached(sql)?;
+
+        let output = select_overrides_statement
+            .query_map(params, |row| {
+                let file_id: String = row.get(0)?;
+                let file_path: String = row.get(1)?;
+                let values = AudioTags {
+                    title: row.get(2)?,
+                    artist: row.get(3)?,
+                    album: row.get(4)?,
+                    genre: row.get(5)?,
+                    year: row.get(6)?,
+                    track: row.get(7)?,
+                    ..AudioTags::default()
+                };
+                Ok(MetadataOverride::new(file_id, file_path, values))
+            })?
+            .flatten()
+            .collect();
+
+        Ok(output)
+    }
+
+    pub fn upsert_metadata_override(
+        &self,
+        metadata_override: &MetadataOverride,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let metadata_override = metadata_override.clone();
+        self.write(move |connection| {
+            let values = &metadata_override.values;
+            let mut upsert_override_statement = connection.prepare_cached(
+                "insert into MetadataOverrides \
+                (File_id, File_path, Title, Artist, Album, Genre, Year, Track) \
+                values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8) \
+                on conflict(File_id) do update set File_path = ?2, Title = ?3, Artist = ?4, \
+                Album = ?5, Genre = ?6, Year = ?7, Track = ?8",
+            )?;
+            upsert_override_statement.execute(params![
+                metadata_override.file_id,
+                metadata_override.file_path,
+                values.title,
+                values.artist,
+                values.album,
+                values.genre,
+                values.year,
+                values.track
+            ])?;
+
+            Ok(())
+        })
+    }
+
+    pub fn update_song_file_id(
+        &self,
+        name: &str,
+        artist: &str,
+        file_id: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let name = name.to_owned();
+        let artist = artist.to_owned();
+        let file_id = file_id.to_owned();
+        self.write(move |connection| {
+            let transaction = connection.transaction()?;
+
+            // Another file with the same identity keeps the override it has
+            transaction.execute(
+                "update or ignore MetadataOverrides set File_id = ?3 \
+                where File_id = (select File_id from Songs where Name = ?1 and Artist = ?2) \
+                and File_id != ?3",
+                params![name, artist, file_id],
+            )?;
+            transaction.execute(
+                "update Songs set File_id = ?3 where Name = ?1 and Artist = ?2",
+                params![name, artist, file_id],
+            )?;
+
+            transaction.commit()?;
+            Ok(())
+        })
+    }
+
+    pub fn delete_metadata_override(
+        &self,
+        file_id: &str,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let file_id = file_id.to_owned();
+        self.write(move |connection| {
+            let mut delete_override_statement =
+                connection.prepare_cached("delete from MetadataOverrides where File_id = ?1")?;
+            let changed = delete_override_statement.execute(params![file_id])?;
+
+            Ok(changed > 0)
+        })
+    }
+
+    pub fn select_scan_issues(&self) -> Result<Vec<ScanIssue>, Box<dyn std::error::Error>> {
+        let connection = self.readers.get()?;
+
+        let mut select_issues_statement = connection.prepare_cached(
+            "select File_path, Reason, Detail, Found_at from ScanIssues order by File_path",
+        )?;
+
+        let output = select_issues_statement
+            .query_map([], |row| {
+                let file_path: String = row.get(0)?;
+                let reason: String = row.get(1)?;
+                let detail: String = row.get(2)?;
+                let found_at: i64 = row.get(3)?;
+                Ok((file_path, reason, detail, found_at))
+            })?
+            .flatten()
+            .filter_map(|(file_path, reason, detail, found_at)| {
+                let reason = IssueReason::parse(&reason)?;
+                Some(ScanIssue::new(file_path, reason, detail, found_at))
+            })
+            .collect();
+
+        Ok(output)
+    }
+
+    pub fn replace_scan_issues(
+        &self,
+        path_prefix: &str,
+        issues: &[ScanIssue],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let path_prefix = path_prefix.to_owned();
+        let issues = issues.to_vec();
+        self.write(move |connection| {
+            let transaction = connection.transaction()?;
+
+            transaction
+                .prepare_cached(
+                    "delete from ScanIssues where substr(File_path, 1, length(?1)) = ?1",
+                )?
+                .execute(params![path_prefix])?;
+            for issue in &issues {
+                SongsSystemDbContext::upsert_scan_issue_with(&transaction, issue)?;
+            }
+
+            transaction.commit()?;
+            Ok(())
+        })
+    }
+
+    pub fn upsert_scan_issue(&self, issue: &ScanIssue) -> Result<(), Box<dyn std::error::Error>> {
+        let issue = issue.clone();
+        self.write(move |connection| {
+            SongsSystemDbContext::upsert_scan_issue_with(connection, &issue)
+        })
+    }
+
+    fn upsert_scan_issue_with(connection: &Connection, issue: &ScanIssue) -> Result<(), DbError> {
+        let mut upsert_issue_statement = connection.prepare_cached(
+            "insert into ScanIssues (File_path, Reason, Detail, Found_at) values (?1, ?2, ?3, ?4) \
+            on conflict(File_path) do update set Reason = ?2, Detail = ?3, Found_at = ?4",
+        )?;
+        upsert_issue_statement.execute(params![
+            issue.file_path,
+            issue.reason.to_string(),
+            issue.detail,
+            issue.found_at
         ])?;
 
         Ok(())
     }
+
+    pub fn delete_scan_issues(&self, file_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let file_path = file_path.to_owned();
+        self.write(move |connection| {
+            let mut delete_issues_statement = connection.prepare_cached(
+                "delete from ScanIssues where ?1 = '' or File_path = ?1 \
+                or substr(File_path, 1, length(?1) + 1) = ?1 || '/'",
+            )?;
+            delete_issues_statement.execute(params![file_path])?;
+
+            Ok(())
+        })
+    }
+
+    fn update_tag_columns(
+        connection: &Connection,
+        name: &str,
+        artist: &str,
+        tags: &AudioTags,
+        image_path: Option<&str>,
+    ) -> Result<(), DbError> {
+        let mut update_tags_statement = connection.prepare_cached(
+            "update Songs set Album = ?3, Genre = ?4, Year = ?5, Track = ?6, Image_path = ?7 \
+            where Name = ?1 and Artist = ?2",
+        )?;
+        let changed = update_tags_statement.execute(params![
+            name, artist, tags.album, tags.genre, tags.year, tags.track, image_path
+        ])?;
+        if changed == 0 {
+            return Err("Could not find tagged song".into());
+        }
+        Ok(())
+    }
+
+    fn delete_empty_ratings(connection: &Connection) -> Result<(), DbError> {
+        let mut delete_ratings_statement = connection
+            .prepare_cached("delete from Ratings where Rating is null and Favourite = 0")?;
+        delete_ratings_statement.execute([])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writer_outlives_a_panicking_write() {
+        let folder = tempfile::tempdir().unwrap();
+        let db_path = folder.path().join("files_database.sqlite");
+        let context = SongsSystemDbContext::new(db_path.to_str().unwrap()).unwrap();
+
+        let panicked = context.write(|_| -> Result<(), DbError> { panic!("write failed") });
+        assert!(panicked.is_err());
+        let written = context.write(|connection| {
+            Ok(connection.query_row("select 1", [], |row| row.get::<_, i64>(0))?)
+        });
+        assert_eq!(written.ok(), Some(1));
+    }
+
+    #[test]
+    fn overrides_are_found_by_path_once_the_file_was_retagged() {
+        let folder = tempfile::tempdir().unwrap();
+        let db_path = folder.path().join("files_database.sqlite");
+        let context = SongsSystemDbContext::new(db_path.to_str().unwrap()).unwrap();
+        let song_at = |file_path: &str, file_id: &str| {
+            let mut song = Song::new(
+                String::from(file_path),
+                String::from("Artist"),
+                None,
+                String::from(file_path),
+            );
+            song.file_id = Some(String::from(file_id));
+            song
+        };
+        let values = AudioTags {
+            title: Some(String::from("Title")),
+            ..AudioTags::default()
+        };
+        context.insert_song(song_at("a.mp3", "old")).unwrap();
+        context.insert_song(song_at("b.mp3", "other")).unwrap();
+        for (file_id, file_path) in [("old", "a.mp3"), ("other", "elsewhere.mp3")] {
+            let metadata_override =
+                MetadataOverride::new(file_id.into(), file_path.into(), values.clone());
+            context
+                .upsert_metadata_override(&metadata_override)
+                .unwrap();
+        }
+
+        let found = context.select_metadata_override_at("a.mp3").unwrap();
+        assert_eq!(found.map(|found| found.file_id), Some(String::from("old")));
+        context.insert_song(song_at("a.mp3", "new")).unwrap();
+        assert!(context.select_metadata_override("old").unwrap().is_none());
+        assert!(context.select_metadata_override("new").unwrap().is_some());
+
+        // The override of a file that lives at another path stays with it
+        context
+            .insert_song(song_at("elsewhere.mp3", "stranger"))
+            .unwrap();
+        assert!(context
+            .select_metadata_override_at("elsewhere.mp3")
+            .unwrap()
+            .is_none());
+        assert!(context.select_metadata_override("other").unwrap().is_some());
+    }
 }
@@ -0,0 +1,14 @@
+use crate::core::utils::audio_tags::AudioTags;
+
+#[derive(Clone)]
+pub struct MetadataOverride {
+    pub file_id: String,
+    pub file_path: String,
+    pub values: AudioTags,
+}
+
+impl MetadataOverride {
+    pub fn new(file_id: String, file_path: String, values: AudioTags) -> Self {
+        MetadataOverride { file_id, file_path, values }
+    }
+}
\ No newline at end of file
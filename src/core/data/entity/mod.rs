@@ -1,2 +1,13 @@
+pub mod metadata_override;
+pub mod play_event;
+pub mod playback_state;
+pub mod queued_song;
+pub mod rating_filter;
+pub mod scan_issue;
 pub mod song;
-pub mod song_info;
\ No newline at end of file
+pub mod song_info;
+pub mod song_plays;
+pub mod song_rating;
+pub mod tag_edit;
+pub mod trashed_song;
+pub mod upload_session;
\ No newline at end of file
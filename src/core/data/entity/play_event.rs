@@ -0,0 +1,21 @@
+pub struct PlayEvent {
+    pub user: String,
+    pub name: String,
+    pub artist: String,
+    pub started_at: i64,
+    pub listened_ms: u64,
+    pub completed: bool,
+}
+
+impl PlayEvent {
+    pub fn new(
+        user: String,
+        name: String,
+        artist: String,
+        started_at: i64,
+        listened_ms: u64,
+        completed: bool,
+    ) -> Self {
+        PlayEvent { user, name, artist, started_at, listened_ms, completed }
+    }
+}
\ No newline at end of file
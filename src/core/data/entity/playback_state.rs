@@ -0,0 +1,34 @@
+use crate::core::data::entity::queued_song::QueuedSong;
+
+#[derive(Clone)]
+pub struct PlaybackState {
+    pub user: String,
+    pub device: String,
+    pub queue: Vec<QueuedSong>,
+    pub index: u32,
+    pub position_ms: u64,
+    pub playing: bool,
+    pub updated_at: i64,
+}
+
+impl PlaybackState {
+    pub fn new(
+        user: String,
+        device: String,
+        queue: Vec<QueuedSong>,
+        index: u32,
+        position_ms: u64,
+        playing: bool,
+        updated_at: i64,
+    ) -> Self {
+        PlaybackState { user, device, queue, index, position_ms, playing, updated_at }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlaybackStateSave {
+    Saved,
+    // Older than the stored state
+    Stale,
+    UnknownSong,
+}
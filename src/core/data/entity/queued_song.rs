@@ -0,0 +1,11 @@
+#[derive(Clone)]
+pub struct QueuedSong {
+    pub name: String,
+    pub artist: String,
+}
+
+impl QueuedSong {
+    pub fn new(name: String, artist: String) -> Self {
+        QueuedSong { name, artist }
+    }
+}
\ No newline at end of file
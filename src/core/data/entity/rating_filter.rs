@@ -0,0 +1,11 @@
+pub struct RatingFilter {
+    pub min_rating: u8,
+    pub favourites_only: bool,
+    pub sort_by_rating: bool,
+}
+
+impl RatingFilter {
+    pub fn new(min_rating: u8, favourites_only: bool, sort_by_rating: bool) -> Self {
+        RatingFilter { min_rating, favourites_only, sort_by_rating }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,15 @@
+use crate::core::utils::audio_checks::IssueReason;
+
+#[derive(Clone)]
+pub struct ScanIssue {
+    pub file_path: String,
+    pub reason: IssueReason,
+    pub detail: String,
+    pub found_at: i64,
+}
+
+impl ScanIssue {
+    pub fn new(file_path: String, reason: IssueReason, detail: String, found_at: i64) -> Self {
+        ScanIssue { file_path, reason, detail, found_at }
+    }
+}
\ No newline at end of file
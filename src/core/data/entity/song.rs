@@ -1,12 +1,22 @@
+use crate::core::utils::audio_tags::AudioTags;
+use crate::core::utils::file_identity::FileStamp;
+
+#[derive(Clone)]
 pub struct Song {
     pub name: String,
     pub artist: String,
     pub image_path: Option<String>,
     pub file_path: String,
+    pub file_id: Option<String>,
+    pub file_stamp: Option<FileStamp>,
+    // Values that win over the ones of the file, set while scanning
+    pub overrides: Option<AudioTags>,
+    // Values read from the file or its path, kept only where the database has none
+    pub parsed: Option<AudioTags>,
 }
 
 impl Song {
     pub fn new(name: String, artist: String, image_path: Option<String>, file_path: String) -> Self {
-        Song { name, artist, image_path, file_path }
+        Song { name, artist, image_path, file_path, file_id: None, file_stamp: None, overrides: None, parsed: None }
     }
 }
\ No newline at end of file
@@ -2,10 +2,20 @@ pub struct SongInfo {
     pub name: String,
     pub artist: String,
     pub image_path: Option<String>,
+    pub file_path: String,
+    pub rating: Option<u8>,
+    pub favourite: bool,
 }
 
 impl SongInfo {
-    pub fn new(name: String, artist: String, image_path: Option<String>) -> Self {
-        SongInfo { name, artist, image_path }
+    pub fn new(
+        name: String,
+        artist: String,
+        image_path: Option<String>,
+        file_path: String,
+        rating: Option<u8>,
+        favourite: bool,
+    ) -> Self {
+        SongInfo { name, artist, image_path, file_path, rating, favourite }
     }
 }
\ No newline at end of file
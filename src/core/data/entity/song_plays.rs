@@ -0,0 +1,12 @@
+pub struct SongPlays {
+    pub name: String,
+    pub artist: String,
+    pub play_count: u64,
+    pub last_played_at: Option<i64>,
+}
+
+impl SongPlays {
+    pub fn new(name: String, artist: String, play_count: u64, last_played_at: Option<i64>) -> Self {
+        SongPlays { name, artist, play_count, last_played_at }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,12 @@
+pub struct SongRating {
+    pub name: String,
+    pub artist: String,
+    pub rating: Option<u8>,
+    pub favourite: bool,
+}
+
+impl SongRating {
+    pub fn new(name: String, artist: String, rating: Option<u8>, favourite: bool) -> Self {
+        SongRating { name, artist, rating, favourite }
+    }
+}
\ No newline at end of file
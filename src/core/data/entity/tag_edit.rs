@@ -0,0 +1,13 @@
+use crate::core::utils::audio_tags::AudioTags;
+
+pub struct TagEdit {
+    pub id: i64,
+    pub edited_at: i64,
+    pub previous: AudioTags,
+}
+
+impl TagEdit {
+    pub fn new(id: i64, edited_at: i64, previous: AudioTags) -> Self {
+        TagEdit { id, edited_at, previous }
+    }
+}
\ No newline at end of file
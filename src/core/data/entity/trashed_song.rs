@@ -0,0 +1,12 @@
+pub struct TrashedSong {
+    pub name: String,
+    pub artist: String,
+    pub file_path: String,
+    pub missing_since: i64,
+}
+
+impl TrashedSong {
+    pub fn new(name: String, artist: String, file_path: String, missing_since: i64) -> Self {
+        TrashedSong { name, artist, file_path, missing_since }
+    }
+}
\ No newline at end of file
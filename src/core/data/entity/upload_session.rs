@@ -0,0 +1,27 @@
+#[derive(Clone)]
+pub struct UploadSession {
+    pub id: String,
+    pub name: String,
+    pub artist: String,
+    pub format: String,
+    pub size: u64,
+    pub sha256: String,
+    pub offset: u64,
+    pub expires_at: i64,
+}
+
+impl UploadSession {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        id: String,
+        name: String,
+        artist: String,
+        format: String,
+        size: u64,
+        sha256: String,
+        offset: u64,
+        expires_at: i64,
+    ) -> Self {
+        UploadSession { id, name, artist, format, size, sha256, offset, expires_at }
+    }
+}
\ No newline at end of file
@@ -1,2 +1,3 @@
 pub mod data;
-pub mod repository;
\ No newline at end of file
+pub mod repository;
+pub mod utils;
\ No newline at end of file
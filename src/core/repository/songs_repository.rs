@@ -1,12 +1,38 @@
 use crate::config::CONFIG;
 use crate::core::data::context::songs_system_db_context::SongsSystemDbContext;
+use crate::core::data::entity::metadata_override::MetadataOverride;
+use crate::core::data::entity::play_event::PlayEvent;
+use crate::core::data::entity::playback_state::{PlaybackState, PlaybackStateSave};
+use crate::core::data::entity::rating_filter::RatingFilter;
+use crate::core::data::entity::scan_issue::ScanIssue;
 use crate::core::data::entity::song::Song;
 use crate::core::data::entity::song_info::SongInfo;
-use itertools::Itertools;
+use crate::core::data::entity::song_plays::SongPlays;
+use crate::core::data::entity::song_rating::SongRating;
+use crate::core::data::entity::tag_edit::TagEdit;
+use crate::core::data::entity::trashed_song::TrashedSong;
+use crate::core::data::entity::upload_session::UploadSession;
+use crate::core::utils::audio_checks::{check_audio_file, IssueReason};
+use crate::core::utils::audio_tags::{self, AudioTags};
+use crate::core::utils::file_identity::{file_identity, file_stamp};
+use crate::core::utils::filename_templates::match_templates;
+use crate::core::utils::folder_overrides::read_folder_overrides;
+use crate::core::utils::ignore_rules::IgnoreRules;
+use crate::core::utils::scan_progress::ScanProgress;
+use crate::core::utils::storage;
+use crate::core::utils::worker_pool::map_parallel;
+use std::collections::HashMap;
 use std::ffi::OsStr;
+use std::fs::File;
+use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::{fs, process};
 
+const UNKNOWN_ARTIST: &str = "Unknown artist";
+
+const SCAN_BATCH_SIZE: usize = 500;
+
 lazy_static! {
     pub static ref SONGS_REPOSITORY: SongsRepository = SongsRepository::new();
 }
@@ -20,8 +46,8 @@ impl SongsRepository {
         let db_path = &CONFIG.files_database_path;
         let songs_db_context = match SongsSystemDbContext::new(db_path) {
             Ok(context) => context,
-            Err(_) => {
-                eprintln!("Could not create db context");
+            Err(e) => {
+                eprintln!("Could not create db context:\n{}", e);
                 process::exit(1);
             }
         };
@@ -29,30 +55,168 @@ impl SongsRepository {
             eprintln!("Could not create files directory");
             process::exit(1);
         };
+        if fs::create_dir_all(&CONFIG.uploads_folder_path).is_err() {
+            eprintln!("Could not create uploads directory");
+            process::exit(1);
+        };
+        if fs::create_dir_all(&CONFIG.covers_folder_path).is_err() {
+            eprintln!("Could not create covers directory");
+            process::exit(1);
+        };
         SongsRepository { songs_db_context }
     }
 
-    pub fn find_song_path(&self, name: &str, artist: &str) -> Option<String> {
+    pub fn find_song_path(&self, name: &str, artist: &str) -> io::Result<String> {
         let path = match self.songs_db_context.select_song_file_path(name, artist) {
             Ok(path) => path,
             Err(e) => {
                 eprintln!("{}", e);
-                return None;
+                return Err(io::ErrorKind::NotFound.into());
             }
         };
 
-        Some(format!("{}{}", &CONFIG.files_folder_path, path))
+        let path = CONFIG
+            .library_roots
+            .served_path(&path, CONFIG.symlink_policy)
+            .inspect_err(|e| eprintln!("{}", e))?;
+        path.to_str()
+            .map(String::from)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Path is not valid UTF-8"))
+    }
+
+    pub fn find_song_infos(
+        &self,
+        keyword: &str,
+        user: &str,
+        filter: &RatingFilter,
+        count: usize,
+    ) -> Vec<SongInfo> {
+        self.songs_db_context
+            .select_song_infos(keyword, user, filter, count)
+            .unwrap_or_default()
+    }
+
+    pub fn find_song_ratings(&self, user: &str, filter: &RatingFilter) -> Vec<SongRating> {
+        match self.songs_db_context.select_song_ratings(user, filter) {
+            Ok(song_ratings) => song_ratings,
+            Err(err) => {
+                eprintln!("{}", err);
+                Vec::new()
+            }
+        }
     }
 
-    pub fn find_song_infos(&self, keyword: &str, count: usize) -> Vec<SongInfo> {
-        match self.songs_db_context.select_song_infos(keyword, count) {
-            Ok(song_infos) => song_infos,
-            Err(_) => Vec::new(),
+    pub fn rate_song(&self, user: &str, name: &str, artist: &str, rating: u8) -> bool {
+        match self
+            .songs_db_context
+            .upsert_rating(user, name, artist, rating)
+        {
+            Ok(_) => true,
+            Err(err) => {
+                eprintln!("{}", err);
+                false
+            }
         }
     }
 
-    pub fn insert_song(&self, song: Song) -> bool {
-        match self.songs_db_context.insert_song(song) {
+    pub fn unrate_song(&self, user: &str, name: &str, artist: &str) -> bool {
+        match self.songs_db_context.clear_rating(user, name, artist) {
+            Ok(_) => true,
+            Err(err) => {
+                eprintln!("{}", err);
+                false
+            }
+        }
+    }
+
+    pub fn favourite_song(&self, user: &str, name: &str, artist: &str) -> bool {
+        match self.songs_db_context.upsert_favourite(user, name, artist) {
+            Ok(_) => true,
+            Err(err) => {
+                eprintln!("{}", err);
+                false
+            }
+        }
+    }
+
+    pub fn unfavourite_song(&self, user: &str, name: &str, artist: &str) -> bool {
+        match self.songs_db_context.clear_favourite(user, name, artist) {
+            Ok(_) => true,
+            Err(err) => {
+                eprintln!("{}", err);
+                false
+            }
+        }
+    }
+
+    pub fn record_play(&self, play: PlayEvent) -> bool {
+        match self.songs_db_context.insert_play(play) {
+            Ok(_) => true,
+            Err(err) => {
+                eprintln!("{}", err);
+                false
+            }
+        }
+    }
+
+    pub fn find_recently_played(&self, user: &str, since: i64, count: usize) -> Vec<SongPlays> {
+        match self
+            .songs_db_context
+            .select_recently_played(user, since, count)
+        {
+            Ok(song_plays) => song_plays,
+            Err(err) => {
+                eprintln!("{}", err);
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn find_most_played(&self, user: &str, from: i64, to: i64, count: usize) -> Vec<SongPlays> {
+        match self
+            .songs_db_context
+            .select_most_played(user, from, to, count)
+        {
+            Ok(song_plays) => song_plays,
+            Err(err) => {
+                eprintln!("{}", err);
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn find_never_played(&self, user: &str, count: usize) -> Vec<SongPlays> {
+        match self.songs_db_context.select_never_played(user, count) {
+            Ok(song_plays) => song_plays,
+            Err(err) => {
+                eprintln!("{}", err);
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn save_playback_state(&self, state: &PlaybackState) -> Option<PlaybackStateSave> {
+        match self.songs_db_context.upsert_playback_state(state) {
+            Ok(save) => Some(save),
+            Err(err) => {
+                eprintln!("{}", err);
+                None
+            }
+        }
+    }
+
+    pub fn find_playback_state(&self, user: &str) -> Option<PlaybackState> {
+        match self.songs_db_context.select_playback_state(user) {
+            Ok(state) => state,
+            Err(err) => {
+                eprintln!("{}", err);
+                None
+            }
+        }
+    }
+
+    pub fn open_upload_session(&self, session: &UploadSession) -> bool {
+        match self.songs_db_context.insert_upload_session(session) {
             Ok(_) => true,
             Err(err) => {
                 eprintln!("{}", err);
@@ -61,6 +225,237 @@ impl SongsRepository {
         }
     }
 
+    pub fn find_upload_session(&self, id: &str) -> Option<UploadSession> {
+        match self.songs_db_context.select_upload_session(id) {
+            Ok(session) => session,
+            Err(err) => {
+                eprintln!("{}", err);
+                None
+            }
+        }
+    }
+
+    pub fn confirm_upload_offset(&self, id: &str, offset: u64, expires_at: i64) -> bool {
+        match self
+            .songs_db_context
+            .update_upload_offset(id, offset, expires_at)
+        {
+            Ok(_) => true,
+            Err(err) => {
+                eprintln!("{}", err);
+                false
+            }
+        }
+    }
+
+    pub fn close_upload_session(&self, id: &str) -> bool {
+        match self.songs_db_context.delete_upload_session(id) {
+            Ok(_) => true,
+            Err(err) => {
+                eprintln!("{}", err);
+                false
+            }
+        }
+    }
+
+    pub fn find_expired_upload_sessions(&self, now: i64) -> Vec<String> {
+        match self.songs_db_context.select_upload_session_ids(now) {
+            Ok(ids) => ids,
+            Err(err) => {
+                eprintln!("{}", err);
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn find_song(&self, name: &str, artist: &str) -> Option<Song> {
+        match self.songs_db_context.select_song(name, artist) {
+            Ok(song) => song,
+            Err(err) => {
+                eprintln!("{}", err);
+                None
+            }
+        }
+    }
+
+    pub fn update_song(&self, name: &str, artist: &str, song: &Song) -> bool {
+        match self.songs_db_context.update_song(name, artist, song) {
+            Ok(changed) => changed,
+            Err(err) => {
+                eprintln!("{}", err);
+                false
+            }
+        }
+    }
+
+    pub fn save_song_tags(
+        &self,
+        song: &Song,
+        tags: &AudioTags,
+        previous: &AudioTags,
+        edited_at: i64,
+    ) -> bool {
+        match self.songs_db_context.update_song_tags(
+            &song.name,
+            &song.artist,
+            tags,
+            song.image_path.as_deref(),
+            previous,
+            edited_at,
+        ) {
+            Ok(_) => {
+                self.rekey_song_file(song);
+                true
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                false
+            }
+        }
+    }
+
+    pub fn undo_song_tags(&self, edit_id: i64, song: &Song, tags: &AudioTags) -> bool {
+        match self.songs_db_context.undo_song_tags(
+            edit_id,
+            &song.name,
+            &song.artist,
+            tags,
+            song.image_path.as_deref(),
+        ) {
+            Ok(_) => {
+                self.rekey_song_file(song);
+                true
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                false
+            }
+        }
+    }
+
+    fn rekey_song_file(&self, song: &Song) {
+        let file_id = match file_identity(&full_path(&song.file_path)) {
+            Ok(file_id) => file_id,
+            Err(e) => {
+                eprintln!("Could not fingerprint {}:\n{}", song.file_path, e);
+                return;
+            }
+        };
+        if let Err(err) =
+            self.songs_db_context
+                .update_song_file_id(&song.name, &song.artist, &file_id)
+        {
+            eprintln!("{}", err);
+        }
+    }
+
+    pub fn find_tag_edits(&self, name: &str, artist: &str, count: usize) -> Vec<TagEdit> {
+        match self.songs_db_context.select_tag_edits(name, artist, count) {
+            Ok(edits) => edits,
+            Err(err) => {
+                eprintln!("{}", err);
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn save_song_details(&self, song: &Song, details: &AudioTags) -> bool {
+        match self
+            .songs_db_context
+            .update_song_details(&song.name, &song.artist, details)
+        {
+            Ok(_) => true,
+            Err(err) => {
+                eprintln!("{}", err);
+                false
+            }
+        }
+    }
+
+    pub fn find_metadata_override(&self, file_id: &str) -> Option<MetadataOverride> {
+        match self.songs_db_context.select_metadata_override(file_id) {
+            Ok(metadata_override) => metadata_override,
+            Err(err) => {
+                eprintln!("{}", err);
+                None
+            }
+        }
+    }
+
+    fn find_metadata_override_at(&self, file_path: &str) -> Option<MetadataOverride> {
+        match self.songs_db_context.select_metadata_override_at(file_path) {
+            Ok(metadata_override) => metadata_override,
+            Err(err) => {
+                eprintln!("{}", err);
+                None
+            }
+        }
+    }
+
+    pub fn find_metadata_overrides(&self) -> Vec<MetadataOverride> {
+        match self.songs_db_context.select_metadata_overrides() {
+            Ok(overrides) => overrides,
+            Err(err) => {
+                eprintln!("{}", err);
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn save_metadata_override(&self, metadata_override: &MetadataOverride) -> bool {
+        match self
+            .songs_db_context
+            .upsert_metadata_override(metadata_override)
+        {
+            Ok(_) => true,
+            Err(err) => {
+                eprintln!("{}", err);
+                false
+            }
+        }
+    }
+
+    pub fn remove_metadata_override(&self, file_id: &str) -> bool {
+        match self.songs_db_context.delete_metadata_override(file_id) {
+            Ok(removed) => removed,
+            Err(err) => {
+                eprintln!("{}", err);
+                false
+            }
+        }
+    }
+
+    pub fn insert_song(&self, mut song: Song) -> bool {
+        if song.file_id.is_none() {
+            song.file_id = self
+                .find_known_file_id(&song.file_path)
+                .or_else(|| file_identity(&full_path(&song.file_path)).ok());
+        }
+        let file_path = song.file_path.clone();
+        match self.songs_db_context.insert_song(song) {
+            Ok(relinked) => {
+                if relinked {
+                    println!("Found moved song at {}", file_path);
+                }
+                true
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                false
+            }
+        }
+    }
+
+    fn find_known_file_id(&self, file_path: &str) -> Option<String> {
+        match self.songs_db_context.select_file_id(file_path) {
+            Ok(file_id) => file_id,
+            Err(err) => {
+                eprintln!("{}", err);
+                None
+            }
+        }
+    }
+
     pub fn delete_song(&self, song: Song) -> bool {
         match self.songs_db_context.delete_song(song) {
             Ok(_) => true,
@@ -71,56 +466,568 @@ impl SongsRepository {
         }
     }
 
-    pub fn fetch_song_from_path(&self, mut path: PathBuf) -> Option<Song> {
-        if !matches!(path.extension(), Some(ext) if ext == "mp3") {
-            return None;
+    pub fn follow_rename(&self, from: PathBuf, to: PathBuf, now: i64) -> bool {
+        let from_path = match relative_file_path(&from) {
+            Some(from_path) => from_path,
+            None => return false,
+        };
+        self.clear_scan_issues(&from_path);
+        if to.is_dir() {
+            let to_path = match relative_file_path(&to) {
+                Some(to_path) => to_path,
+                None => return false,
+            };
+            if let Err(err) = self
+                .songs_db_context
+                .update_folder_path(&format!("{}/", from_path), &format!("{}/", to_path))
+            {
+                eprintln!("{}", err);
+                return false;
+            }
+            return self.update_folder(&to, now);
         }
 
-        let file_full_name = path.file_name().and_then(OsStr::to_str)?.to_string();
+        let renamed = match self.songs_db_context.select_song_by_file_path(&from_path) {
+            Ok(renamed) => renamed,
+            Err(err) => {
+                eprintln!("{}", err);
+                None
+            }
+        };
+        let to_path = match relative_file_path(&to) {
+            Some(to_path) => to_path,
+            None => return false,
+        };
+        let song = if has_audio_extension(&to) {
+            self.inspect_file(to, to_path, now, &self.override_lookup())
+                .map_err(Some)
+        } else {
+            Err(None)
+        };
+        let song = match song {
+            Ok(song) => song,
+            // Renamed to a file that is not a song, which is as good as gone
+            Err(issue) => {
+                if let Some(issue) = issue {
+                    self.record_scan_issue(&issue);
+                }
+                return match renamed {
+                    Some(renamed) => self.mark_song_missing(&renamed.file_path, now),
+                    None => true,
+                };
+            }
+        };
+        if let Some(renamed) = renamed {
+            // The name is taken by another song, which keeps it
+            if !self.update_song(&renamed.name, &renamed.artist, &song) {
+                self.mark_song_missing(&renamed.file_path, now);
+            }
+        }
+        self.insert_song(song)
+    }
 
-        if !path.set_extension("") {
-            return None;
+    pub fn refresh_song(&self, path: PathBuf, now: i64) -> bool {
+        // Events may arrive after the file was renamed or removed
+        if !storage::is_file(&path) || !has_audio_extension(&path) {
+            return false;
+        }
+        let file_path = match relative_file_path(&path) {
+            Some(file_path) => file_path,
+            None => return false,
+        };
+        let lookup = self.override_lookup();
+        let song = match self.inspect_file(path.clone(), file_path.clone(), now, &lookup) {
+            Ok(song) => song,
+            Err(issue) => {
+                self.record_scan_issue(&issue);
+                return false;
+            }
+        };
+        self.clear_scan_issues(&file_path);
+        if !self.insert_song(song.clone()) {
+            return false;
         }
 
-        let (artist, name) = path
-            .file_name()
-            .and_then(OsStr::to_str)
-            .and_then(|x| x.split(" - ").collect_tuple())?;
+        let mut details = song.parsed.clone().unwrap_or_default();
+        if let Ok(tags) = audio_tags::read_tags(&path) {
+            details.apply(&tags);
+        }
+        if let Some(overrides) = &song.overrides {
+            details.apply(overrides);
+        }
+        self.save_song_details(&song, &details)
+    }
 
-        Some(Song::new(
-            name.to_string(),
-            artist.to_string(),
-            None,
-            file_full_name,
-        ))
+    pub fn update_access(&self, path: PathBuf, now: i64) -> bool {
+        let readable = if path.is_dir() {
+            path.read_dir().is_ok()
+        } else {
+            File::open(&path).is_ok()
+        };
+        if !readable {
+            return self.mark_missing(path, now);
+        }
+        if path.is_dir() {
+            return self.update_folder(&path, now);
+        }
+        match self.fetch_song_from_path(path) {
+            Some(song) => self.insert_song(song),
+            None => false,
+        }
     }
 
-    pub fn auto_update(&self) -> bool {
-        let songs_repository = &CONFIG.files_folder_path;
-        let path = Path::new(songs_repository);
-        let read_dir = match path.read_dir() {
-            Ok(iterator) => iterator,
-            Err(_) => return false,
+    pub fn mark_missing(&self, path: PathBuf, now: i64) -> bool {
+        if let Some((root, relative)) = CONFIG.library_roots.locate(&path) {
+            if relative.as_os_str().is_empty() {
+                println!("Library root {} is offline, its songs are kept", root);
+                return true;
+            }
+        }
+        let file_path = match relative_file_path(&path) {
+            Some(file_path) => file_path,
+            None => return false,
         };
-        for file_path in read_dir.flatten().map(|file| file.path()) {
-            if let Some(song) = self.fetch_song_from_path(file_path) {
-                self.insert_song(song);
+        self.clear_scan_issues(&file_path);
+        match self.songs_db_context.update_song_missing(&file_path, now) {
+            Ok(0) => {}
+            Ok(_) => return true,
+            Err(err) => {
+                eprintln!("{}", err);
+                return false;
+            }
+        }
+        // Nothing was stored for the path, so it may have been a folder
+        let path_prefix = format!("{}/", file_path);
+        match self
+            .songs_db_context
+            .update_folder_missing(&path_prefix, now)
+        {
+            Ok(_) => true,
+            Err(err) => {
+                eprintln!("{}", err);
+                false
             }
         }
-        true
     }
 
-    pub fn validate(&self) -> bool {
-        let songs = match self.songs_db_context.select_all_songs() {
+    fn mark_song_missing(&self, file_path: &str, now: i64) -> bool {
+        match self.songs_db_context.update_song_missing(file_path, now) {
+            Ok(_) => true,
+            Err(err) => {
+                eprintln!("{}", err);
+                false
+            }
+        }
+    }
+
+    pub fn find_missing_songs(&self) -> Vec<TrashedSong> {
+        match self.songs_db_context.select_missing_songs() {
             Ok(songs) => songs,
-            Err(_) => return false,
+            Err(err) => {
+                eprintln!("{}", err);
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn find_missing_song(&self, name: &str, artist: &str) -> Option<TrashedSong> {
+        match self.songs_db_context.select_missing_song(name, artist) {
+            Ok(song) => song,
+            Err(err) => {
+                eprintln!("{}", err);
+                None
+            }
+        }
+    }
+
+    pub fn purge_missing_songs(&self, missing_before: i64) -> usize {
+        match self.songs_db_context.delete_missing_songs(missing_before) {
+            Ok(deleted) => deleted,
+            Err(err) => {
+                eprintln!("{}", err);
+                0
+            }
+        }
+    }
+
+    pub fn fetch_song_from_path(&self, path: PathBuf) -> Option<Song> {
+        self.read_song(&path, &self.override_lookup())
+    }
+
+    fn read_song(&self, path: &Path, lookup: &OverrideLookup) -> Option<Song> {
+        if !has_audio_extension(path) {
+            return None;
+        }
+
+        let file_path = relative_file_path(path)?;
+        // Files are only fingerprinted while there are overrides to find
+        let file_id = if lookup.has_stored_overrides {
+            file_identity(path).ok()
+        } else {
+            None
+        };
+        let overrides = self.find_overrides(path, &file_path, file_id.as_deref(), lookup);
+        let parsed = match_templates(&CONFIG.filename_templates, Path::new(&file_path))
+            .map(|(_, values)| values);
+
+        let mut values = parsed.clone().unwrap_or_default();
+        if let Some(overrides) = &overrides {
+            values.apply(overrides);
+        }
+        // Values the templates miss fall back to the ones tagged in the file
+        let tags = if values.title.is_none() || values.artist.is_none() {
+            audio_tags::read_tags(path).unwrap_or_default()
+        } else {
+            AudioTags::default()
+        };
+        let tagged = |value: Option<String>| value.filter(|value| !value.trim().is_empty());
+        let (tagged_title, tagged_artist) = (tags.title, tags.artist);
+        let name = values.title.or_else(|| tagged(tagged_title))?;
+        let artist = values
+            .artist
+            .or_else(|| tagged(tagged_artist))
+            .unwrap_or_else(|| String::from(UNKNOWN_ARTIST));
+
+        let mut song = Song::new(name, artist, None, file_path);
+        song.file_id = file_id;
+        song.file_stamp = file_stamp(path);
+        song.overrides = overrides;
+        song.parsed = parsed;
+        Some(song)
+    }
+
+    fn find_overrides(
+        &self,
+        path: &Path,
+        file_path: &str,
+        file_id: Option<&str>,
+        lookup: &OverrideLookup,
+    ) -> Option<AudioTags> {
+        let folder_override = match (path.parent(), path.file_name().and_then(OsStr::to_str)) {
+            (Some(folder), Some(file_name)) => lookup
+                .folder_overrides(folder)
+                .and_then(|overrides| overrides.get(file_name).cloned()),
+            _ => None,
+        };
+        let stored = file_id
+            .and_then(|file_id| {
+                self.find_metadata_override(file_id)
+                    .or_else(|| self.find_metadata_override_at(file_path))
+            })
+            .map(|metadata_override| metadata_override.values);
+        match (folder_override, stored) {
+            (Some(mut overrides), Some(stored)) => {
+                overrides.apply(&stored);
+                Some(overrides)
+            }
+            (overrides, None) | (None, overrides) => overrides,
+        }
+    }
+
+    fn override_lookup(&self) -> OverrideLookup {
+        let has_stored_overrides = match self.songs_db_context.count_metadata_overrides() {
+            Ok(count) => count > 0,
+            Err(err) => {
+                eprintln!("{}", err);
+                false
+            }
         };
-        for song in songs {
-            let file_path = Path::new(&song.file_path);
-            if !file_path.exists() && !self.delete_song(song) {
-                println!("Error while validating")
+        OverrideLookup {
+            has_stored_overrides,
+            folders: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn auto_update(&self, now: i64) -> bool {
+        self.rescan(false, now, &ScanProgress::default())
+    }
+
+    pub fn rescan(&self, force: bool, now: i64, progress: &ScanProgress) -> bool {
+        let mut scanned = true;
+        for root in CONFIG.library_roots.iter() {
+            if !root.is_online() {
+                println!("Library root {} is offline, its songs are kept", root);
+                continue;
             }
+            scanned &= self.scan_folder(Path::new(&root.path), force, now, progress);
+        }
+        scanned
+    }
+
+    pub fn update_folder(&self, path: &Path, now: i64) -> bool {
+        self.scan_folder(path, true, now, &ScanProgress::default())
+    }
+
+    pub fn sync_folder(&self, path: &Path, now: i64) -> bool {
+        self.scan_folder(path, false, now, &ScanProgress::default())
+    }
+
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        // Patterns match the path below the root, like for the files folder
+        let (root, file_path) = match CONFIG.library_roots.locate(path) {
+            Some((root, relative)) => match relative.to_str() {
+                Some(file_path) if !file_path.is_empty() => (root, file_path.to_string()),
+                _ => return false,
+            },
+            None => return false,
+        };
+        let path_prefix = match file_path.rfind('/') {
+            Some(index) => &file_path[..index + 1],
+            None => "",
+        };
+        match CONFIG
+            .ignore_rules
+            .for_folder(Path::new(&root.path), path_prefix)
+        {
+            Some(rules) => rules.is_ignored(&file_path, path.is_dir()),
+            None => true,
+        }
+    }
+
+    fn scan_folder(&self, folder: &Path, force: bool, now: i64, progress: &ScanProgress) -> bool {
+        let path_prefix = match relative_file_path(folder) {
+            Some(folder_path) if folder_path.is_empty() => folder_path,
+            Some(folder_path) => format!("{}/", folder_path),
+            None => return false,
+        };
+        let root = CONFIG.library_roots.find(&path_prefix);
+        let rules_prefix = &path_prefix[root.path_prefix().len().min(path_prefix.len())..];
+        let mut files = Vec::new();
+        if let Some(rules) = CONFIG
+            .ignore_rules
+            .for_folder(Path::new(&root.path), rules_prefix)
+        {
+            if !list_audio_files(folder, rules_prefix, &rules, &mut files, progress) {
+                return false;
+            }
+        }
+        let mut known = match self.songs_db_context.select_file_stamps(&path_prefix) {
+            Ok(known) => known,
+            Err(err) => {
+                eprintln!("{}", err);
+                return false;
+            }
+        };
+        // The paths of the files folder hold the songs of the other roots too
+        known.retain(|file_path, _| CONFIG.library_roots.find(file_path) == root);
+
+        let mut changed = Vec::new();
+        for (path, stamp) in map_parallel(files, |path| {
+            let stamp = file_stamp(&path);
+            (path, stamp)
+        }) {
+            let file_path = match relative_file_path(&path) {
+                Some(file_path) => file_path,
+                None => {
+                    progress.processed(1);
+                    progress.failed(1);
+                    continue;
+                }
+            };
+            match known.remove(&file_path) {
+                Some(Some(known_stamp)) if !force && stamp == Some(known_stamp) => {
+                    progress.processed(1)
+                }
+                known_song => changed.push((path, file_path, known_song.is_some())),
+            }
+        }
+
+        // Songs are marked first, so the files they moved to can take them over
+        let gone: Vec<String> = known.into_keys().collect();
+        if !gone.is_empty() {
+            match self.songs_db_context.update_songs_missing(&gone, now) {
+                Ok(_) => progress.removed(gone.len() as u64),
+                Err(err) => eprintln!("{}", err),
+            }
+        }
+
+        let mut songs = Vec::new();
+        let mut known = Vec::new();
+        let mut issues = Vec::new();
+        let lookup = self.override_lookup();
+        for result in map_parallel(changed, |(path, file_path, known)| {
+            let song = self.inspect_file(path, file_path, now, &lookup);
+            progress.processed(1);
+            song.map(|song| (song, known))
+        }) {
+            match result {
+                Ok((song, known_song)) => {
+                    songs.push(song);
+                    known.push(known_song);
+                }
+                Err(issue) => {
+                    progress.failed(1);
+                    issues.push(issue);
+                }
+            }
+        }
+
+        let mut relinked = 0;
+        for (batch, batch_known) in songs
+            .chunks(SCAN_BATCH_SIZE)
+            .zip(known.chunks(SCAN_BATCH_SIZE))
+        {
+            match self.songs_db_context.insert_songs(batch) {
+                Ok(batch_relinked) => {
+                    relinked += batch_relinked;
+                    let updated = batch_known.iter().filter(|known| **known).count() as u64;
+                    progress.updated(updated);
+                    progress.added(batch.len() as u64 - updated);
+                }
+                Err(err) => {
+                    eprintln!("{}", err);
+                    progress.failed(batch.len() as u64);
+                }
+            }
+        }
+        if relinked > 0 {
+            println!("Found {} moved songs", relinked);
+        }
+
+        if !issues.is_empty() {
+            println!(
+                "{} files could not be added, see the scan issues",
+                issues.len()
+            );
+        }
+        if path_prefix.is_empty() {
+            issues.extend(
+                self.find_scan_issues()
+                    .into_iter()
+                    .filter(|issue| CONFIG.library_roots.find(&issue.file_path) != root),
+            );
+        }
+        if let Err(err) = self
+            .songs_db_context
+            .replace_scan_issues(&path_prefix, &issues)
+        {
+            eprintln!("{}", err);
         }
         true
     }
+
+    fn inspect_file(
+        &self,
+        path: PathBuf,
+        file_path: String,
+        now: i64,
+        lookup: &OverrideLookup,
+    ) -> Result<Song, ScanIssue> {
+        if let Err(defect) = check_audio_file(&path) {
+            return Err(ScanIssue::new(file_path, defect.reason, defect.detail, now));
+        }
+        match self.read_song(&path, lookup) {
+            Some(mut song) => {
+                if song.file_id.is_none() {
+                    song.file_id = file_identity(&path).ok();
+                }
+                Ok(song)
+            }
+            None => Err(ScanIssue::new(
+                file_path,
+                IssueReason::NoMetadata,
+                String::from("No title in the file name or the overrides"),
+                now,
+            )),
+        }
+    }
+
+    pub fn find_scan_issues(&self) -> Vec<ScanIssue> {
+        match self.songs_db_context.select_scan_issues() {
+            Ok(issues) => issues,
+            Err(err) => {
+                eprintln!("{}", err);
+                Vec::new()
+            }
+        }
+    }
+
+    fn record_scan_issue(&self, issue: &ScanIssue) {
+        if let Err(err) = self.songs_db_context.upsert_scan_issue(issue) {
+            eprintln!("{}", err);
+        }
+    }
+
+    fn clear_scan_issues(&self, file_path: &str) {
+        if let Err(err) = self.songs_db_context.delete_scan_issues(file_path) {
+            eprintln!("{}", err);
+        }
+    }
+}
+
+fn list_audio_files(
+    folder: &Path,
+    path_prefix: &str,
+    rules: &IgnoreRules,
+    files: &mut Vec<PathBuf>,
+    progress: &ScanProgress,
+) -> bool {
+    let entries = match storage::list(folder) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+    let folder_rules = rules.with_folder(folder, path_prefix);
+    let rules = folder_rules.as_ref().unwrap_or(rules);
+    for entry in entries {
+        let file_name = match entry.path.file_name() {
+            Some(file_name) => file_name.to_string_lossy(),
+            None => continue,
+        };
+        let file_path = format!("{}{}", path_prefix, file_name);
+        if rules.is_ignored(&file_path, entry.is_dir) {
+            continue;
+        }
+        if entry.is_dir {
+            list_audio_files(
+                &entry.path,
+                &format!("{}/", file_path),
+                rules,
+                files,
+                progress,
+            );
+        } else if has_audio_extension(&entry.path) {
+            files.push(entry.path);
+            progress.discovered(1);
+        }
+    }
+    true
+}
+
+type FolderOverrides = HashMap<String, AudioTags>;
+
+struct OverrideLookup {
+    has_stored_overrides: bool,
+    folders: Mutex<HashMap<PathBuf, Option<Arc<FolderOverrides>>>>,
+}
+
+impl OverrideLookup {
+    fn folder_overrides(&self, folder: &Path) -> Option<Arc<FolderOverrides>> {
+        if let Some(overrides) = self.folders.lock().ok()?.get(folder) {
+            return overrides.clone();
+        }
+        // Read without holding the lock, the other scan threads read other folders meanwhile
+        let overrides = read_folder_overrides(folder).map(Arc::new);
+        if let Ok(mut folders) = self.folders.lock() {
+            folders.insert(folder.to_path_buf(), overrides.clone());
+        }
+        overrides
+    }
+}
+
+fn has_audio_extension(path: &Path) -> bool {
+    match path.extension().and_then(OsStr::to_str) {
+        Some(extension) => CONFIG.audio_formats.contains(&extension.to_lowercase()),
+        None => false,
+    }
+}
+
+fn full_path(file_path: &str) -> PathBuf {
+    CONFIG.library_roots.full_path(file_path)
+}
+
+fn relative_file_path(path: &Path) -> Option<String> {
+    if CONFIG.library_roots.locate(path).is_some() {
+        return CONFIG.library_roots.file_path(path);
+    }
+    path.file_name()?.to_str().map(String::from)
 }
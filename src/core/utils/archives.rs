@@ -0,0 +1,336 @@
+use flate2::read::GzDecoder;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+const MAX_ENTRIES: usize = 10_000;
+
+const FILE_TYPE_MASK: u32 = 0o170000;
+const SYMBOLIC_LINK: u32 = 0o120000;
+
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+pub fn is_archive(path: &Path) -> bool {
+    archive_kind(path).is_some()
+}
+
+pub fn archive_stem(path: &Path) -> Option<String> {
+    let file_name = path.file_name()?.to_str()?;
+    let extension_length = match archive_kind(path)? {
+        ArchiveKind::Zip | ArchiveKind::Tar => 4,
+        ArchiveKind::TarGz if file_name.to_lowercase().ends_with(".tgz") => 4,
+        ArchiveKind::TarGz => 7,
+    };
+    Some(file_name[..file_name.len() - extension_length].to_string())
+        .filter(|stem| !stem.is_empty())
+}
+
+// Only the bytes actually written count against the size limit, whatever the headers claim
+pub fn extract(
+    archive: &Path,
+    target: &Path,
+    accept: &dyn Fn(&Path) -> bool,
+    max_size: u64,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut extractor = Extractor {
+        target,
+        accept,
+        remaining: max_size,
+        entries: 0,
+        files: Vec::new(),
+    };
+    match archive_kind(archive).ok_or("Unknown archive format")? {
+        ArchiveKind::Zip => extract_zip(File::open(archive)?, &mut extractor)?,
+        ArchiveKind::Tar => extract_tar(File::open(archive)?, &mut extractor)?,
+        ArchiveKind::TarGz => extract_tar(GzDecoder::new(File::open(archive)?), &mut extractor)?,
+    }
+    Ok(extractor.files)
+}
+
+fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    let file_name = path.file_name()?.to_str()?.to_lowercase();
+    if file_name.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+        Some(ArchiveKind::TarGz)
+    } else if file_name.ends_with(".tar") {
+        Some(ArchiveKind::Tar)
+    } else {
+        None
+    }
+}
+
+fn extract_zip(file: File, extractor: &mut Extractor) -> Result<(), Box<dyn Error>> {
+    let mut archive = zip::ZipArchive::new(file)?;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let is_link =
+            matches!(entry.unix_mode(), Some(mode) if mode & FILE_TYPE_MASK == SYMBOLIC_LINK);
+        if entry.is_dir() || is_link {
+            continue;
+        }
+        // Archives made on Windows may separate folders with backslashes
+        let name = PathBuf::from(entry.name().replace('\\', "/"));
+        extractor.add(&name, &mut entry)?;
+    }
+    Ok(())
+}
+
+fn extract_tar<R: Read>(reader: R, extractor: &mut Extractor) -> Result<(), Box<dyn Error>> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.into_owned();
+        extractor.add(&name, &mut entry)?;
+    }
+    Ok(())
+}
+
+struct Extractor<'a> {
+    target: &'a Path,
+    accept: &'a dyn Fn(&Path) -> bool,
+    remaining: u64,
+    entries: usize,
+    files: Vec<PathBuf>,
+}
+
+impl Extractor<'_> {
+    fn add(&mut self, name: &Path, reader: &mut dyn Read) -> Result<(), Box<dyn Error>> {
+        self.entries += 1;
+        if self.entries > MAX_ENTRIES {
+            return Err("Archive has too many entries".into());
+        }
+        let relative_path = safe_path(name)
+            .ok_or_else(|| format!("Archive entry has an unsafe path: {}", name.display()))?;
+        if !(self.accept)(&relative_path) {
+            return Ok(());
+        }
+
+        let path = self.target.join(&relative_path);
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        let written = io::copy(&mut reader.take(self.remaining + 1), &mut file)?;
+        if written > self.remaining {
+            return Err("Archive content is larger than allowed".into());
+        }
+        self.remaining -= written;
+        self.files.push(path);
+        Ok(())
+    }
+}
+
+fn safe_path(name: &Path) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(path).filter(|path| !path.as_os_str().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+    use std::io::Write;
+    use tempfile::TempDir;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    const MAX_SIZE: u64 = 1024;
+
+    fn accept_all(_: &Path) -> bool {
+        true
+    }
+
+    fn zip_archive(folder: &TempDir, entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let path = folder.path().join("build.zip");
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+        for (name, data) in entries {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap();
+        fs::read(&path).unwrap()
+    }
+
+    fn tar_archive(entries: &[(&str, tar::EntryType, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, entry_type, data) in entries {
+            let mut header = tar::Header::new_old();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_entry_type(*entry_type);
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            if *entry_type == tar::EntryType::Symlink {
+                header.set_link_name("/etc/passwd").unwrap();
+            }
+            header.set_cksum();
+            builder.append(&header, *data).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn extract_bytes(folder: &TempDir, name: &str, data: &[u8]) -> Result<Vec<PathBuf>, String> {
+        let archive = folder.path().join(name);
+        fs::write(&archive, data).unwrap();
+        extract(&archive, &folder.path().join("out"), &accept_all, MAX_SIZE)
+            .map_err(|e| e.to_string())
+    }
+
+    fn written(folder: &TempDir) -> Vec<String> {
+        fn walk(folder: &Path, root: &Path, found: &mut Vec<String>) {
+            for entry in fs::read_dir(folder).unwrap().flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    walk(&path, root, found);
+                } else {
+                    let relative = path.strip_prefix(root).unwrap();
+                    found.push(relative.to_string_lossy().into_owned());
+                }
+            }
+        }
+        let mut found = Vec::new();
+        walk(folder.path(), folder.path(), &mut found);
+        found.retain(|path| !path.ends_with(".zip") && !path.ends_with(".tar"));
+        found.sort();
+        found
+    }
+
+    fn patch_u32(data: &mut [u8], signature: &[u8; 4], offset: usize, value: u32) {
+        let start = data
+            .windows(4)
+            .position(|window| window == signature)
+            .unwrap();
+        data[start + offset..start + offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    #[test]
+    fn accepted_files_are_extracted_in_their_folders() {
+        let folder = tempfile::tempdir().unwrap();
+        let zip = zip_archive(
+            &folder,
+            &[
+                ("Album/01 One.mp3", b"one"),
+                ("Album\\02 Two.mp3", b"two"),
+                ("./Album/notes.txt", b"notes"),
+            ],
+        );
+        let archive = folder.path().join("Album.zip");
+        fs::write(&archive, zip).unwrap();
+        let accept_songs = |path: &Path| path.extension() == Some(OsStr::new("mp3"));
+
+        let files = extract(
+            &archive,
+            &folder.path().join("out"),
+            &accept_songs,
+            MAX_SIZE,
+        )
+        .unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(
+            written(&folder),
+            vec!["out/Album/01 One.mp3", "out/Album/02 Two.mp3"]
+        );
+        assert_eq!(
+            fs::read(folder.path().join("out/Album/02 Two.mp3")).unwrap(),
+            b"two"
+        );
+    }
+
+    #[test]
+    fn entries_leaving_the_folder_are_refused() {
+        let folder = tempfile::tempdir().unwrap();
+        for name in ["../evil.mp3", "Album/../../evil.mp3", "/tmp/evil.mp3"] {
+            let zip = zip_archive(&folder, &[(name, b"evil")]);
+            let error = extract_bytes(&folder, "evil.zip", &zip).unwrap_err();
+            assert!(error.contains("unsafe path"), "{}: {}", name, error);
+
+            let tar = tar_archive(&[(name, tar::EntryType::Regular, b"evil")]);
+            let error = extract_bytes(&folder, "evil.tar", &tar).unwrap_err();
+            assert!(error.contains("unsafe path"), "{}: {}", name, error);
+        }
+        assert!(written(&folder).is_empty());
+        assert!(!folder.path().parent().unwrap().join("evil.mp3").exists());
+    }
+
+    #[test]
+    fn symbolic_links_are_left_out() {
+        let folder = tempfile::tempdir().unwrap();
+        let tar = tar_archive(&[
+            ("link.mp3", tar::EntryType::Symlink, b""),
+            ("song.mp3", tar::EntryType::Regular, b"song"),
+        ]);
+        let files = extract_bytes(&folder, "links.tar", &tar).unwrap();
+        assert_eq!(files, vec![folder.path().join("out/song.mp3")]);
+
+        let mut zip = zip_archive(&folder, &[("link.mp3", b"/etc/passwd")]);
+        patch_u32(&mut zip, b"PK\x01\x02", 38, (SYMBOLIC_LINK | 0o777) << 16);
+        let files = extract_bytes(&folder, "links.zip", &zip).unwrap();
+        assert!(files.is_empty());
+        assert!(fs::symlink_metadata(folder.path().join("out/link.mp3")).is_err());
+    }
+
+    #[test]
+    fn sizes_are_counted_by_the_bytes_written() {
+        let folder = tempfile::tempdir().unwrap();
+        let mut zip = zip_archive(&folder, &[("big.mp3", &[0; 64 * 1024])]);
+        patch_u32(&mut zip, b"PK\x03\x04", 22, 10);
+        patch_u32(&mut zip, b"PK\x01\x02", 24, 10);
+        assert!(extract_bytes(&folder, "lying.zip", &zip).is_err());
+        let big = folder.path().join("out/big.mp3");
+        assert!(fs::metadata(&big).map_or(0, |metadata| metadata.len()) <= MAX_SIZE + 1);
+
+        let mut tar = tar_archive(&[("short.mp3", tar::EntryType::Regular, &[1; 600])]);
+        tar.truncate(512 + 100);
+        assert!(extract_bytes(&folder, "short.tar", &tar).is_err());
+
+        let tar = tar_archive(&[
+            ("first.mp3", tar::EntryType::Regular, &[1; 600]),
+            ("second.mp3", tar::EntryType::Regular, &[2; 600]),
+        ]);
+        let error = extract_bytes(&folder, "large.tar", &tar).unwrap_err();
+        assert!(error.contains("larger than allowed"), "{}", error);
+    }
+
+    #[test]
+    fn repeated_entries_do_not_overwrite_each_other() {
+        let folder = tempfile::tempdir().unwrap();
+        let tar = tar_archive(&[
+            ("song.mp3", tar::EntryType::Regular, b"first"),
+            ("./song.mp3", tar::EntryType::Regular, b"second"),
+        ]);
+        assert!(extract_bytes(&folder, "repeated.tar", &tar).is_err());
+        assert_eq!(
+            fs::read(folder.path().join("out/song.mp3")).unwrap(),
+            b"first"
+        );
+    }
+
+    #[test]
+    fn stems_leave_out_the_archive_extensions() {
+        let stem = |name: &str| archive_stem(Path::new(name));
+        assert_eq!(stem("/music/Album.zip"), Some(String::from("Album")));
+        assert_eq!(stem("Album.TAR.GZ"), Some(String::from("Album")));
+        assert_eq!(stem("Album.tgz"), Some(String::from("Album")));
+        assert_eq!(stem("Album.v2.tar"), Some(String::from("Album.v2")));
+        assert_eq!(stem(".zip"), None);
+        assert_eq!(stem("Album.rar"), None);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::core::utils::audio_checks::{read_at, read_tail, Defect, IssueReason};
+use crate::core::utils::storage::StorageFile;
+
+const STREAM_INFO: u8 = 0;
+const STREAM_INFO_LENGTH: usize = 34;
+const INVALID_BLOCK: u8 = 127;
+const MAX_FRAME_HEADER_LENGTH: usize = 16;
+
+struct FrameHeader {
+    number: u64,
+    variable: bool,
+    block_size: u64,
+    format: [u8; 2],
+}
+
+pub fn check(file: &mut StorageFile) -> Result<(), Defect> {
+    let file_length = file.len()?;
+    let mut offset = 4;
+    let mut stream_info = None;
+    loop {
+        let header = read_at(file, offset, 4)?;
+        if header.len() < 4 {
+            return Err(Defect::new(IssueReason::Truncated, "Metadata ends early"));
+        }
+        let kind = header[0] & 0x7F;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as u64;
+        if offset == 4 && kind != STREAM_INFO {
+            return Err(Defect::new(
+                IssueReason::BadHeader,
+                "Stream info is missing",
+            ));
+        }
+        if kind == INVALID_BLOCK {
+            return Err(Defect::new(
+                IssueReason::BadHeader,
+                "Invalid metadata block",
+            ));
+        }
+        if kind == STREAM_INFO {
+            stream_info = Some(read_at(file, offset + 4, STREAM_INFO_LENGTH)?);
+        }
+        offset += 4 + length;
+        if offset > file_length {
+            return Err(Defect::new(
+                IssueReason::Truncated,
+                "Metadata block runs past the end of the file",
+            ));
+        }
+        if header[0] & 0x80 != 0 {
+            break;
+        }
+    }
+    let stream_info = stream_info.unwrap_or_default();
+    if stream_info.len() < STREAM_INFO_LENGTH {
+        return Err(Defect::new(
+            IssueReason::BadHeader,
+            "Stream info is too short",
+        ));
+    }
+    if offset == file_length {
+        return Err(Defect::new(IssueReason::Truncated, "No audio frames"));
+    }
+
+    let first_frame = parse_frame_header(&read_at(file, offset, MAX_FRAME_HEADER_LENGTH)?)
+        .ok_or_else(|| Defect::new(IssueReason::BadHeader, "First audio frame is invalid"))?;
+
+    // Without a sample count the end of the stream is unknown
+    let total_samples = (((stream_info[13] & 0x0F) as u64) << 32)
+        | u32::from_be_bytes([
+            stream_info[14],
+            stream_info[15],
+            stream_info[16],
+            stream_info[17],
+        ]) as u64;
+    if total_samples == 0 {
+        return Ok(());
+    }
+    let max_block_size = u16::from_be_bytes([stream_info[2], stream_info[3]]) as u64;
+    let min_frame_length =
+        u32::from_be_bytes([0, stream_info[4], stream_info[5], stream_info[6]]) as usize;
+    let (_, tail) = read_tail(file, offset)?;
+    let last_frame = (0..tail.len()).rev().find_map(|position| {
+        parse_frame_header(&tail[position..])
+            .filter(|frame| {
+                frame.variable == first_frame.variable && frame.format == first_frame.format
+            })
+            .map(|frame| (frame, tail.len() - position))
+    });
+    if let Some((last_frame, last_frame_length)) = last_frame {
+        if last_frame_length < min_frame_length {
+            return Err(Defect::new(IssueReason::Truncated, "Last frame ends early"));
+        }
+        let first_sample = if last_frame.variable {
+            last_frame.number
+        } else {
+            last_frame.number * max_block_size
+        };
+        if first_sample + last_frame.block_size < total_samples {
+            return Err(Defect::new(
+                IssueReason::Truncated,
+                "Audio ends before the last samples",
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Only headers whose checksum matches are accepted, so a sync inside the audio is not taken for one
+fn parse_frame_header(data: &[u8]) -> Option<FrameHeader> {
+    if data.len() < 6 || data[0] != 0xFF || data[1] & 0xFE != 0xF8 {
+        return None;
+    }
+    let block_size_code = data[2] >> 4;
+    let sample_rate_code = data[2] & 0x0F;
+    let channels_code = data[3] >> 4;
+    let sample_size_code = (data[3] >> 1) & 0x07;
+    if block_size_code == 0
+        || sample_rate_code == 15
+        || channels_code > 10
+        || sample_size_code == 3
+        || data[3] & 0x01 != 0
+    {
+        return None;
+    }
+
+    // The number is coded like a UTF-8 character, in up to 7 bytes
+    let leading_ones = data[4].leading_ones() as usize;
+    let number_length = match leading_ones {
+        0 => 1,
+        2..=7 => leading_ones,
+        _ => return None,
+    };
+    let mut position = 4 + number_length;
+    if data.len() < position {
+        return None;
+    }
+    let mut number = match number_length {
+        1 => data[4] as u64,
+        _ => (data[4] & (0x7F >> number_length)) as u64,
+    };
+    for byte in &data[5..position] {
+        if byte & 0xC0 != 0x80 {
+            return None;
+        }
+        number = (number << 6) | (byte & 0x3F) as u64;
+    }
+
+    let block_size = match block_size_code {
+        1 => 192,
+        2..=5 => 576 << (block_size_code - 2),
+        6 => {
+            position += 1;
+            *data.get(position - 1)? as u64 + 1
+        }
+        7 => {
+            position += 2;
+            u16::from_be_bytes([*data.get(position - 2)?, *data.get(position - 1)?]) as u64 + 1
+        }
+        _ => 256 << (block_size_code - 8),
+    };
+    position += match sample_rate_code {
+        12 => 1,
+        13 | 14 => 2,
+        _ => 0,
+    };
+    if *data.get(position)? != crc8(&data[..position]) {
+        return None;
+    }
+
+    Some(FrameHeader {
+        number,
+        variable: data[1] & 0x01 != 0,
+        block_size,
+        format: [sample_rate_code, data[3]],
+    })
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| match crc & 0x80 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x07,
+        })
+    })
+}
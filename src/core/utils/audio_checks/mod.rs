@@ -0,0 +1,370 @@
+use crate::core::utils::audio_format::{detect_audio_format, AUDIO_HEADER_LENGTH};
+use crate::core::utils::storage::{self, StorageFile};
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+mod flac_check;
+mod mp4_check;
+mod mpeg_check;
+mod ogg_check;
+mod wav_check;
+
+const SAMPLE_FRAMES: usize = 8;
+const TAIL_LENGTH: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IssueReason {
+    BadHeader,
+    Truncated,
+    UnsupportedCodec,
+    NoMetadata,
+}
+
+impl IssueReason {
+    pub fn parse(code: &str) -> Option<Self> {
+        match code {
+            "bad_header" => Some(IssueReason::BadHeader),
+            "truncated" => Some(IssueReason::Truncated),
+            "unsupported_codec" => Some(IssueReason::UnsupportedCodec),
+            "no_metadata" => Some(IssueReason::NoMetadata),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for IssueReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IssueReason::BadHeader => write!(f, "bad_header"),
+            IssueReason::Truncated => write!(f, "truncated"),
+            IssueReason::UnsupportedCodec => write!(f, "unsupported_codec"),
+            IssueReason::NoMetadata => write!(f, "no_metadata"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Defect {
+    pub reason: IssueReason,
+    pub detail: String,
+}
+
+impl Defect {
+    pub fn new(reason: IssueReason, detail: impl Into<String>) -> Self {
+        Defect {
+            reason,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl From<io::Error> for Defect {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::UnexpectedEof => Defect::new(IssueReason::Truncated, "File ends early"),
+            _ => Defect::new(
+                IssueReason::BadHeader,
+                format!("File could not be read: {}", e),
+            ),
+        }
+    }
+}
+
+pub fn check_audio_file(path: &Path) -> Result<(), Defect> {
+    let mut file = storage::open(path)?;
+    let header = read_at(&mut file, 0, AUDIO_HEADER_LENGTH)?;
+    match detect_audio_format(&header) {
+        Some("mp3") => mpeg_check::check(&mut file),
+        Some("flac") => flac_check::check(&mut file),
+        Some("ogg") => ogg_check::check(&mut file),
+        Some("m4a") => mp4_check::check(&mut file),
+        Some("wav") => wav_check::check(&mut file),
+        _ => Err(Defect::new(
+            IssueReason::BadHeader,
+            "Not a known audio format",
+        )),
+    }
+}
+
+fn read_at(file: &mut StorageFile, offset: u64, length: usize) -> io::Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut data = Vec::with_capacity(length);
+    file.take(length as u64).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn read_tail(file: &mut StorageFile, min_offset: u64) -> io::Result<(u64, Vec<u8>)> {
+    let file_length = file.len()?;
+    let offset = file_length.saturating_sub(TAIL_LENGTH).max(min_offset);
+    let data = read_at(
+        file,
+        offset,
+        (file_length - offset.min(file_length)) as usize,
+    )?;
+    Ok((offset, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+
+    const MPEG_FRAME: [u8; 4] = [0xFF, 0xFB, 0x90, 0x00];
+    const MPEG_FRAME_LENGTH: usize = 417;
+    const PCM_FORMAT: u16 = 1;
+
+    fn check(data: &[u8]) -> Result<(), IssueReason> {
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join("song");
+        std::fs::write(&path, data).unwrap();
+        check_audio_file(&path).map_err(|defect| defect.reason)
+    }
+
+    fn check_table(cases: Vec<(&str, Vec<u8>, Result<(), IssueReason>)>) {
+        for (name, data, expected) in cases {
+            assert_eq!(check(&data), expected, "{}", name);
+        }
+    }
+
+    fn mpeg_frames(header: [u8; 4], count: usize) -> Vec<u8> {
+        let mut frame = vec![0; MPEG_FRAME_LENGTH];
+        frame[..4].copy_from_slice(&header);
+        frame.repeat(count)
+    }
+
+    fn id3_tag(size: usize) -> Vec<u8> {
+        let mut tag = b"ID3\x03\x00\x00".to_vec();
+        tag.extend(
+            (0..4)
+                .rev()
+                .map(|shift| ((size >> (shift * 7)) & 0x7F) as u8),
+        );
+        tag.resize(10 + size, 0);
+        tag
+    }
+
+    #[test]
+    fn mpeg_files() {
+        let frames = mpeg_frames(MPEG_FRAME, 20);
+        let with = |parts: &[&[u8]]| parts.concat();
+        check_table(vec![
+            ("plain", frames.clone(), Ok(())),
+            ("tagged", with(&[&id3_tag(1000), &frames]), Ok(())),
+            (
+                "padded after the tag",
+                with(&[&id3_tag(1000), &[0; 300], &frames]),
+                Ok(()),
+            ),
+            (
+                "ID3v1 at the end",
+                with(&[&frames, b"TAG", &[0; 125]]),
+                Ok(()),
+            ),
+            (
+                "cut in the last frame",
+                frames[..frames.len() - 200].to_vec(),
+                Err(IssueReason::Truncated),
+            ),
+            (
+                "cut in the tag",
+                id3_tag(1000)[..500].to_vec(),
+                Err(IssueReason::Truncated),
+            ),
+            (
+                "free format",
+                mpeg_frames([0xFF, 0xFB, 0x00, 0x00], 20),
+                Err(IssueReason::UnsupportedCodec),
+            ),
+            (
+                "tag without frames",
+                with(&[&id3_tag(100), &[0x55; 4000]]),
+                Err(IssueReason::BadHeader),
+            ),
+            (
+                "not audio",
+                b"Just some text".to_vec(),
+                Err(IssueReason::BadHeader),
+            ),
+        ]);
+    }
+
+    fn flac_frame(number: u8) -> Vec<u8> {
+        let mut frame = vec![0xFF, 0xF8, 0xC9, 0x18, number];
+        let checksum = frame.iter().fold(0u8, |crc, byte| {
+            (0..8).fold(crc ^ byte, |crc, _| match crc & 0x80 {
+                0 => crc << 1,
+                _ => (crc << 1) ^ 0x07,
+            })
+        });
+        frame.push(checksum);
+        frame.resize(100, 0x11);
+        frame
+    }
+
+    fn flac(total_samples: u32, frames: u8) -> Vec<u8> {
+        let mut stream_info = vec![0; 34];
+        stream_info[2..4].copy_from_slice(&4096u16.to_be_bytes());
+        stream_info[14..18].copy_from_slice(&total_samples.to_be_bytes());
+        let mut data = b"fLaC\x80\x00\x00\x22".to_vec();
+        data.extend(stream_info);
+        data.extend((0..frames).flat_map(flac_frame));
+        data
+    }
+
+    #[test]
+    fn flac_files() {
+        let mut without_stream_info = flac(0, 3);
+        without_stream_info[4] = 0x81;
+        check_table(vec![
+            ("complete", flac(3 * 4096, 3), Ok(())),
+            ("without a sample count", flac(0, 3), Ok(())),
+            (
+                "missing its last frame",
+                flac(3 * 4096, 2),
+                Err(IssueReason::Truncated),
+            ),
+            (
+                "without frames",
+                flac(3 * 4096, 0),
+                Err(IssueReason::Truncated),
+            ),
+            (
+                "cut in the metadata",
+                flac(3 * 4096, 0)[..20].to_vec(),
+                Err(IssueReason::Truncated),
+            ),
+            (
+                "without stream info",
+                without_stream_info,
+                Err(IssueReason::BadHeader),
+            ),
+        ]);
+    }
+
+    fn ogg(first_packet: &[u8], end: PacketWriteEndInfo) -> Vec<u8> {
+        let mut writer = PacketWriter::new(Vec::new());
+        let packets = [first_packet, &[2; 200], &[3; 200]];
+        for (index, packet) in packets.iter().enumerate() {
+            let end = match index {
+                2 => end,
+                _ => PacketWriteEndInfo::EndPage,
+            };
+            let packet = packet.to_vec().into_boxed_slice();
+            writer.write_packet(packet, 1, end, index as u64).unwrap();
+        }
+        writer.into_inner()
+    }
+
+    #[test]
+    fn ogg_files() {
+        let vorbis = ogg(b"\x01vorbis header", PacketWriteEndInfo::EndStream);
+        let mut corrupted = vorbis.clone();
+        corrupted[40] ^= 0xFF;
+        check_table(vec![
+            ("vorbis", vorbis.clone(), Ok(())),
+            (
+                "opus",
+                ogg(b"OpusHead", PacketWriteEndInfo::EndStream),
+                Ok(()),
+            ),
+            (
+                "cut in the last page",
+                vorbis[..vorbis.len() - 50].to_vec(),
+                Err(IssueReason::Truncated),
+            ),
+            (
+                "without an end",
+                ogg(b"\x01vorbis header", PacketWriteEndInfo::EndPage),
+                Err(IssueReason::Truncated),
+            ),
+            (
+                "speex",
+                ogg(b"Speex   header", PacketWriteEndInfo::EndStream),
+                Err(IssueReason::UnsupportedCodec),
+            ),
+            ("bad checksum", corrupted, Err(IssueReason::BadHeader)),
+        ]);
+    }
+
+    fn atom(kind: &[u8; 4], children: &[Vec<u8>]) -> Vec<u8> {
+        let payload = children.concat();
+        let mut atom = ((8 + payload.len()) as u32).to_be_bytes().to_vec();
+        atom.extend_from_slice(kind);
+        atom.extend(payload);
+        atom
+    }
+
+    fn mp4(codec: &[u8; 4], movie_first: bool, chunk_offset: u32) -> Vec<u8> {
+        let descriptions = [&[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 16][..], codec, &[0; 8]].concat();
+        let offsets = [&[0, 0, 0, 0, 0, 0, 0, 1][..], &chunk_offset.to_be_bytes()].concat();
+        let sample_table = atom(
+            b"stbl",
+            &[atom(b"stsd", &[descriptions]), atom(b"stco", &[offsets])],
+        );
+        let track = atom(b"trak", &[atom(b"mdia", &[atom(b"minf", &[sample_table])])]);
+        let movie = atom(b"moov", &[track]);
+        let file_type = atom(b"ftyp", &[b"M4A \x00\x00\x00\x00".to_vec()]);
+        let media_data = atom(b"mdat", &[vec![0; 1000]]);
+        match movie_first {
+            true => [file_type, movie, media_data].concat(),
+            false => [file_type, media_data, movie].concat(),
+        }
+    }
+
+    #[test]
+    fn mp4_files() {
+        let movie_last = mp4(b"mp4a", false, 24);
+        check_table(vec![
+            ("movie last", movie_last.clone(), Ok(())),
+            ("movie first", mp4(b"alac", true, 200), Ok(())),
+            (
+                "cut in the media data",
+                mp4(b"mp4a", true, 200)[..400].to_vec(),
+                Err(IssueReason::Truncated),
+            ),
+            (
+                "cut before the movie",
+                movie_last[..24 + 1008].to_vec(),
+                Err(IssueReason::Truncated),
+            ),
+            (
+                "chunks past the end",
+                mp4(b"mp4a", false, 100_000),
+                Err(IssueReason::Truncated),
+            ),
+            (
+                "video only",
+                mp4(b"avc1", true, 200),
+                Err(IssueReason::UnsupportedCodec),
+            ),
+        ]);
+    }
+
+    fn wav(format: u16, data_size: u32, data_length: usize) -> Vec<u8> {
+        let mut data = b"RIFF\x00\x00\x00\x00WAVEfmt \x10\x00\x00\x00".to_vec();
+        data.extend_from_slice(&format.to_le_bytes());
+        data.extend_from_slice(&[0; 14]);
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&data_size.to_le_bytes());
+        data.resize(data.len() + data_length, 0);
+        data
+    }
+
+    #[test]
+    fn wav_files() {
+        check_table(vec![
+            ("pcm", wav(PCM_FORMAT, 1000, 1000), Ok(())),
+            (
+                "cut",
+                wav(PCM_FORMAT, 1000, 500),
+                Err(IssueReason::Truncated),
+            ),
+            (
+                "mp3 inside",
+                wav(0x55, 1000, 1000),
+                Err(IssueReason::UnsupportedCodec),
+            ),
+        ]);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::core::utils::audio_checks::{read_at, Defect, IssueReason};
+use crate::core::utils::storage::StorageFile;
+
+const AUDIO_CODECS: [&[u8; 4]; 4] = [b"mp4a", b"alac", b"fLaC", b"Opus"];
+
+struct Atom<'a> {
+    kind: [u8; 4],
+    payload: &'a [u8],
+}
+
+pub fn check(file: &mut StorageFile) -> Result<(), Defect> {
+    let file_length = file.len()?;
+    let mut movie = None;
+    let mut has_media_data = false;
+    let mut offset = 0;
+    while offset < file_length {
+        let header = read_at(file, offset, 16)?;
+        if header.len() < 8 {
+            return Err(Defect::new(
+                IssueReason::Truncated,
+                "Atom header ends early",
+            ));
+        }
+        let kind = [header[4], header[5], header[6], header[7]];
+        let (header_length, size) =
+            match u32::from_be_bytes([header[0], header[1], header[2], header[3]]) {
+                0 => (8, file_length - offset),
+                1 if header.len() == 16 => {
+                    let mut large_size = [0; 8];
+                    large_size.copy_from_slice(&header[8..]);
+                    (16, u64::from_be_bytes(large_size))
+                }
+                1 => {
+                    return Err(Defect::new(
+                        IssueReason::Truncated,
+                        "Atom header ends early",
+                    ))
+                }
+                size => (8, size as u64),
+            };
+        if size < header_length {
+            return Err(Defect::new(IssueReason::BadHeader, "Invalid atom size"));
+        }
+        if offset + size > file_length {
+            return Err(Defect::new(
+                IssueReason::Truncated,
+                format!("{} atom runs past the end of the file", kind_name(&kind)),
+            ));
+        }
+        match &kind {
+            b"moov" => movie = Some((offset + header_length, (size - header_length) as usize)),
+            b"mdat" => has_media_data = true,
+            _ => {}
+        }
+        offset += size;
+    }
+
+    // Files are often written with the movie atom last
+    let (movie_offset, movie_length) = match movie {
+        Some(movie) => movie,
+        None if has_media_data => {
+            return Err(Defect::new(IssueReason::Truncated, "Movie atom is missing"))
+        }
+        None => return Err(Defect::new(IssueReason::BadHeader, "Movie atom is missing")),
+    };
+    let movie = read_at(file, movie_offset, movie_length)?;
+    let mut codecs = Vec::new();
+    for track in children(&movie)?
+        .iter()
+        .filter(|atom| &atom.kind == b"trak")
+    {
+        let sample_table = find_path(track.payload, &[b"mdia", b"minf", b"stbl"])?;
+        let sample_table = match sample_table {
+            Some(sample_table) => sample_table,
+            None => continue,
+        };
+        let codec = match find_path(sample_table, &[b"stsd"])? {
+            Some(descriptions) if descriptions.len() >= 16 => [
+                descriptions[12],
+                descriptions[13],
+                descriptions[14],
+                descriptions[15],
+            ],
+            _ => continue,
+        };
+        if !AUDIO_CODECS.contains(&&codec) {
+            codecs.push(kind_name(&codec));
+            continue;
+        }
+        if last_chunk_offset(sample_table)? >= file_length {
+            return Err(Defect::new(IssueReason::Truncated, "Audio data ends early"));
+        }
+        return Ok(());
+    }
+    let detail = if codecs.is_empty() {
+        String::from("No audio track")
+    } else {
+        format!(
+            "No AAC, ALAC, FLAC or Opus track, found {}",
+            codecs.join(", ")
+        )
+    };
+    Err(Defect::new(IssueReason::UnsupportedCodec, detail))
+}
+
+fn last_chunk_offset(sample_table: &[u8]) -> Result<u64, Defect> {
+    let (table, entry_length) = match find_path(sample_table, &[b"stco"])? {
+        Some(table) => (table, 4),
+        None => match find_path(sample_table, &[b"co64"])? {
+            Some(table) => (table, 8),
+            None => return Ok(0),
+        },
+    };
+    if table.len() < 8 {
+        return Err(Defect::new(IssueReason::BadHeader, "Invalid chunk offsets"));
+    }
+    let count = u32::from_be_bytes([table[4], table[5], table[6], table[7]]) as usize;
+    if count == 0 {
+        return Ok(0);
+    }
+    let entry_start = 8 + (count - 1) * entry_length;
+    let entry = table
+        .get(entry_start..entry_start + entry_length)
+        .ok_or_else(|| Defect::new(IssueReason::BadHeader, "Invalid chunk offsets"))?;
+    Ok(entry
+        .iter()
+        .fold(0, |offset, byte| (offset << 8) | *byte as u64))
+}
+
+fn find_path<'a>(payload: &'a [u8], path: &[&[u8; 4]]) -> Result<Option<&'a [u8]>, Defect> {
+    let mut current = payload;
+    for kind in path {
+        match children(current)?
+            .into_iter()
+            .find(|atom| &atom.kind == *kind)
+        {
+            Some(atom) => current = atom.payload,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(current))
+}
+
+fn children(payload: &[u8]) -> Result<Vec<Atom<'_>>, Defect> {
+    let mut atoms = Vec::new();
+    let mut position = 0;
+    while position + 8 <= payload.len() {
+        let data = &payload[position..];
+        let size = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        let size = if size == 0 { data.len() } else { size };
+        if size < 8 || size > data.len() {
+            return Err(Defect::new(IssueReason::BadHeader, "Invalid atom size"));
+        }
+        atoms.push(Atom {
+            kind: [data[4], data[5], data[6], data[7]],
+            payload: &data[8..size],
+        });
+        position += size;
+    }
+    Ok(atoms)
+}
+
+fn kind_name(kind: &[u8; 4]) -> String {
+    String::from_utf8_lossy(kind).trim().to_string()
+}
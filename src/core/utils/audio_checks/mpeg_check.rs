@@ -0,0 +1,193 @@
+use crate::core::utils::audio_checks::{read_at, read_tail, Defect, IssueReason, SAMPLE_FRAMES};
+use crate::core::utils::storage::StorageFile;
+
+// Frames are searched for this far past the ID3 tag, as some files have padding or junk first
+const SYNC_SEARCH_LENGTH: usize = 64 * 1024;
+const MAX_FRAME_LENGTH: usize = 2881;
+const TAIL_FRAMES: usize = 3;
+
+const BITRATES: [[[u16; 15]; 3]; 2] = [
+    [
+        [
+            0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+        ],
+        [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+        ],
+        [
+            0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+        ],
+    ],
+    [
+        [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+        ],
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+    ],
+];
+const SAMPLE_RATES: [[u32; 3]; 3] = [
+    [44100, 48000, 32000],
+    [22050, 24000, 16000],
+    [11025, 12000, 8000],
+];
+
+enum Frame {
+    Valid(usize),
+    // Frames without a bitrate have to be decoded to find where they end
+    FreeFormat,
+    Invalid,
+}
+
+enum ChainEnd {
+    Sampled,
+    FileEnd,
+    Cut,
+    Broken,
+}
+
+pub fn check(file: &mut StorageFile) -> Result<(), Defect> {
+    let file_length = file.len()?;
+    let audio_start = id3_length(&read_at(file, 0, 10)?);
+    if audio_start >= file_length {
+        return Err(Defect::new(
+            IssueReason::Truncated,
+            "ID3 tag runs past the end of the file",
+        ));
+    }
+
+    let start_length = SYNC_SEARCH_LENGTH + (SAMPLE_FRAMES + 1) * MAX_FRAME_LENGTH;
+    let start = read_at(file, audio_start, start_length)?;
+    let at_file_end = audio_start + start.len() as u64 == file_length;
+    check_start(&start, at_file_end)?;
+
+    let (_, tail) = read_tail(file, audio_start)?;
+    check_tail(&tail)
+}
+
+// The first frames have to follow each other, a single sync could be part of the padding
+fn check_start(data: &[u8], at_file_end: bool) -> Result<(), Defect> {
+    let search_end = data.len().min(SYNC_SEARCH_LENGTH);
+    for offset in 0..search_end {
+        match parse_frame(&data[offset..]) {
+            Frame::Valid(_) => {}
+            Frame::FreeFormat => {
+                return Err(Defect::new(
+                    IssueReason::UnsupportedCodec,
+                    "Free format MPEG audio",
+                ))
+            }
+            Frame::Invalid => continue,
+        }
+        match follow_frames(data, offset, SAMPLE_FRAMES, at_file_end) {
+            (count, ChainEnd::Sampled) | (count, ChainEnd::FileEnd) if count > 0 => return Ok(()),
+            (count, ChainEnd::Cut) if count > 0 => {
+                return Err(Defect::new(IssueReason::Truncated, "Last frame ends early"))
+            }
+            _ => {}
+        }
+    }
+    Err(Defect::new(
+        IssueReason::BadHeader,
+        "No MPEG audio frames found",
+    ))
+}
+
+fn check_tail(data: &[u8]) -> Result<(), Defect> {
+    for offset in 0..data.len() {
+        if !matches!(parse_frame(&data[offset..]), Frame::Valid(_)) {
+            continue;
+        }
+        match follow_frames(data, offset, usize::MAX, true) {
+            (count, ChainEnd::FileEnd) if count >= TAIL_FRAMES => return Ok(()),
+            (count, ChainEnd::Cut) if count >= TAIL_FRAMES => {
+                return Err(Defect::new(IssueReason::Truncated, "Last frame ends early"))
+            }
+            _ => {}
+        }
+    }
+    // Nothing conclusive, like a long tag at the end
+    Ok(())
+}
+
+fn follow_frames(data: &[u8], offset: usize, limit: usize, at_file_end: bool) -> (usize, ChainEnd) {
+    let mut position = offset;
+    let mut count = 0;
+    loop {
+        if count == limit {
+            return (count, ChainEnd::Sampled);
+        }
+        let rest = &data[position..];
+        if at_file_end && (rest.is_empty() || is_trailing_tag(rest)) {
+            return (count, ChainEnd::FileEnd);
+        }
+        if rest.len() < 4 {
+            let end = if at_file_end {
+                ChainEnd::Cut
+            } else {
+                ChainEnd::Sampled
+            };
+            return (count, end);
+        }
+        match parse_frame(rest) {
+            Frame::Valid(length) if length <= rest.len() => {
+                count += 1;
+                position += length;
+            }
+            Frame::Valid(_) if at_file_end => return (count, ChainEnd::Cut),
+            Frame::Valid(_) => return (count, ChainEnd::Sampled),
+            _ => return (count, ChainEnd::Broken),
+        }
+    }
+}
+
+fn parse_frame(header: &[u8]) -> Frame {
+    if header.len() < 4 || header[0] != 0xFF || header[1] & 0xE0 != 0xE0 {
+        return Frame::Invalid;
+    }
+    let version = (header[1] >> 3) & 0x03;
+    let layer = (header[1] >> 1) & 0x03;
+    let bitrate_index = (header[2] >> 4) as usize;
+    let sample_rate_index = ((header[2] >> 2) & 0x03) as usize;
+    let padding = ((header[2] >> 1) & 0x01) as usize;
+    if version == 1 || layer == 0 || bitrate_index == 15 || sample_rate_index == 3 {
+        return Frame::Invalid;
+    }
+    if header[3] & 0x03 == 2 {
+        return Frame::Invalid;
+    }
+    if bitrate_index == 0 {
+        return Frame::FreeFormat;
+    }
+
+    let is_mpeg1 = version == 3;
+    let layer_index = 3 - layer as usize;
+    let bitrate = BITRATES[!is_mpeg1 as usize][layer_index][bitrate_index] as usize * 1000;
+    let sample_rate = match version {
+        3 => SAMPLE_RATES[0][sample_rate_index],
+        2 => SAMPLE_RATES[1][sample_rate_index],
+        _ => SAMPLE_RATES[2][sample_rate_index],
+    } as usize;
+    let length = match layer_index {
+        0 => (12 * bitrate / sample_rate + padding) * 4,
+        2 if !is_mpeg1 => 72 * bitrate / sample_rate + padding,
+        _ => 144 * bitrate / sample_rate + padding,
+    };
+    Frame::Valid(length)
+}
+
+fn id3_length(header: &[u8]) -> u64 {
+    if header.len() < 10 || !header.starts_with(b"ID3") {
+        return 0;
+    }
+    // Sizes are stored in 7 bits a byte
+    let size = header[6..10]
+        .iter()
+        .fold(0u64, |size, byte| (size << 7) | (*byte & 0x7F) as u64);
+    let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+    10 + size + footer
+}
+
+fn is_trailing_tag(data: &[u8]) -> bool {
+    data.starts_with(b"TAG") || data.starts_with(b"APETAGEX") || data.starts_with(b"LYRICS")
+}
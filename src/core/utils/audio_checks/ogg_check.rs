@@ -0,0 +1,65 @@
+use crate::core::utils::audio_checks::{read_tail, Defect, IssueReason, SAMPLE_FRAMES};
+use crate::core::utils::storage::StorageFile;
+use ogg::{OggReadError, PacketReader};
+use std::io::{BufReader, Seek, SeekFrom};
+
+const CODEC_HEADERS: [&[u8]; 3] = [b"\x01vorbis", b"OpusHead", b"\x7FFLAC"];
+const PAGE_HEADER_LENGTH: usize = 27;
+const END_OF_STREAM: u8 = 0x04;
+
+pub fn check(file: &mut StorageFile) -> Result<(), Defect> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut reader = PacketReader::new(BufReader::new(&mut *file));
+    // The reader checks the checksum of every page it reads
+    let first_packet = reader
+        .read_packet()
+        .map_err(from_ogg_error)?
+        .ok_or_else(|| Defect::new(IssueReason::Truncated, "No packets"))?;
+    if !CODEC_HEADERS
+        .iter()
+        .any(|header| first_packet.data.starts_with(header))
+    {
+        return Err(Defect::new(
+            IssueReason::UnsupportedCodec,
+            "Stream is not Vorbis, Opus or FLAC",
+        ));
+    }
+    for _ in 1..SAMPLE_FRAMES {
+        if reader.read_packet().map_err(from_ogg_error)?.is_none() {
+            break;
+        }
+    }
+    drop(reader);
+
+    let (_, tail) = read_tail(file, 0)?;
+    let last_page = (0..tail.len())
+        .rev()
+        .find(|position| tail[*position..].starts_with(b"OggS\0"));
+    if let Some(position) = last_page {
+        let page = &tail[position..];
+        if page.len() < PAGE_HEADER_LENGTH
+            || page.len() < PAGE_HEADER_LENGTH + page[PAGE_HEADER_LENGTH - 1] as usize
+        {
+            return Err(Defect::new(IssueReason::Truncated, "Last page ends early"));
+        }
+        let segments = page[PAGE_HEADER_LENGTH - 1] as usize;
+        let body_length: usize = page[PAGE_HEADER_LENGTH..PAGE_HEADER_LENGTH + segments]
+            .iter()
+            .map(|length| *length as usize)
+            .sum();
+        if page.len() < PAGE_HEADER_LENGTH + segments + body_length {
+            return Err(Defect::new(IssueReason::Truncated, "Last page ends early"));
+        }
+        if page[5] & END_OF_STREAM == 0 {
+            return Err(Defect::new(IssueReason::Truncated, "Stream has no end"));
+        }
+    }
+    Ok(())
+}
+
+fn from_ogg_error(e: OggReadError) -> Defect {
+    match e {
+        OggReadError::ReadError(e) => Defect::from(e),
+        e => Defect::new(IssueReason::BadHeader, e.to_string()),
+    }
+}
@@ -0,0 +1,52 @@
+use crate::core::utils::audio_checks::{read_at, Defect, IssueReason};
+use crate::core::utils::storage::StorageFile;
+
+const PCM: u16 = 1;
+const FLOAT: u16 = 3;
+// The actual format is given by a sub format in the rest of the chunk
+const EXTENSIBLE: u16 = 0xFFFE;
+
+pub fn check(file: &mut StorageFile) -> Result<(), Defect> {
+    let file_length = file.len()?;
+    let mut format = None;
+    let mut offset = 12;
+    let data_size = loop {
+        let header = read_at(file, offset, 10)?;
+        if header.len() < 8 {
+            return Err(match format {
+                Some(_) => Defect::new(IssueReason::Truncated, "No audio data"),
+                None => Defect::new(IssueReason::BadHeader, "Format chunk is missing"),
+            });
+        }
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as u64;
+        match &header[..4] {
+            b"fmt " if header.len() == 10 => {
+                format = Some(u16::from_le_bytes([header[8], header[9]]));
+            }
+            b"data" => break size,
+            _ => {}
+        }
+        // Chunks are aligned to two bytes
+        offset += 8 + size + size % 2;
+    };
+
+    match format {
+        Some(PCM) | Some(FLOAT) | Some(EXTENSIBLE) => {}
+        Some(format) => {
+            return Err(Defect::new(
+                IssueReason::UnsupportedCodec,
+                format!("WAVE format {:#06x}", format),
+            ))
+        }
+        None => {
+            return Err(Defect::new(
+                IssueReason::BadHeader,
+                "Format chunk is missing",
+            ))
+        }
+    }
+    if offset + 8 + data_size > file_length {
+        return Err(Defect::new(IssueReason::Truncated, "Audio data ends early"));
+    }
+    Ok(())
+}
@@ -0,0 +1,30 @@
+pub const AUDIO_HEADER_LENGTH: usize = 12;
+
+pub fn detect_audio_format(header: &[u8]) -> Option<&'static str> {
+    if header.starts_with(b"ID3") || is_mpeg_frame_sync(header) {
+        Some("mp3")
+    } else if header.starts_with(b"fLaC") {
+        Some("flac")
+    } else if header.starts_with(b"OggS") {
+        Some("ogg")
+    } else if header.len() >= 12 && &header[0..4] == b"RIFF" && &header[8..12] == b"WAVE" {
+        Some("wav")
+    } else if header.len() >= 8 && &header[4..8] == b"ftyp" {
+        Some("m4a")
+    } else {
+        None
+    }
+}
+
+fn is_mpeg_frame_sync(header: &[u8]) -> bool {
+    header.len() >= 2 && header[0] == 0xFF && header[1] & 0xE0 == 0xE0
+}
+
+pub fn matches_extension(format: &str, extension: &str) -> bool {
+    let extension = extension.to_lowercase();
+    match format {
+        "ogg" => matches!(extension.as_str(), "ogg" | "oga" | "opus"),
+        "m4a" => matches!(extension.as_str(), "m4a" | "mp4"),
+        _ => format == extension,
+    }
+}
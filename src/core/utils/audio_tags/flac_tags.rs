@@ -0,0 +1,230 @@
+use crate::core::utils::audio_tags::vorbis_comments::{
+    is_front_cover, parse_picture, picture_bytes, VorbisComments, FRONT_COVER,
+};
+use crate::core::utils::audio_tags::AudioTags;
+use crate::core::utils::storage;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const STREAM_INFO: u8 = 0;
+const PADDING: u8 = 1;
+const VORBIS_COMMENT: u8 = 4;
+const PICTURE: u8 = 6;
+const MAX_BLOCK_LENGTH: usize = 0xFF_FFFF;
+// Leaves room for later edits without rewriting the whole file
+const PADDING_LENGTH: usize = 4096;
+
+type Block = (u8, Vec<u8>);
+
+pub fn read(path: &Path) -> Result<AudioTags, Box<dyn Error>> {
+    let blocks = read_blocks(&mut BufReader::new(storage::open(path)?))?;
+
+    let mut tags = match blocks.iter().find(|(kind, _)| *kind == VORBIS_COMMENT) {
+        Some((_, data)) => VorbisComments::parse(data)?.to_tags(),
+        None => AudioTags::default(),
+    };
+    let pictures: Vec<_> = blocks
+        .iter()
+        .filter(|(kind, _)| *kind == PICTURE)
+        .filter_map(|(_, data)| parse_picture(data).ok())
+        .collect();
+    tags.cover = pictures
+        .iter()
+        .find(|(picture_type, _)| *picture_type == FRONT_COVER)
+        .or_else(|| pictures.first())
+        .map(|(_, cover)| cover.clone());
+    Ok(tags)
+}
+
+pub fn write(source: &Path, target: &Path, tags: &AudioTags) -> Result<(), Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(source)?);
+    let blocks = read_blocks(&mut reader)?;
+
+    let mut comments = match blocks.iter().find(|(kind, _)| *kind == VORBIS_COMMENT) {
+        Some((_, data)) => VorbisComments::parse(data)?,
+        None => VorbisComments::default(),
+    };
+    comments.apply(tags);
+
+    // Stream info has to stay the first block
+    let mut new_blocks: Vec<Block> = blocks
+        .into_iter()
+        .filter(|(kind, data)| match *kind {
+            PADDING | VORBIS_COMMENT => false,
+            PICTURE => !is_front_cover(data),
+            _ => true,
+        })
+        .collect();
+    new_blocks.push((VORBIS_COMMENT, comments.to_bytes()));
+    if let Some(cover) = &tags.cover {
+        new_blocks.push((PICTURE, picture_bytes(cover)));
+    }
+    new_blocks.push((PADDING, vec![0; PADDING_LENGTH]));
+
+    let mut writer = BufWriter::new(File::create(target)?);
+    writer.write_all(b"fLaC")?;
+    let last_index = new_blocks.len() - 1;
+    for (index, (kind, data)) in new_blocks.iter().enumerate() {
+        if data.len() > MAX_BLOCK_LENGTH {
+            return Err("Tag data is too large for a FLAC file".into());
+        }
+        let last_flag = if index == last_index { 0x80 } else { 0 };
+        let length = (data.len() as u32).to_be_bytes();
+        writer.write_all(&[kind | last_flag, length[1], length[2], length[3]])?;
+        writer.write_all(data)?;
+    }
+    io::copy(&mut reader, &mut writer)?;
+    writer.into_inner()?.sync_all()?;
+    Ok(())
+}
+
+fn read_blocks(reader: &mut impl Read) -> Result<Vec<Block>, Box<dyn Error>> {
+    let mut marker = [0; 4];
+    reader.read_exact(&mut marker)?;
+    if &marker != b"fLaC" {
+        return Err("Not a FLAC file".into());
+    }
+
+    let mut blocks = Vec::new();
+    loop {
+        let mut header = [0; 4];
+        reader.read_exact(&mut header)?;
+        let kind = header[0] & 0x7F;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        let mut data = vec![0; length];
+        reader.read_exact(&mut data)?;
+        blocks.push((kind, data));
+        if header[0] & 0x80 != 0 {
+            break;
+        }
+    }
+
+    if blocks.first().map(|(kind, _)| *kind) != Some(STREAM_INFO) {
+        return Err("FLAC stream info is missing".into());
+    }
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::utils::audio_tags::Cover;
+    use std::fs;
+
+    const AUDIO: &[u8] = b"\xFF\xF8 audio frames";
+
+    fn block(kind: u8, last: bool, data: &[u8]) -> Vec<u8> {
+        let length = (data.len() as u32).to_be_bytes();
+        let flag = if last { 0x80 } else { 0 };
+        [&[kind | flag, length[1], length[2], length[3]][..], data].concat()
+    }
+
+    fn back_cover() -> Vec<u8> {
+        let mut picture = picture_bytes(&Cover {
+            mime_type: String::from("image/png"),
+            data: b"\x89PNG back".to_vec(),
+        });
+        picture[..4].copy_from_slice(&4u32.to_be_bytes());
+        picture
+    }
+
+    fn flac_file() -> Vec<u8> {
+        let comments = VorbisComments {
+            vendor: String::from("reference libFLAC 1.3.3"),
+            comments: vec![
+                (String::from("TITLE"), String::from("Old title")),
+                (String::from("ALBUMARTIST"), String::from("Various Artists")),
+            ],
+        };
+        [
+            &b"fLaC"[..],
+            &block(STREAM_INFO, false, &[7; 34]),
+            &block(VORBIS_COMMENT, false, &comments.to_bytes()),
+            &block(PICTURE, false, &back_cover()),
+            &block(PADDING, true, &[0; 16]),
+            AUDIO,
+        ]
+        .concat()
+    }
+
+    fn tags() -> AudioTags {
+        AudioTags {
+            title: Some(String::from("Hells Bells")),
+            artist: Some(String::from("AC/DC")),
+            year: Some(1980),
+            track: Some(1),
+            cover: Some(Cover {
+                mime_type: String::from("image/jpeg"),
+                data: vec![0xFF, 0xD8, 0xFF, 1, 2, 3],
+            }),
+            ..AudioTags::default()
+        }
+    }
+
+    fn tagged(file: &[u8], tags: &AudioTags) -> Vec<u8> {
+        let folder = tempfile::tempdir().unwrap();
+        let source = folder.path().join("song.flac");
+        let target = folder.path().join("song.tagging");
+        fs::write(&source, file).unwrap();
+        write(&source, &target, tags).unwrap();
+        fs::read(&target).unwrap()
+    }
+
+    #[test]
+    fn tags_are_read_back_as_written() {
+        let folder = tempfile::tempdir().unwrap();
+        let source = folder.path().join("song.flac");
+        let target = folder.path().join("song.tagging");
+        fs::write(&source, flac_file()).unwrap();
+        write(&source, &target, &tags()).unwrap();
+
+        let read_back = read(&target).unwrap();
+        assert_eq!(
+            read_back,
+            AudioTags {
+                album_artist: Some(String::from("Various Artists")),
+                ..tags()
+            }
+        );
+    }
+
+    #[test]
+    fn audio_and_other_blocks_are_kept() {
+        let once = tagged(&flac_file(), &tags());
+        let twice = tagged(&once, &tags());
+
+        assert!(once.ends_with(AUDIO));
+        assert_eq!(once, twice);
+        let blocks = read_blocks(&mut &once[..]).unwrap();
+        let kinds: Vec<u8> = blocks.iter().map(|(kind, _)| *kind).collect();
+        assert_eq!(
+            kinds,
+            vec![STREAM_INFO, PICTURE, VORBIS_COMMENT, PICTURE, PADDING]
+        );
+        assert_eq!(blocks[0].1, vec![7; 34]);
+        assert_eq!(blocks[1].1, back_cover());
+        assert_eq!(blocks[4].1.len(), PADDING_LENGTH);
+    }
+
+    #[test]
+    fn removed_cover_leaves_other_pictures() {
+        let with_cover = tagged(&flac_file(), &tags());
+        let without_cover = tagged(&with_cover, &AudioTags::default());
+        let blocks = read_blocks(&mut &without_cover[..]).unwrap();
+
+        let pictures: Vec<_> = blocks.iter().filter(|(kind, _)| *kind == PICTURE).collect();
+        assert_eq!(pictures.len(), 1);
+        assert!(!is_front_cover(&pictures[0].1));
+    }
+
+    #[test]
+    fn files_without_stream_info_are_refused() {
+        let file = [&b"fLaC"[..], &block(PADDING, true, &[0; 4]), AUDIO].concat();
+        assert!(read_blocks(&mut &file[..]).is_err());
+        assert!(read_blocks(&mut &b"ID3 not flac"[..]).is_err());
+        let truncated = flac_file();
+        assert!(read_blocks(&mut &truncated[..40]).is_err());
+    }
+}
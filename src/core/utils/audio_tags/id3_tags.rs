@@ -0,0 +1,87 @@
+use crate::core::utils::audio_tags::{AudioTags, Cover};
+use crate::core::utils::storage;
+use id3::frame::{Picture, PictureType};
+use id3::{ErrorKind, Tag, TagLike, Version};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+pub fn read(path: &Path) -> Result<AudioTags, Box<dyn Error>> {
+    from_result(Tag::read_from2(storage::open(path)?))
+}
+
+pub fn write(source: &Path, target: &Path, tags: &AudioTags) -> Result<(), Box<dyn Error>> {
+    let tag = with_tags(read_existing(Tag::read_from_path(source))?, tags);
+    fs::copy(source, target)?;
+    tag.write_to_path(target, Version::Id3v24)?;
+    Ok(())
+}
+
+fn read_existing(result: id3::Result<Tag>) -> Result<Tag, Box<dyn Error>> {
+    match result {
+        Ok(tag) => Ok(tag),
+        Err(e) if matches!(e.kind, ErrorKind::NoTag) => Ok(Tag::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn from_result(result: id3::Result<Tag>) -> Result<AudioTags, Box<dyn Error>> {
+    let tag = read_existing(result)?;
+    let cover = tag
+        .pictures()
+        .find(|picture| picture.picture_type == PictureType::CoverFront)
+        .or_else(|| tag.pictures().next())
+        .map(|picture| Cover {
+            mime_type: picture.mime_type.clone(),
+            data: picture.data.clone(),
+        });
+
+    Ok(AudioTags {
+        title: tag.title().map(String::from),
+        artist: tag.artist().map(String::from),
+        album: tag.album().map(String::from),
+        genre: tag.genre_parsed().map(|genre| genre.into_owned()),
+        year: tag.year(),
+        track: tag.track(),
+        cover,
+        album_artist: tag.album_artist().map(String::from),
+        disc: tag.disc(),
+    })
+}
+
+fn with_tags(mut tag: Tag, tags: &AudioTags) -> Tag {
+    match &tags.title {
+        Some(title) => tag.set_title(title.as_str()),
+        None => tag.remove_title(),
+    }
+    match &tags.artist {
+        Some(artist) => tag.set_artist(artist.as_str()),
+        None => tag.remove_artist(),
+    }
+    match &tags.album {
+        Some(album) => tag.set_album(album.as_str()),
+        None => tag.remove_album(),
+    }
+    match &tags.genre {
+        Some(genre) => tag.set_genre(genre.as_str()),
+        None => tag.remove_genre(),
+    }
+    match tags.year {
+        Some(year) => tag.set_year(year),
+        None => tag.remove_year(),
+    }
+    match tags.track {
+        Some(track) => tag.set_track(track),
+        None => tag.remove_track(),
+    }
+    tag.remove_picture_by_type(PictureType::CoverFront);
+    if let Some(cover) = &tags.cover {
+        tag.add_frame(Picture {
+            mime_type: cover.mime_type.clone(),
+            picture_type: PictureType::CoverFront,
+            description: String::new(),
+            data: cover.data.clone(),
+        });
+    }
+    tag
+}
@@ -0,0 +1,141 @@
+use crate::core::utils::audio_format::{detect_audio_format, AUDIO_HEADER_LENGTH};
+use crate::core::utils::storage;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+mod flac_tags;
+mod id3_tags;
+mod mp4_tags;
+mod ogg_tags;
+mod vorbis_comments;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AudioTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub genre: Option<String>,
+    pub year: Option<i32>,
+    pub track: Option<u32>,
+    pub cover: Option<Cover>,
+    // Only read, writing keeps the values the file already has
+    pub album_artist: Option<String>,
+    pub disc: Option<u32>,
+}
+
+impl AudioTags {
+    pub fn apply(&mut self, overrides: &AudioTags) {
+        if overrides.title.is_some() {
+            self.title = overrides.title.clone();
+        }
+        if overrides.artist.is_some() {
+            self.artist = overrides.artist.clone();
+        }
+        if overrides.album.is_some() {
+            self.album = overrides.album.clone();
+        }
+        if overrides.genre.is_some() {
+            self.genre = overrides.genre.clone();
+        }
+        if overrides.year.is_some() {
+            self.year = overrides.year;
+        }
+        if overrides.track.is_some() {
+            self.track = overrides.track;
+        }
+        if overrides.cover.is_some() {
+            self.cover = overrides.cover.clone();
+        }
+        if overrides.album_artist.is_some() {
+            self.album_artist = overrides.album_artist.clone();
+        }
+        if overrides.disc.is_some() {
+            self.disc = overrides.disc;
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cover {
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+pub fn read_tags(path: &Path) -> Result<AudioTags, Box<dyn Error>> {
+    match detect_format(path)? {
+        "mp3" | "wav" => id3_tags::read(path),
+        "flac" => flac_tags::read(path),
+        "ogg" => ogg_tags::read(path),
+        "m4a" => mp4_tags::read(path),
+        format => Err(format!("Tags of {} files are not supported", format).into()),
+    }
+}
+
+pub fn write_tags(path: &Path, tags: &AudioTags) -> Result<(), Box<dyn Error>> {
+    if storage::has_events(path) {
+        return write_local_tags(path, tags);
+    }
+    // Objects can only be replaced whole, so a local copy is tagged and uploaded
+    let local_file = tempfile::Builder::new().prefix("hyppo-").tempfile()?;
+    let local_path = local_file.path().to_path_buf();
+    io::copy(&mut storage::open(path)?, &mut local_file.as_file())?;
+    write_local_tags(&local_path, tags)?;
+    let data = fs::read(&local_path)?;
+    storage::write(path, &data)?;
+
+    // The copy is removed when dropped as well, closing it tells whether that failed
+    if let Err(e) = local_file.close() {
+        eprintln!("Could not remove temporary tag file:\n{}", e);
+    }
+    Ok(())
+}
+
+fn write_local_tags(path: &Path, tags: &AudioTags) -> Result<(), Box<dyn Error>> {
+    let format = detect_format(path)?;
+    let temp_path = path.with_extension("tagging");
+
+    let result = match format {
+        "mp3" | "wav" => id3_tags::write(path, &temp_path, tags),
+        "flac" => flac_tags::write(path, &temp_path, tags),
+        "ogg" => ogg_tags::write(path, &temp_path, tags),
+        "m4a" => mp4_tags::write(path, &temp_path, tags),
+        format => Err(format!("Tags of {} files are not supported", format).into()),
+    }
+    .and_then(|_| fs::rename(&temp_path, path).map_err(|e| e.into()));
+
+    if result.is_err() && temp_path.exists() {
+        if let Err(e) = fs::remove_file(&temp_path) {
+            eprintln!("Could not remove temporary tag file:\n{}", e);
+        }
+    }
+    result
+}
+
+fn detect_format(path: &Path) -> Result<&'static str, Box<dyn Error>> {
+    let mut header = vec![0; AUDIO_HEADER_LENGTH];
+    let header_length = storage::open(path)?.read(&mut header)?;
+    detect_audio_format(&header[..header_length]).ok_or_else(|| "Unknown audio format".into())
+}
+
+fn parse_year(date: &str) -> Option<i32> {
+    date.get(..4).and_then(|year| year.parse().ok())
+}
+
+fn parse_track(track: &str) -> Option<u32> {
+    track
+        .split('/')
+        .next()
+        .and_then(|track| track.trim().parse().ok())
+}
+
+pub fn detect_image_mime_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if data.starts_with(b"\x89PNG") {
+        Some("image/png")
+    } else {
+        None
+    }
+}
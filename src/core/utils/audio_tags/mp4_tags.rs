@@ -0,0 +1,573 @@
+use crate::core::utils::audio_tags::{detect_image_mime_type, parse_year, AudioTags, Cover};
+use crate::core::utils::storage::{self, StorageFile};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const TITLE: &[u8; 4] = b"\xA9nam";
+const ARTIST: &[u8; 4] = b"\xA9ART";
+const ALBUM: &[u8; 4] = b"\xA9alb";
+const GENRE: &[u8; 4] = b"\xA9gen";
+const GENRE_INDEX: &[u8; 4] = b"gnre";
+const YEAR: &[u8; 4] = b"\xA9day";
+const TRACK: &[u8; 4] = b"trkn";
+const COVER: &[u8; 4] = b"covr";
+const ALBUM_ARTIST: &[u8; 4] = b"aART";
+const DISC: &[u8; 4] = b"disk";
+const MANAGED_ITEMS: [&[u8; 4]; 8] = [TITLE, ARTIST, ALBUM, GENRE, GENRE_INDEX, YEAR, TRACK, COVER];
+
+const TEXT_TYPE: u32 = 1;
+const JPEG_TYPE: u32 = 13;
+const PNG_TYPE: u32 = 14;
+const IMPLICIT_TYPE: u32 = 0;
+
+// The iTunes metadata handler, required for the tags to be found by players
+const METADATA_HANDLER: [u8; 25] = [
+    0, 0, 0, 0, 0, 0, 0, 0, b'm', b'd', b'i', b'r', b'a', b'p', b'p', b'l', 0, 0, 0, 0, 0, 0, 0, 0,
+    0,
+];
+
+struct AtomHeader {
+    kind: [u8; 4],
+    offset: u64,
+    size: u64,
+}
+
+struct Atom<'a> {
+    kind: [u8; 4],
+    payload: &'a [u8],
+    raw: &'a [u8],
+}
+
+pub fn read(path: &Path) -> Result<AudioTags, Box<dyn Error>> {
+    let mut file = storage::open(path)?;
+    let moov_header = find_movie(&read_top_level(&mut file)?)?;
+    let moov = read_payload(&mut file, &moov_header)?;
+
+    let ilst = match find_item_list(&moov)? {
+        Some(ilst) => ilst,
+        None => return Ok(AudioTags::default()),
+    };
+
+    let mut tags = AudioTags::default();
+    for item in children(ilst)? {
+        let (type_code, value) = match find_child(item.payload, b"data")? {
+            Some(data) if data.len() >= 8 => (
+                u32::from_be_bytes([0, data[1], data[2], data[3]]),
+                &data[8..],
+            ),
+            _ => continue,
+        };
+        let text = || String::from_utf8_lossy(value).into_owned();
+        match &item.kind {
+            TITLE => tags.title = Some(text()),
+            ARTIST => tags.artist = Some(text()),
+            ALBUM => tags.album = Some(text()),
+            GENRE => tags.genre = Some(text()),
+            YEAR => tags.year = parse_year(&text()),
+            TRACK if value.len() >= 4 => {
+                tags.track = Some(u16::from_be_bytes([value[2], value[3]]) as u32)
+            }
+            ALBUM_ARTIST => tags.album_artist = Some(text()),
+            DISC if value.len() >= 4 => {
+                tags.disc = Some(u16::from_be_bytes([value[2], value[3]]) as u32)
+            }
+            COVER => {
+                let mime_type = match type_code {
+                    PNG_TYPE => Some("image/png"),
+                    JPEG_TYPE => Some("image/jpeg"),
+                    _ => detect_image_mime_type(value),
+                };
+                tags.cover = Some(Cover {
+                    mime_type: mime_type.unwrap_or("image/jpeg").to_string(),
+                    data: value.to_vec(),
+                });
+            }
+            _ => {}
+        }
+    }
+    Ok(tags)
+}
+
+fn find_item_list(moov: &[u8]) -> Result<Option<&[u8]>, Box<dyn Error>> {
+    let udta = match find_child(moov, b"udta")? {
+        Some(udta) => udta,
+        None => return Ok(None),
+    };
+    match find_child(udta, b"meta")? {
+        Some(meta) if meta.len() >= 4 => find_child(&meta[4..], b"ilst"),
+        _ => Ok(None),
+    }
+}
+
+// Media data stored after the movie atom moves with it, so its chunk offsets are corrected
+pub fn write(source: &Path, target: &Path, tags: &AudioTags) -> Result<(), Box<dyn Error>> {
+    let mut file = storage::open(source)?;
+    let atoms = read_top_level(&mut file)?;
+    if atoms.iter().any(|atom| &atom.kind == b"moof") {
+        return Err("Fragmented MP4 files are not supported".into());
+    }
+    let moov_header = find_movie(&atoms)?;
+    let moov = read_payload(&mut file, &moov_header)?;
+
+    let mut new_moov = atom(b"moov", &tagged_container(&moov, tags, b"udta")?)?;
+    let delta = new_moov.len() as i64 - moov_header.size as i64;
+    if delta != 0 {
+        let moved_from = moov_header.offset + moov_header.size;
+        shift_chunk_offsets(&mut new_moov[8..], moved_from, delta)?;
+    }
+
+    let mut writer = BufWriter::new(File::create(target)?);
+    for atom_header in &atoms {
+        if atom_header.offset == moov_header.offset {
+            writer.write_all(&new_moov)?;
+        } else {
+            file.seek(SeekFrom::Start(atom_header.offset))?;
+            io::copy(&mut (&mut file).take(atom_header.size), &mut writer)?;
+        }
+    }
+    writer.into_inner()?.sync_all()?;
+    Ok(())
+}
+
+fn tagged_container(
+    payload: &[u8],
+    tags: &AudioTags,
+    child_kind: &[u8; 4],
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let tagged_child = |child_payload: &[u8]| -> Result<Vec<u8>, Box<dyn Error>> {
+        match child_kind {
+            b"udta" => atom(b"udta", &tagged_container(child_payload, tags, b"meta")?),
+            b"meta" => atom(b"meta", &tagged_meta(child_payload, tags)?),
+            _ => Err("Unexpected MP4 atom".into()),
+        }
+    };
+
+    let mut new_payload = Vec::new();
+    let mut found = false;
+    for child in children(payload)? {
+        if &child.kind == child_kind && !found {
+            new_payload.extend_from_slice(&tagged_child(child.payload)?);
+            found = true;
+        } else {
+            new_payload.extend_from_slice(child.raw);
+        }
+    }
+    if !found {
+        new_payload.extend_from_slice(&tagged_child(&[])?);
+    }
+    Ok(new_payload)
+}
+
+fn tagged_meta(payload: &[u8], tags: &AudioTags) -> Result<Vec<u8>, Box<dyn Error>> {
+    let (version_flags, meta_children) = if payload.len() >= 4 {
+        (&payload[..4], &payload[4..])
+    } else {
+        (&[0u8; 4][..], &[][..])
+    };
+    let meta_children = children(meta_children)?;
+
+    let mut new_payload = version_flags.to_vec();
+    if !meta_children.iter().any(|child| &child.kind == b"hdlr") {
+        new_payload.extend_from_slice(&atom(b"hdlr", &METADATA_HANDLER)?);
+    }
+    let mut found = false;
+    for child in &meta_children {
+        if &child.kind == b"ilst" && !found {
+            new_payload.extend_from_slice(&atom(b"ilst", &tagged_ilst(child.payload, tags)?)?);
+            found = true;
+        } else {
+            new_payload.extend_from_slice(child.raw);
+        }
+    }
+    if !found {
+        new_payload.extend_from_slice(&atom(b"ilst", &tagged_ilst(&[], tags)?)?);
+    }
+    Ok(new_payload)
+}
+
+fn tagged_ilst(payload: &[u8], tags: &AudioTags) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut new_payload = Vec::new();
+    for item in children(payload)? {
+        if !MANAGED_ITEMS.contains(&&item.kind) {
+            new_payload.extend_from_slice(item.raw);
+        }
+    }
+
+    let texts = [
+        (TITLE, &tags.title),
+        (ARTIST, &tags.artist),
+        (ALBUM, &tags.album),
+        (GENRE, &tags.genre),
+    ];
+    for (kind, text) in texts {
+        if let Some(text) = text {
+            new_payload.extend_from_slice(&item(kind, TEXT_TYPE, text.as_bytes())?);
+        }
+    }
+    if let Some(year) = tags.year {
+        new_payload.extend_from_slice(&item(YEAR, TEXT_TYPE, year.to_string().as_bytes())?);
+    }
+    if let Some(track) = tags.track {
+        let track = u16::try_from(track).map_err(|_| "Track number is too large")?;
+        let [high, low] = track.to_be_bytes();
+        new_payload.extend_from_slice(&item(TRACK, IMPLICIT_TYPE, &[0, 0, high, low, 0, 0, 0, 0])?);
+    }
+    if let Some(cover) = &tags.cover {
+        let type_code = if cover.mime_type == "image/png" {
+            PNG_TYPE
+        } else {
+            JPEG_TYPE
+        };
+        new_payload.extend_from_slice(&item(COVER, type_code, &cover.data)?);
+    }
+    Ok(new_payload)
+}
+
+fn item(kind: &[u8; 4], type_code: u32, value: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut data = type_code.to_be_bytes().to_vec();
+    data.extend_from_slice(&[0; 4]);
+    data.extend_from_slice(value);
+    atom(kind, &atom(b"data", &data)?)
+}
+
+fn atom(kind: &[u8; 4], payload: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let size = u32::try_from(payload.len() + 8).map_err(|_| "MP4 atom is too large")?;
+    let mut data = size.to_be_bytes().to_vec();
+    data.extend_from_slice(kind);
+    data.extend_from_slice(payload);
+    Ok(data)
+}
+
+fn shift_chunk_offsets(
+    payload: &mut [u8],
+    moved_from: u64,
+    delta: i64,
+) -> Result<(), Box<dyn Error>> {
+    let mut position = 0;
+    while position < payload.len() {
+        let (kind, header_length, size) = atom_header(&payload[position..])?;
+        let body = &mut payload[position + header_length..position + size];
+        match &kind {
+            b"trak" | b"mdia" | b"minf" | b"stbl" => shift_chunk_offsets(body, moved_from, delta)?,
+            b"stco" => shift_offset_table(body, 4, moved_from, delta)?,
+            b"co64" => shift_offset_table(body, 8, moved_from, delta)?,
+            _ => {}
+        }
+        position += size;
+    }
+    Ok(())
+}
+
+fn shift_offset_table(
+    body: &mut [u8],
+    entry_length: usize,
+    moved_from: u64,
+    delta: i64,
+) -> Result<(), Box<dyn Error>> {
+    if body.len() < 8 {
+        return Err("MP4 chunk offset table is truncated".into());
+    }
+    let count = u32::from_be_bytes([body[4], body[5], body[6], body[7]]) as usize;
+    let entries = body[8..].chunks_exact_mut(entry_length).take(count);
+    for entry in entries {
+        let offset = if entry_length == 4 {
+            u32::from_be_bytes([entry[0], entry[1], entry[2], entry[3]]) as u64
+        } else {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(entry);
+            u64::from_be_bytes(bytes)
+        };
+        if offset < moved_from {
+            continue;
+        }
+        let shifted = (offset as i64 + delta) as u64;
+        if entry_length == 4 {
+            let shifted = u32::try_from(shifted).map_err(|_| "MP4 chunk offset overflows")?;
+            entry.copy_from_slice(&shifted.to_be_bytes());
+        } else {
+            entry.copy_from_slice(&shifted.to_be_bytes());
+        }
+    }
+    Ok(())
+}
+
+fn read_top_level(file: &mut StorageFile) -> Result<Vec<AtomHeader>, Box<dyn Error>> {
+    let file_length = file.len()?;
+    let mut atoms = Vec::new();
+    let mut offset = 0;
+    while offset < file_length {
+        file.seek(SeekFrom::Start(offset))?;
+        let mut header = [0; 8];
+        file.read_exact(&mut header)?;
+        let mut kind = [0; 4];
+        kind.copy_from_slice(&header[4..]);
+        let size = match u32::from_be_bytes([header[0], header[1], header[2], header[3]]) {
+            0 => file_length - offset,
+            1 => {
+                let mut large_size = [0; 8];
+                file.read_exact(&mut large_size)?;
+                u64::from_be_bytes(large_size)
+            }
+            size => size as u64,
+        };
+        if size < 8 || size > file_length - offset {
+            return Err("MP4 atom is truncated".into());
+        }
+        atoms.push(AtomHeader { kind, offset, size });
+        offset += size;
+    }
+    Ok(atoms)
+}
+
+fn find_movie(atoms: &[AtomHeader]) -> Result<AtomHeader, Box<dyn Error>> {
+    atoms
+        .iter()
+        .find(|atom| &atom.kind == b"moov")
+        .map(|atom| AtomHeader {
+            kind: atom.kind,
+            offset: atom.offset,
+            size: atom.size,
+        })
+        .ok_or_else(|| "MP4 movie atom is missing".into())
+}
+
+fn read_payload(file: &mut StorageFile, header: &AtomHeader) -> Result<Vec<u8>, Box<dyn Error>> {
+    let file_length = file.len()?;
+    match header.offset.checked_add(header.size) {
+        Some(end) if header.size >= 8 && end <= file_length => {}
+        _ => return Err("MP4 atom is truncated".into()),
+    }
+    let size = usize::try_from(header.size).map_err(|_| "MP4 atom is too large")?;
+    let mut data = vec![0; size];
+    file.seek(SeekFrom::Start(header.offset))?;
+    file.read_exact(&mut data)?;
+    let (_, header_length, _) = atom_header(&data)?;
+    Ok(data.split_off(header_length))
+}
+
+fn children(payload: &[u8]) -> Result<Vec<Atom<'_>>, Box<dyn Error>> {
+    let mut atoms = Vec::new();
+    let mut position = 0;
+    while position < payload.len() {
+        let (kind, header_length, size) = atom_header(&payload[position..])?;
+        atoms.push(Atom {
+            kind,
+            payload: &payload[position + header_length..position + size],
+            raw: &payload[position..position + size],
+        });
+        position += size;
+    }
+    Ok(atoms)
+}
+
+fn find_child<'a>(payload: &'a [u8], kind: &[u8; 4]) -> Result<Option<&'a [u8]>, Box<dyn Error>> {
+    Ok(children(payload)?
+        .into_iter()
+        .find(|child| &child.kind == kind)
+        .map(|child| child.payload))
+}
+
+fn atom_header(data: &[u8]) -> Result<([u8; 4], usize, usize), Box<dyn Error>> {
+    if data.len() < 8 {
+        return Err("MP4 atom is truncated".into());
+    }
+    let mut kind = [0; 4];
+    kind.copy_from_slice(&data[4..8]);
+    let (header_length, size) = match u32::from_be_bytes([data[0], data[1], data[2], data[3]]) {
+        0 => (8, data.len() as u64),
+        1 if data.len() >= 16 => {
+            let mut large_size = [0; 8];
+            large_size.copy_from_slice(&data[8..16]);
+            (16, u64::from_be_bytes(large_size))
+        }
+        1 => return Err("MP4 atom is truncated".into()),
+        size => (8, size as u64),
+    };
+    if size < header_length as u64 || size > data.len() as u64 {
+        return Err("MP4 atom is truncated".into());
+    }
+    Ok((kind, header_length, size as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const FIRST_CHUNK: &[u8] = b"first chunk";
+    const SECOND_CHUNK: &[u8] = b"second chunk";
+
+    fn offset_table(kind: &[u8; 4], offsets: &[u64]) -> Vec<u8> {
+        let mut body = vec![0; 4];
+        body.extend_from_slice(&(offsets.len() as u32).to_be_bytes());
+        for offset in offsets {
+            if kind == b"stco" {
+                body.extend_from_slice(&(*offset as u32).to_be_bytes());
+            } else {
+                body.extend_from_slice(&offset.to_be_bytes());
+            }
+        }
+        atom(kind, &body).unwrap()
+    }
+
+    fn track(table: Vec<u8>) -> Vec<u8> {
+        let stbl = atom(b"stbl", &table).unwrap();
+        let minf = atom(b"minf", &stbl).unwrap();
+        let mdia = atom(b"mdia", &minf).unwrap();
+        atom(b"trak", &mdia).unwrap()
+    }
+
+    fn movie(media_first: bool, udta: &[u8]) -> Vec<u8> {
+        let ftyp = atom(b"ftyp", b"M4A \0\0\0\0").unwrap();
+        let mdat = atom(b"mdat", &[FIRST_CHUNK, SECOND_CHUNK].concat()).unwrap();
+        let moov_length = |offsets: &[u64]| {
+            let tracks = [
+                track(offset_table(b"stco", &offsets[..1])),
+                track(offset_table(b"co64", &offsets[1..])),
+            ]
+            .concat();
+            atom(b"moov", &[&tracks[..], udta].concat()).unwrap()
+        };
+        let placeholder = moov_length(&[0, 0]).len();
+        let mdat_offset = if media_first {
+            ftyp.len()
+        } else {
+            ftyp.len() + placeholder
+        } as u64;
+        let offsets = [mdat_offset + 8, mdat_offset + 8 + FIRST_CHUNK.len() as u64];
+        let moov = moov_length(&offsets);
+        if media_first {
+            [ftyp, mdat, moov].concat()
+        } else {
+            [ftyp, moov, mdat].concat()
+        }
+    }
+
+    fn chunk_offsets(file: &[u8]) -> Vec<u64> {
+        let top_level = children(file).unwrap();
+        let moov = top_level.iter().find(|atom| &atom.kind == b"moov").unwrap();
+        let mut offsets = Vec::new();
+        let traks = children(moov.payload).unwrap();
+        for trak in traks.iter().filter(|child| &child.kind == b"trak") {
+            let mdia = find_child(trak.payload, b"mdia").unwrap().unwrap();
+            let minf = find_child(mdia, b"minf").unwrap().unwrap();
+            let stbl = find_child(minf, b"stbl").unwrap().unwrap();
+            for table in children(stbl).unwrap() {
+                let entry_length = if &table.kind == b"stco" { 4 } else { 8 };
+                for entry in table.payload[8..].chunks_exact(entry_length) {
+                    let mut bytes = [0; 8];
+                    bytes[8 - entry_length..].copy_from_slice(entry);
+                    offsets.push(u64::from_be_bytes(bytes));
+                }
+            }
+        }
+        offsets
+    }
+
+    fn tags() -> AudioTags {
+        AudioTags {
+            title: Some(String::from("Hells Bells")),
+            artist: Some(String::from("AC/DC")),
+            album: Some(String::from("Back in Black")),
+            genre: Some(String::from("Rock")),
+            year: Some(1980),
+            track: Some(1),
+            cover: Some(Cover {
+                mime_type: String::from("image/png"),
+                data: b"\x89PNG cover".repeat(100),
+            }),
+            ..AudioTags::default()
+        }
+    }
+
+    fn tagged(file: &[u8], tags: &AudioTags) -> (Vec<u8>, AudioTags) {
+        let folder = tempfile::tempdir().unwrap();
+        let source = folder.path().join("song.m4a");
+        let target = folder.path().join("song.tagging");
+        fs::write(&source, file).unwrap();
+        write(&source, &target, tags).unwrap();
+        (fs::read(&target).unwrap(), read(&target).unwrap())
+    }
+
+    #[test]
+    fn tags_are_read_back_as_written() {
+        let (_, read_back) = tagged(&movie(false, &[]), &tags());
+        assert_eq!(read_back, tags());
+    }
+
+    #[test]
+    fn chunk_offsets_follow_a_growing_movie() {
+        let file = movie(false, &[]);
+        let (tagged_file, _) = tagged(&file, &tags());
+
+        let offsets = chunk_offsets(&tagged_file);
+        assert_ne!(offsets, chunk_offsets(&file));
+        let first = offsets[0] as usize;
+        let second = offsets[1] as usize;
+        assert_eq!(&tagged_file[first..first + FIRST_CHUNK.len()], FIRST_CHUNK);
+        assert_eq!(
+            &tagged_file[second..second + SECOND_CHUNK.len()],
+            SECOND_CHUNK
+        );
+    }
+
+    #[test]
+    fn chunk_offsets_before_the_movie_are_kept() {
+        let file = movie(true, &[]);
+        let (tagged_file, _) = tagged(&file, &tags());
+
+        assert!(tagged_file.len() > file.len());
+        let offsets = chunk_offsets(&tagged_file);
+        assert_eq!(offsets, chunk_offsets(&file));
+        let second = offsets[1] as usize;
+        assert_eq!(
+            &tagged_file[second..second + SECOND_CHUNK.len()],
+            SECOND_CHUNK
+        );
+    }
+
+    #[test]
+    fn items_not_managed_are_kept_and_removed_values_dropped() {
+        let album_artist = item(ALBUM_ARTIST, TEXT_TYPE, b"Various Artists").unwrap();
+        let title = item(TITLE, TEXT_TYPE, b"Old title").unwrap();
+        let ilst = atom(b"ilst", &[album_artist, title].concat()).unwrap();
+        let hdlr = atom(b"hdlr", &METADATA_HANDLER).unwrap();
+        let meta = atom(b"meta", &[&[0; 4][..], &hdlr, &ilst].concat()).unwrap();
+        let udta = atom(b"udta", &meta).unwrap();
+        let file = movie(false, &udta);
+
+        let only_artist = AudioTags {
+            artist: Some(String::from("AC/DC")),
+            ..AudioTags::default()
+        };
+        let (tagged_file, read_back) = tagged(&file, &only_artist);
+        assert_eq!(read_back.title, None);
+        assert_eq!(read_back.artist.as_deref(), Some("AC/DC"));
+        assert_eq!(read_back.album_artist.as_deref(), Some("Various Artists"));
+        // The movie shrank, the chunks still are where the offsets say
+        let first = chunk_offsets(&tagged_file)[0] as usize;
+        assert_eq!(&tagged_file[first..first + FIRST_CHUNK.len()], FIRST_CHUNK);
+    }
+
+    #[test]
+    fn movie_larger_than_the_file_is_refused() {
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join("song.m4a");
+        let mut file = movie(false, &[]);
+        let moov_start = children(&file).unwrap()[0].raw.len();
+        file[moov_start..moov_start + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        fs::write(&path, &file).unwrap();
+
+        assert!(read(&path).is_err());
+        let mut storage_file = storage::open(&path).unwrap();
+        let header = AtomHeader {
+            kind: *b"moov",
+            offset: moov_start as u64,
+            size: u64::MAX,
+        };
+        assert!(read_payload(&mut storage_file, &header).is_err());
+    }
+}
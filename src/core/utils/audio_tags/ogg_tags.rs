@@ -0,0 +1,271 @@
+use crate::core::utils::audio_tags::vorbis_comments::{
+    parse_picture, picture_bytes, VorbisComments, FRONT_COVER,
+};
+use crate::core::utils::audio_tags::AudioTags;
+use crate::core::utils::storage;
+use ogg::{PacketReader, PacketWriteEndInfo, PacketWriter};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+const VORBIS_HEADER: &[u8] = b"\x03vorbis";
+const OPUS_HEADER: &[u8] = b"OpusTags";
+// Covers are embedded as base64 encoded FLAC picture blocks
+const PICTURE_KEY: &str = "METADATA_BLOCK_PICTURE";
+
+pub fn read(path: &Path) -> Result<AudioTags, Box<dyn Error>> {
+    let mut reader = PacketReader::new(BufReader::new(storage::open(path)?));
+    reader.read_packet_expected()?;
+    let comment_packet = reader.read_packet_expected()?;
+    let (_, comments) = parse_comment_packet(&comment_packet.data)?;
+
+    let mut tags = comments.to_tags();
+    let pictures: Vec<_> = comments
+        .comments
+        .iter()
+        .filter(|(key, _)| key == PICTURE_KEY)
+        .filter_map(|(_, value)| base64::decode(value).ok())
+        .filter_map(|data| parse_picture(&data).ok())
+        .collect();
+    tags.cover = pictures
+        .iter()
+        .find(|(picture_type, _)| *picture_type == FRONT_COVER)
+        .or_else(|| pictures.first())
+        .map(|(_, cover)| cover.clone());
+    Ok(tags)
+}
+
+pub fn write(source: &Path, target: &Path, tags: &AudioTags) -> Result<(), Box<dyn Error>> {
+    let mut reader = PacketReader::new(BufReader::new(File::open(source)?));
+    let mut writer = PacketWriter::new(BufWriter::new(File::create(target)?));
+
+    let mut first_serial = None;
+    let mut first_stream_packets = 0;
+    while let Some(packet) = reader.read_packet()? {
+        let serial = packet.stream_serial();
+        let first_serial = *first_serial.get_or_insert(serial);
+        let end_info = if packet.last_in_stream() {
+            PacketWriteEndInfo::EndStream
+        } else if packet.last_in_page() {
+            PacketWriteEndInfo::EndPage
+        } else {
+            PacketWriteEndInfo::NormalPacket
+        };
+        let absgp = packet.absgp_page();
+
+        let mut data = packet.data;
+        if serial == first_serial {
+            if first_stream_packets == 1 {
+                data = tagged_comment_packet(&data, tags)?;
+            }
+            first_stream_packets += 1;
+        }
+        writer.write_packet(data.into_boxed_slice(), serial, end_info, absgp)?;
+    }
+    if first_stream_packets < 2 {
+        return Err("Ogg comment header is missing".into());
+    }
+
+    let mut file = writer.into_inner();
+    file.flush()?;
+    file.into_inner()?.sync_all()?;
+    Ok(())
+}
+
+fn tagged_comment_packet(data: &[u8], tags: &AudioTags) -> Result<Vec<u8>, Box<dyn Error>> {
+    let (header, mut comments) = parse_comment_packet(data)?;
+    comments.apply(tags);
+    let keep_other_pictures = |(key, value): &(String, String)| {
+        key != PICTURE_KEY
+            || !matches!(
+                base64::decode(value).map(|data| parse_picture(&data)),
+                Ok(Ok((FRONT_COVER, _)))
+            )
+    };
+    comments.comments.retain(keep_other_pictures);
+    if let Some(cover) = &tags.cover {
+        comments.comments.push((
+            PICTURE_KEY.to_string(),
+            base64::encode(picture_bytes(cover)),
+        ));
+    }
+
+    let mut packet = header.to_vec();
+    packet.extend_from_slice(&comments.to_bytes());
+    if header == VORBIS_HEADER {
+        // Vorbis ends the header with a framing bit
+        packet.push(1);
+    }
+    Ok(packet)
+}
+
+fn parse_comment_packet(data: &[u8]) -> Result<(&'static [u8], VorbisComments), Box<dyn Error>> {
+    for header in [VORBIS_HEADER, OPUS_HEADER] {
+        if data.starts_with(header) {
+            return Ok((header, VorbisComments::parse(&data[header.len()..])?));
+        }
+    }
+    Err("Unsupported Ogg codec".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::utils::audio_tags::Cover;
+    use ogg::Packet;
+    use std::fs;
+    use std::io::Cursor;
+
+    const SERIAL: u32 = 0x5EED;
+
+    fn comment_packet(header: &[u8], pairs: &[(&str, &str)]) -> Vec<u8> {
+        let comments = VorbisComments {
+            vendor: String::from("Xiph.Org libVorbis I 20200704"),
+            comments: pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        };
+        let mut packet = [header, &comments.to_bytes()].concat();
+        if header == VORBIS_HEADER {
+            packet.push(1);
+        }
+        packet
+    }
+
+    fn ogg_file(packets: &[Vec<u8>]) -> Vec<u8> {
+        let mut writer = PacketWriter::new(Cursor::new(Vec::new()));
+        for (index, packet) in packets.iter().enumerate() {
+            let end_info = if index + 1 == packets.len() {
+                PacketWriteEndInfo::EndStream
+            } else if index < 3 {
+                PacketWriteEndInfo::EndPage
+            } else {
+                PacketWriteEndInfo::NormalPacket
+            };
+            writer
+                .write_packet(
+                    packet.clone().into_boxed_slice(),
+                    SERIAL,
+                    end_info,
+                    index as u64,
+                )
+                .unwrap();
+        }
+        writer.into_inner().into_inner()
+    }
+
+    fn vorbis_file() -> Vec<u8> {
+        ogg_file(&[
+            b"\x01vorbis identification".to_vec(),
+            comment_packet(
+                VORBIS_HEADER,
+                &[("TITLE", "Old title"), ("ALBUMARTIST", "Various Artists")],
+            ),
+            b"\x05vorbis setup".to_vec(),
+            b"first audio packet".to_vec(),
+            b"last audio packet".to_vec(),
+        ])
+    }
+
+    fn packets(file: &[u8]) -> Vec<Packet> {
+        let mut reader = PacketReader::new(Cursor::new(file));
+        let mut packets = Vec::new();
+        while let Some(packet) = reader.read_packet().unwrap() {
+            packets.push(packet);
+        }
+        packets
+    }
+
+    fn tags() -> AudioTags {
+        AudioTags {
+            title: Some(String::from("Hells Bells")),
+            artist: Some(String::from("AC/DC")),
+            genre: Some(String::from("Rock")),
+            track: Some(1),
+            cover: Some(Cover {
+                mime_type: String::from("image/png"),
+                data: b"\x89PNG cover".to_vec(),
+            }),
+            ..AudioTags::default()
+        }
+    }
+
+    fn tagged(file: &[u8], tags: &AudioTags) -> (Vec<u8>, AudioTags) {
+        let folder = tempfile::tempdir().unwrap();
+        let source = folder.path().join("song.ogg");
+        let target = folder.path().join("song.tagging");
+        fs::write(&source, file).unwrap();
+        write(&source, &target, tags).unwrap();
+        (fs::read(&target).unwrap(), read(&target).unwrap())
+    }
+
+    #[test]
+    fn vorbis_tags_are_read_back_as_written() {
+        let (_, read_back) = tagged(&vorbis_file(), &tags());
+        assert_eq!(
+            read_back,
+            AudioTags {
+                album_artist: Some(String::from("Various Artists")),
+                ..tags()
+            }
+        );
+    }
+
+    #[test]
+    fn opus_tags_are_read_back_as_written() {
+        let file = ogg_file(&[
+            b"OpusHead identification".to_vec(),
+            comment_packet(OPUS_HEADER, &[]),
+            b"audio packet".to_vec(),
+        ]);
+        let (tagged_file, read_back) = tagged(&file, &tags());
+
+        assert_eq!(read_back, tags());
+        // Opus comments have no framing bit
+        let comment = &packets(&tagged_file)[1].data;
+        let (_, comments) = parse_comment_packet(comment).unwrap();
+        assert_eq!(comment.len(), OPUS_HEADER.len() + comments.to_bytes().len());
+    }
+
+    #[test]
+    fn packets_other_than_the_comments_are_kept() {
+        let file = vorbis_file();
+        let (tagged_file, _) = tagged(&file, &tags());
+        let before = packets(&file);
+        let after = packets(&tagged_file);
+
+        assert_eq!(before.len(), after.len());
+        for (index, (before, after)) in before.iter().zip(&after).enumerate() {
+            assert_eq!(after.stream_serial(), SERIAL);
+            assert_eq!(after.absgp_page(), before.absgp_page());
+            if index != 1 {
+                assert_eq!(after.data, before.data);
+            }
+        }
+        assert_eq!(after[1].data.last(), Some(&1));
+        assert!(after.last().unwrap().last_in_stream());
+    }
+
+    #[test]
+    fn removed_cover_is_dropped() {
+        let (with_cover, _) = tagged(&vorbis_file(), &tags());
+        let (_, read_back) = tagged(&with_cover, &AudioTags::default());
+        assert_eq!(read_back.cover, None);
+        assert_eq!(read_back.title, None);
+    }
+
+    #[test]
+    fn streams_without_comments_are_refused() {
+        let folder = tempfile::tempdir().unwrap();
+        let source = folder.path().join("song.ogg");
+        let target = folder.path().join("song.tagging");
+        fs::write(&source, ogg_file(&[b"\x01vorbis identification".to_vec()])).unwrap();
+        assert!(write(&source, &target, &tags()).is_err());
+
+        let unknown_codec = ogg_file(&[b"Speex".to_vec(), b"comments".to_vec()]);
+        fs::write(&source, unknown_codec).unwrap();
+        assert!(write(&source, &target, &tags()).is_err());
+    }
+}
@@ -0,0 +1,239 @@
+use crate::core::utils::audio_tags::{parse_track, parse_year, AudioTags, Cover};
+use std::error::Error;
+
+pub const FRONT_COVER: u32 = 3;
+const MANAGED_KEYS: [&str; 6] = ["TITLE", "ARTIST", "ALBUM", "GENRE", "DATE", "TRACKNUMBER"];
+
+#[derive(Debug, Default)]
+pub struct VorbisComments {
+    pub vendor: String,
+    pub comments: Vec<(String, String)>,
+}
+
+impl VorbisComments {
+    pub fn parse(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut reader = ByteReader::new(data);
+        let vendor_length = reader.u32_le()? as usize;
+        let vendor = String::from_utf8_lossy(reader.bytes(vendor_length)?).into_owned();
+        let count = reader.u32_le()?;
+
+        let mut comments = Vec::new();
+        for _ in 0..count {
+            let length = reader.u32_le()? as usize;
+            let comment = String::from_utf8_lossy(reader.bytes(length)?).into_owned();
+            if let Some((key, value)) = comment.split_once('=') {
+                comments.push((key.to_uppercase(), value.to_string()));
+            }
+        }
+        Ok(VorbisComments { vendor, comments })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&(self.vendor.len() as u32).to_le_bytes());
+        data.extend_from_slice(self.vendor.as_bytes());
+        data.extend_from_slice(&(self.comments.len() as u32).to_le_bytes());
+        for (key, value) in &self.comments {
+            let comment = format!("{}={}", key, value);
+            data.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            data.extend_from_slice(comment.as_bytes());
+        }
+        data
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.comments
+            .iter()
+            .find(|(comment_key, _)| comment_key == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn to_tags(&self) -> AudioTags {
+        AudioTags {
+            title: self.get("TITLE").map(String::from),
+            artist: self.get("ARTIST").map(String::from),
+            album: self.get("ALBUM").map(String::from),
+            genre: self.get("GENRE").map(String::from),
+            year: self.get("DATE").and_then(parse_year),
+            track: self.get("TRACKNUMBER").and_then(parse_track),
+            cover: None,
+            album_artist: self
+                .get("ALBUMARTIST")
+                .or_else(|| self.get("ALBUM ARTIST"))
+                .map(String::from),
+            disc: self.get("DISCNUMBER").and_then(parse_track),
+        }
+    }
+
+    pub fn apply(&mut self, tags: &AudioTags) {
+        self.comments
+            .retain(|(key, _)| !MANAGED_KEYS.contains(&key.as_str()));
+        let values = [
+            ("TITLE", tags.title.clone()),
+            ("ARTIST", tags.artist.clone()),
+            ("ALBUM", tags.album.clone()),
+            ("GENRE", tags.genre.clone()),
+            ("DATE", tags.year.map(|year| year.to_string())),
+            ("TRACKNUMBER", tags.track.map(|track| track.to_string())),
+        ];
+        for (key, value) in values {
+            if let Some(value) = value {
+                self.comments.push((key.to_string(), value));
+            }
+        }
+    }
+}
+
+pub fn parse_picture(data: &[u8]) -> Result<(u32, Cover), Box<dyn Error>> {
+    let mut reader = ByteReader::new(data);
+    let picture_type = reader.u32_be()?;
+    let mime_length = reader.u32_be()? as usize;
+    let mime_type = String::from_utf8_lossy(reader.bytes(mime_length)?).into_owned();
+    let description_length = reader.u32_be()? as usize;
+    reader.bytes(description_length)?;
+    reader.bytes(16)?;
+    let data_length = reader.u32_be()? as usize;
+    let data = reader.bytes(data_length)?.to_vec();
+    Ok((picture_type, Cover { mime_type, data }))
+}
+
+pub fn is_front_cover(data: &[u8]) -> bool {
+    matches!(parse_picture(data), Ok((FRONT_COVER, _)))
+}
+
+pub fn picture_bytes(cover: &Cover) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&FRONT_COVER.to_be_bytes());
+    data.extend_from_slice(&(cover.mime_type.len() as u32).to_be_bytes());
+    data.extend_from_slice(cover.mime_type.as_bytes());
+    data.extend_from_slice(&0u32.to_be_bytes());
+    data.extend_from_slice(&[0; 16]);
+    data.extend_from_slice(&(cover.data.len() as u32).to_be_bytes());
+    data.extend_from_slice(&cover.data);
+    data
+}
+
+pub struct ByteReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        ByteReader { data, position: 0 }
+    }
+
+    pub fn bytes(&mut self, length: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|end| *end <= self.data.len())
+            .ok_or("Tag data is truncated")?;
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    pub fn u32_le(&mut self) -> Result<u32, Box<dyn Error>> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub fn u32_be(&mut self) -> Result<u32, Box<dyn Error>> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_be_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comments(pairs: &[(&str, &str)]) -> VorbisComments {
+        VorbisComments {
+            vendor: String::from("Xiph.Org libVorbis I 20200704"),
+            comments: pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn comments_are_read_back_as_written() {
+        let written = comments(&[("TITLE", "Hells Bells"), ("ARTIST", "AC/DC = rock")]);
+        let read_back = VorbisComments::parse(&written.to_bytes()).unwrap();
+
+        assert_eq!(read_back.vendor, written.vendor);
+        assert_eq!(read_back.comments, written.comments);
+    }
+
+    #[test]
+    fn keys_are_read_case_insensitively() {
+        let mut data = 0u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&2u32.to_le_bytes());
+        for comment in ["title=Hells Bells", "no separator"] {
+            data.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            data.extend_from_slice(comment.as_bytes());
+        }
+        let read_back = VorbisComments::parse(&data).unwrap();
+
+        assert_eq!(read_back.get("TITLE"), Some("Hells Bells"));
+        assert_eq!(read_back.comments.len(), 1);
+    }
+
+    #[test]
+    fn truncated_comments_are_refused() {
+        let data = comments(&[("TITLE", "Hells Bells")]).to_bytes();
+        assert!(VorbisComments::parse(&data[..data.len() - 1]).is_err());
+        assert!(VorbisComments::parse(&[]).is_err());
+    }
+
+    #[test]
+    fn managed_values_are_replaced_and_others_kept() {
+        let mut written = comments(&[
+            ("TITLE", "Old title"),
+            ("GENRE", "Old genre"),
+            ("ALBUMARTIST", "Various Artists"),
+            ("DISCNUMBER", "2/3"),
+        ]);
+        written.apply(&AudioTags {
+            title: Some(String::from("Hells Bells")),
+            year: Some(1980),
+            track: Some(1),
+            ..AudioTags::default()
+        });
+        let tags = written.to_tags();
+
+        assert_eq!(tags.title.as_deref(), Some("Hells Bells"));
+        assert_eq!(tags.genre, None);
+        assert_eq!(tags.year, Some(1980));
+        assert_eq!(tags.track, Some(1));
+        assert_eq!(tags.album_artist.as_deref(), Some("Various Artists"));
+        assert_eq!(tags.disc, Some(2));
+    }
+
+    #[test]
+    fn dates_and_counted_numbers_are_parsed() {
+        let tags = comments(&[("DATE", "1980-07-25"), ("TRACKNUMBER", "3/12")]).to_tags();
+        assert_eq!(tags.year, Some(1980));
+        assert_eq!(tags.track, Some(3));
+        let tags = comments(&[("ALBUM ARTIST", "AC/DC")]).to_tags();
+        assert_eq!(tags.album_artist.as_deref(), Some("AC/DC"));
+    }
+
+    #[test]
+    fn pictures_are_read_back_as_front_covers() {
+        let cover = Cover {
+            mime_type: String::from("image/jpeg"),
+            data: vec![0xFF, 0xD8, 0xFF, 1, 2, 3],
+        };
+        let data = picture_bytes(&cover);
+
+        assert!(is_front_cover(&data));
+        assert_eq!(parse_picture(&data).unwrap(), (FRONT_COVER, cover));
+        assert!(parse_picture(&data[..data.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,42 @@
+use crate::core::utils::storage;
+use sha2::{Digest, Sha256};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FileStamp {
+    pub size: u64,
+    pub modified_at: i64,
+}
+
+pub fn file_stamp(path: &Path) -> Option<FileStamp> {
+    let stat = storage::stat(path).ok()?;
+    Some(FileStamp {
+        size: stat.size,
+        modified_at: stat.modified_at,
+    })
+}
+
+const SAMPLE_LENGTH: u64 = 64 * 1024;
+
+// Tags are sampled with the audio, so a re-tagged file gets a new identity
+pub fn file_identity(path: &Path) -> io::Result<String> {
+    let mut file = storage::open(path)?;
+    let size = file.len()?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(size.to_le_bytes());
+
+    let mut sample = Vec::with_capacity(SAMPLE_LENGTH as usize);
+    (&mut file).take(SAMPLE_LENGTH).read_to_end(&mut sample)?;
+    hasher.update(&sample);
+
+    if size > SAMPLE_LENGTH {
+        sample.clear();
+        file.seek(SeekFrom::Start((size - SAMPLE_LENGTH).max(SAMPLE_LENGTH)))?;
+        file.take(SAMPLE_LENGTH).read_to_end(&mut sample)?;
+        hasher.update(&sample);
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
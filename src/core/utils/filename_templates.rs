@@ -0,0 +1,415 @@
+use crate::core::utils::audio_tags::AudioTags;
+use std::fmt;
+use std::iter;
+use std::path::{Component, Path, PathBuf};
+
+pub const DEFAULT_TEMPLATE: &str = "{artist} - {title}";
+
+pub const DEFAULT_LAYOUT: &str = "{artist} - {title}.{ext}";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Artist,
+    AlbumArtist,
+    Album,
+    Disc,
+    Track,
+    Title,
+    Year,
+    Extension,
+}
+
+#[derive(Debug)]
+enum Part {
+    Literal(String),
+    Field(Field),
+}
+
+#[derive(Debug)]
+pub struct FilenameTemplate {
+    pattern: String,
+    parts: Vec<Part>,
+    depth: usize,
+}
+
+impl FilenameTemplate {
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        let parts = parse_parts(pattern)?;
+        if has_field(&parts, Field::Extension) {
+            return Err(format!(
+                "Templates are matched without the extension: {}",
+                pattern
+            ));
+        }
+        Ok(FilenameTemplate {
+            pattern: pattern.to_string(),
+            parts,
+            depth: pattern.matches('/').count() + 1,
+        })
+    }
+
+    pub fn parse_path(&self, relative_path: &Path) -> Option<AudioTags> {
+        let mut components = relative_path
+            .with_extension("")
+            .components()
+            .map(|component| match component {
+                Component::Normal(name) => name.to_str().map(String::from),
+                _ => None,
+            })
+            .collect::<Option<Vec<String>>>()?;
+        if components.len() < self.depth {
+            return None;
+        }
+        let input = components
+            .split_off(components.len() - self.depth)
+            .join("/");
+
+        let mut values = Vec::new();
+        if !match_parts(&self.parts, &input, &mut values) {
+            return None;
+        }
+
+        let mut tags = AudioTags::default();
+        for (field, value) in values {
+            let value = value.trim();
+            match field {
+                Field::Artist => tags.artist = Some(value.to_string()),
+                Field::AlbumArtist => tags.album_artist = Some(value.to_string()),
+                Field::Album => tags.album = Some(value.to_string()),
+                Field::Title => tags.title = Some(value.to_string()),
+                Field::Disc => tags.disc = value.parse().ok(),
+                Field::Track => tags.track = value.parse().ok(),
+                Field::Year => tags.year = value.parse().ok(),
+                Field::Extension => {}
+            }
+        }
+        Some(tags)
+    }
+}
+
+impl fmt::Display for FilenameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.pattern)
+    }
+}
+
+#[derive(Debug)]
+pub struct LibraryLayout {
+    pattern: String,
+    parts: Vec<Part>,
+}
+
+impl LibraryLayout {
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        let parts = parse_parts(pattern)?;
+        if !has_field(&parts, Field::Extension) {
+            return Err(format!("Layout has no {{ext}}: {}", pattern));
+        }
+        if pattern
+            .split('/')
+            .any(|component| component.is_empty() || component == "." || component == "..")
+        {
+            return Err(format!("Layout must be a relative path: {}", pattern));
+        }
+        Ok(LibraryLayout {
+            pattern: pattern.to_string(),
+            parts,
+        })
+    }
+
+    pub fn format(&self, tags: &AudioTags, extension: &str) -> PathBuf {
+        let artist = tags.artist.as_deref().unwrap_or("Unknown artist");
+        let mut path = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => path.push_str(literal),
+                Part::Field(field) => {
+                    let value = match field {
+                        Field::Artist => artist.to_string(),
+                        Field::AlbumArtist => {
+                            tags.album_artist.as_deref().unwrap_or(artist).to_string()
+                        }
+                        Field::Album => {
+                            tags.album.as_deref().unwrap_or("Unknown album").to_string()
+                        }
+                        Field::Disc => tags.disc.unwrap_or(1).to_string(),
+                        Field::Track => format!("{:02}", tags.track.unwrap_or(0)),
+                        Field::Title => {
+                            tags.title.as_deref().unwrap_or("Unknown title").to_string()
+                        }
+                        Field::Year => tags
+                            .year
+                            .map(|year| year.to_string())
+                            .unwrap_or_else(|| String::from("Unknown year")),
+                        Field::Extension => extension.to_string(),
+                    };
+                    path.push_str(&sanitize(&value));
+                }
+            }
+        }
+        PathBuf::from(path)
+    }
+}
+
+impl fmt::Display for LibraryLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.pattern)
+    }
+}
+
+pub fn match_templates<'a>(
+    templates: &'a [FilenameTemplate],
+    relative_path: &Path,
+) -> Option<(&'a FilenameTemplate, AudioTags)> {
+    templates.iter().find_map(|template| {
+        template
+            .parse_path(relative_path)
+            .map(|values| (template, values))
+    })
+}
+
+fn parse_parts(pattern: &str) -> Result<Vec<Part>, String> {
+    let mut parts = Vec::new();
+    let mut rest = pattern;
+
+    while !rest.is_empty() {
+        let literal_end = rest.find('{').unwrap_or(rest.len());
+        if literal_end > 0 {
+            parts.push(Part::Literal(rest[..literal_end].to_string()));
+            rest = &rest[literal_end..];
+            continue;
+        }
+
+        let field_end = rest
+            .find('}')
+            .ok_or_else(|| format!("Unclosed placeholder in: {}", pattern))?;
+        let field = match &rest[1..field_end] {
+            "artist" => Field::Artist,
+            "album_artist" => Field::AlbumArtist,
+            "album" => Field::Album,
+            "disc" => Field::Disc,
+            "track" => Field::Track,
+            "title" => Field::Title,
+            "year" => Field::Year,
+            "ext" => Field::Extension,
+            name => return Err(format!("Unknown placeholder {{{}}} in: {}", name, pattern)),
+        };
+        if has_field(&parts, field) {
+            return Err(format!("Repeated placeholder in: {}", pattern));
+        }
+        parts.push(Part::Field(field));
+        rest = &rest[field_end + 1..];
+    }
+
+    if !has_field(&parts, Field::Title) {
+        return Err(format!("No {{title}} in: {}", pattern));
+    }
+    Ok(parts)
+}
+
+fn has_field(parts: &[Part], field: Field) -> bool {
+    parts
+        .iter()
+        .any(|part| matches!(part, Part::Field(part_field) if *part_field == field))
+}
+
+fn sanitize(value: &str) -> String {
+    let value: String = value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let value = value.trim().trim_start_matches('.').trim();
+    if value.is_empty() {
+        String::from("_")
+    } else {
+        value.to_string()
+    }
+}
+
+// Placeholders take as little as they can, so the last one keeps any separators left in the name
+fn match_parts<'a>(parts: &[Part], input: &'a str, values: &mut Vec<(Field, &'a str)>) -> bool {
+    let (part, rest) = match parts.split_first() {
+        Some(split) => split,
+        None => return input.is_empty(),
+    };
+    let field = match part {
+        Part::Literal(literal) => {
+            return match input.strip_prefix(literal.as_str()) {
+                Some(input) => match_parts(rest, input, values),
+                None => false,
+            }
+        }
+        Part::Field(field) => *field,
+    };
+
+    let ends = input
+        .char_indices()
+        .skip(1)
+        .map(|(index, _)| index)
+        .chain(iter::once(input.len()));
+    for end in ends {
+        let value = &input[..end];
+        if value.contains('/') {
+            break;
+        }
+        if !accepts(field, value) {
+            continue;
+        }
+        values.push((field, value));
+        if match_parts(rest, &input[end..], values) {
+            return true;
+        }
+        values.pop();
+    }
+    false
+}
+
+fn accepts(field: Field, value: &str) -> bool {
+    match field {
+        Field::Track | Field::Disc => {
+            !value.is_empty() && value.chars().all(|c| c.is_ascii_digit())
+        }
+        Field::Year => value.len() == 4 && value.chars().all(|c| c.is_ascii_digit()),
+        _ => !value.trim().is_empty(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARTIST_TITLE: &str = "{artist} - {title}";
+    const TRACK_TITLE: &str = "{track} - {title}";
+    const ARTIST_ALBUM_TRACK_TITLE: &str = "{artist} - {album} - {track} - {title}";
+    const FOLDERS: &str = "{artist}/{album}/{track} {title}";
+
+    fn tags(
+        artist: Option<&str>,
+        album: Option<&str>,
+        track: Option<u32>,
+        title: &str,
+    ) -> AudioTags {
+        AudioTags {
+            title: Some(title.to_string()),
+            artist: artist.map(String::from),
+            album: album.map(String::from),
+            track,
+            ..AudioTags::default()
+        }
+    }
+
+    fn parse_path(pattern: &str, path: &str) -> Option<AudioTags> {
+        FilenameTemplate::parse(pattern)
+            .unwrap()
+            .parse_path(Path::new(path))
+    }
+
+    #[test]
+    fn templates_read_each_layout() {
+        let cases = [
+            (
+                ARTIST_TITLE,
+                "Artist - Title.mp3",
+                Some(tags(Some("Artist"), None, None, "Title")),
+            ),
+            (
+                ARTIST_TITLE,
+                "Folder/Artist - Title.mp3",
+                Some(tags(Some("Artist"), None, None, "Title")),
+            ),
+            (
+                ARTIST_TITLE,
+                "A - B - C.mp3",
+                Some(tags(Some("A"), None, None, "B - C")),
+            ),
+            (ARTIST_TITLE, "Title.mp3", None),
+            (ARTIST_TITLE, " - Title.mp3", None),
+            (
+                TRACK_TITLE,
+                "07 - Title.flac",
+                Some(tags(None, None, Some(7), "Title")),
+            ),
+            (TRACK_TITLE, "Artist - Title.flac", None),
+            (
+                ARTIST_ALBUM_TRACK_TITLE,
+                "Artist - Album - 03 - A - Title.ogg",
+                Some(tags(Some("Artist"), Some("Album"), Some(3), "A - Title")),
+            ),
+            (ARTIST_ALBUM_TRACK_TITLE, "Artist - Album - Title.ogg", None),
+            (
+                FOLDERS,
+                "Music/Artist/Album/12 Title.m4a",
+                Some(tags(Some("Artist"), Some("Album"), Some(12), "Title")),
+            ),
+            (FOLDERS, "Album/12 Title.m4a", None),
+            (FOLDERS, "Artist/Album/Title.m4a", None),
+            (ARTIST_TITLE, "Artist - Album/Title.mp3", None),
+        ];
+        for (pattern, path, expected) in cases.iter() {
+            assert_eq!(&parse_path(pattern, path), expected, "{} {}", pattern, path);
+        }
+    }
+
+    #[test]
+    fn placeholders_take_as_little_as_they_can() {
+        let parts = parse_parts("{artist} - {album} - {title}").unwrap();
+        let mut values = Vec::new();
+        assert!(match_parts(&parts, "A - B - C - D", &mut values));
+        assert_eq!(
+            values,
+            vec![
+                (Field::Artist, "A"),
+                (Field::Album, "B"),
+                (Field::Title, "C - D")
+            ]
+        );
+
+        let parts = parse_parts("{year} {track}.{title}").unwrap();
+        let mut values = Vec::new();
+        assert!(match_parts(&parts, "1999 12.1.5", &mut values));
+        assert_eq!(
+            values,
+            vec![
+                (Field::Year, "1999"),
+                (Field::Track, "12"),
+                (Field::Title, "1.5")
+            ]
+        );
+        assert!(!match_parts(&parts, "99 12.Title", &mut Vec::new()));
+    }
+
+    #[test]
+    fn first_matching_template_wins() {
+        let templates: Vec<FilenameTemplate> = [TRACK_TITLE, ARTIST_TITLE]
+            .iter()
+            .map(|pattern| FilenameTemplate::parse(pattern).unwrap())
+            .collect();
+        let matched = |path: &str| {
+            match_templates(&templates, Path::new(path)).map(|(template, _)| template.to_string())
+        };
+        assert_eq!(matched("01 - Title.mp3"), Some(String::from(TRACK_TITLE)));
+        assert_eq!(
+            matched("Artist - Title.mp3"),
+            Some(String::from(ARTIST_TITLE))
+        );
+        assert_eq!(matched("Title.mp3"), None);
+    }
+
+    #[test]
+    fn invalid_templates_are_refused() {
+        let patterns = [
+            "{artist}",
+            "{artist} - {title",
+            "{artist} - {name}",
+            "{title} - {title}",
+            "{artist} - {title}.{ext}",
+        ];
+        for pattern in patterns.iter() {
+            assert!(FilenameTemplate::parse(pattern).is_err(), "{}", pattern);
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use crate::core::utils::audio_tags::AudioTags;
+use crate::core::utils::storage;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::Path;
+
+pub const OVERRIDES_FILE_NAME: &str = ".hyppo-overrides.toml";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileOverride {
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    genre: Option<String>,
+    year: Option<i32>,
+    track: Option<u32>,
+}
+
+pub fn is_overrides_file(path: &Path) -> bool {
+    path.file_name() == Some(OsStr::new(OVERRIDES_FILE_NAME))
+}
+
+pub fn read_folder_overrides(folder: &Path) -> Option<HashMap<String, AudioTags>> {
+    let overrides_path = folder.join(OVERRIDES_FILE_NAME);
+    if !storage::is_file(&overrides_path) {
+        return None;
+    }
+
+    let overrides: HashMap<String, FileOverride> = match storage::read_to_string(&overrides_path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| toml::from_str(&text).map_err(|e| e.to_string()))
+    {
+        Ok(overrides) => overrides,
+        Err(e) => {
+            eprintln!(
+                "Invalid overrides file {}:\n{}",
+                overrides_path.display(),
+                e
+            );
+            return None;
+        }
+    };
+
+    let overrides = overrides.into_iter().map(|(file_name, values)| {
+        let tags = AudioTags {
+            title: values.title,
+            artist: values.artist,
+            album: values.album,
+            genre: values.genre,
+            year: values.year,
+            track: values.track,
+            ..AudioTags::default()
+        };
+        (file_name, tags)
+    });
+    Some(overrides.collect())
+}
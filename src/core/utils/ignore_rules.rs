@@ -0,0 +1,308 @@
+use crate::core::utils::storage;
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::Path;
+
+pub const IGNORE_FILE_NAME: &str = ".hyppoignore";
+
+#[derive(Debug, Clone)]
+struct IgnorePattern {
+    pattern: String,
+    glob: Vec<char>,
+    base: String,
+    negated: bool,
+    directory_only: bool,
+    // Patterns with a slash match the path from their folder, others the name at any depth
+    anchored: bool,
+}
+
+impl IgnorePattern {
+    fn parse(line: &str, base: &str) -> Option<Self> {
+        let pattern = line.trim_end();
+        if pattern.is_empty() || pattern.starts_with('#') {
+            return None;
+        }
+        let (negated, glob) = match pattern.strip_prefix('!') {
+            Some(glob) => (true, glob),
+            None => (false, pattern.strip_prefix('\\').unwrap_or(pattern)),
+        };
+        let (directory_only, glob) = match glob.strip_suffix('/') {
+            Some(glob) => (true, glob),
+            None => (false, glob),
+        };
+        let anchored = glob.contains('/');
+        let glob = glob.strip_prefix('/').unwrap_or(glob);
+        if glob.is_empty() {
+            return None;
+        }
+        Some(IgnorePattern {
+            pattern: pattern.to_string(),
+            glob: glob.chars().collect(),
+            base: base.to_string(),
+            negated,
+            directory_only,
+            anchored,
+        })
+    }
+
+    fn matches(&self, file_path: &str, is_dir: bool) -> Option<bool> {
+        if self.directory_only && !is_dir {
+            return None;
+        }
+        let relative_path = file_path.strip_prefix(self.base.as_str())?;
+        let subject = if self.anchored {
+            relative_path
+        } else {
+            relative_path.rsplit('/').next().unwrap_or(relative_path)
+        };
+        let subject: Vec<char> = subject.chars().collect();
+        if glob_matches(&self.glob, &subject) {
+            Some(!self.negated)
+        } else {
+            None
+        }
+    }
+}
+
+// The last pattern that matches a path decides, like in git
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    patterns: Vec<IgnorePattern>,
+}
+
+impl IgnoreRules {
+    pub fn parse(patterns: &[String]) -> Self {
+        IgnoreRules {
+            patterns: patterns
+                .iter()
+                .filter_map(|pattern| IgnorePattern::parse(pattern, ""))
+                .collect(),
+        }
+    }
+
+    pub fn with_folder(&self, folder: &Path, path_prefix: &str) -> Option<IgnoreRules> {
+        let text = storage::read_to_string(&folder.join(IGNORE_FILE_NAME)).ok()?;
+        let mut rules = self.clone();
+        rules.patterns.extend(
+            text.lines()
+                .filter_map(|line| IgnorePattern::parse(line, path_prefix)),
+        );
+        Some(rules)
+    }
+
+    pub fn for_folder(&self, files_folder: &Path, path_prefix: &str) -> Option<IgnoreRules> {
+        let mut rules = Cow::Borrowed(self);
+        let mut folder_path = String::new();
+        for name in path_prefix.split('/').filter(|name| !name.is_empty()) {
+            if let Some(folder_rules) =
+                rules.with_folder(&files_folder.join(&folder_path), &folder_path)
+            {
+                rules = Cow::Owned(folder_rules);
+            }
+            folder_path = format!("{}{}/", folder_path, name);
+            if rules.is_ignored(folder_path.trim_end_matches('/'), true) {
+                return None;
+            }
+        }
+        if let Some(folder_rules) =
+            rules.with_folder(&files_folder.join(&folder_path), &folder_path)
+        {
+            rules = Cow::Owned(folder_rules);
+        }
+        Some(rules.into_owned())
+    }
+
+    pub fn is_ignored(&self, file_path: &str, is_dir: bool) -> bool {
+        self.patterns
+            .iter()
+            .rev()
+            .find_map(|pattern| pattern.matches(file_path, is_dir))
+            .unwrap_or(false)
+    }
+}
+
+impl fmt::Display for IgnoreRules {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let patterns: Vec<&str> = self
+            .patterns
+            .iter()
+            .map(|pattern| pattern.pattern.as_str())
+            .collect();
+        write!(f, "{}", patterns.join(", "))
+    }
+}
+
+pub fn is_ignore_file(path: &Path) -> bool {
+    path.file_name() == Some(OsStr::new(IGNORE_FILE_NAME))
+}
+
+fn glob_matches(glob: &[char], text: &[char]) -> bool {
+    match glob {
+        [] => text.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            // Also matches no folder at all
+            glob_matches(rest, text)
+                || (0..text.len())
+                    .filter(|index| text[*index] == '/')
+                    .any(|index| glob_matches(rest, &text[index + 1..]))
+        }
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|index| glob_matches(rest, &text[index..])),
+        ['*', rest @ ..] => {
+            for index in 0..=text.len() {
+                if glob_matches(rest, &text[index..]) {
+                    return true;
+                }
+                if index < text.len() && text[index] == '/' {
+                    break;
+                }
+            }
+            false
+        }
+        ['?', rest @ ..] => match text {
+            [c, text @ ..] if *c != '/' => glob_matches(rest, text),
+            _ => false,
+        },
+        ['[', class @ ..] => match (text, match_class(class, text.first().copied())) {
+            ([_, text @ ..], Some((true, rest))) => glob_matches(rest, text),
+            (_, Some((false, _))) => false,
+            // Without a closing bracket it is an ordinary character
+            _ => matches!(text, ['[', text @ ..] if glob_matches(class, text)),
+        },
+        ['\\', c, rest @ ..] | [c, rest @ ..] => match text {
+            [first, text @ ..] if first == c => glob_matches(rest, text),
+            _ => false,
+        },
+    }
+}
+
+fn match_class(class: &[char], c: Option<char>) -> Option<(bool, &[char])> {
+    let (negated, items) = match class {
+        ['!', items @ ..] | ['^', items @ ..] => (true, items),
+        items => (false, items),
+    };
+    // A bracket right at the start is part of the class
+    let end = items
+        .iter()
+        .skip(1)
+        .position(|item| *item == ']')
+        .map(|position| position + 1)?;
+    let c = match c {
+        Some(c) if c != '/' => c,
+        _ => return Some((false, &items[end + 1..])),
+    };
+    let mut found = false;
+    let mut index = 0;
+    while index < end {
+        if index + 2 < end && items[index + 1] == '-' {
+            found |= items[index] <= c && c <= items[index + 2];
+            index += 3;
+        } else {
+            found |= items[index] == c;
+            index += 1;
+        }
+    }
+    Some((found != negated, &items[end + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn matches(glob: &str, text: &str) -> bool {
+        let glob: Vec<char> = glob.chars().collect();
+        let text: Vec<char> = text.chars().collect();
+        glob_matches(&glob, &text)
+    }
+
+    #[test]
+    fn globs_match_like_gitignore() {
+        let cases = [
+            ("*.part", "song.mp3.part", true),
+            ("*.part", "song.mp3", false),
+            ("*", "", true),
+            ("a*/b", "a/x/b", false),
+            ("a/*", "a/b/c", false),
+            ("a/*/c", "a/b/c", true),
+            ("**/cache", "cache", true),
+            ("**/cache", "a/b/cache", true),
+            ("**/cache", "a/bcache", false),
+            ("a/**/b", "a/b", true),
+            ("a/**/b", "a/x/y/b", true),
+            ("a/**", "a/x/y", true),
+            ("a**z", "a/b/z", true),
+            ("?.mp3", "1.mp3", true),
+            ("?.mp3", "12.mp3", false),
+            ("a?b", "a/b", false),
+            ("[0-9]*.mp3", "01 One.mp3", true),
+            ("[0-9]*.mp3", "One.mp3", false),
+            ("[!0-9]*", "One.mp3", true),
+            ("[^0-9]*", "1.mp3", false),
+            ("[abc].mp3", "b.mp3", true),
+            ("[]].mp3", "].mp3", true),
+            ("a[/]b", "a/b", false),
+            ("[unclosed", "[unclosed", true),
+            ("\\*.mp3", "*.mp3", true),
+            ("\\*.mp3", "a.mp3", false),
+            ("@eaDir", "@eaDir", true),
+        ];
+        for (glob, text, expected) in cases.iter() {
+            assert_eq!(matches(glob, text), *expected, "{} {}", glob, text);
+        }
+    }
+
+    #[test]
+    fn last_matching_pattern_decides() {
+        let patterns: Vec<String> = [
+            "# Comments and empty lines are left out",
+            "",
+            "*.part",
+            "@eaDir/",
+            "/Downloads",
+            "Live/*.mp3",
+            "!Live/keep.mp3",
+            "\\!bang.mp3",
+        ]
+        .iter()
+        .map(|pattern| pattern.to_string())
+        .collect();
+        let rules = IgnoreRules::parse(&patterns);
+        let cases = [
+            ("a/b/song.part", false, true),
+            ("song.mp3", false, false),
+            ("a/@eaDir", true, true),
+            ("a/@eaDir", false, false),
+            ("Downloads", true, true),
+            ("a/Downloads", true, false),
+            ("Live/one.mp3", false, true),
+            ("a/Live/one.mp3", false, false),
+            ("Live/keep.mp3", false, false),
+            ("!bang.mp3", false, true),
+            ("bang.mp3", false, false),
+        ];
+        for (path, is_dir, expected) in cases.iter() {
+            assert_eq!(rules.is_ignored(path, *is_dir), *expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn ignore_files_apply_below_their_folder() {
+        let files = tempfile::tempdir().unwrap();
+        fs::create_dir_all(files.path().join("a/b/Skipped")).unwrap();
+        fs::write(files.path().join(IGNORE_FILE_NAME), "*.tmp\n").unwrap();
+        fs::write(
+            files.path().join("a").join(IGNORE_FILE_NAME),
+            "!keep.tmp\n/b/Skipped/\n",
+        )
+        .unwrap();
+
+        let rules = IgnoreRules::default();
+        let top = rules.for_folder(files.path(), "").unwrap();
+        assert!(top.is_ignored("keep.tmp", false));
+        let b = rules.for_folder(files.path(), "a/b/").unwrap();
+        assert!(b.is_ignored("a/b/song.tmp", false));
+        assert!(!b.is_ignored("a/b/keep.tmp", false));
+        assert!(rules.for_folder(files.path(), "a/b/Skipped/").is_none());
+    }
+}
@@ -0,0 +1,172 @@
+use crate::core::utils::safe_paths::{confine, SymlinkPolicy};
+use crate::core::utils::storage::{self, S3Settings, S3Storage};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub const DEFAULT_ROOT_NAME: &str = "files";
+
+pub const S3_SCHEME: &str = "s3://";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibraryRoot {
+    pub name: String,
+    pub path: String,
+}
+
+impl LibraryRoot {
+    pub fn new(name: &str, path: &str) -> Self {
+        LibraryRoot {
+            name: name.to_string(),
+            path: format!("{}/", path.trim_end_matches('/')),
+        }
+    }
+
+    pub fn parse(root: &str) -> Result<Self, String> {
+        let (name, path) = match root.split_once('=') {
+            Some((name, path)) => (name.trim(), path.trim()),
+            None => return Err(format!("Library root is not name=path: {}", root)),
+        };
+        if name.is_empty()
+            || name.contains('/')
+            || name.contains('\\')
+            || name == "."
+            || name == ".."
+        {
+            return Err(format!("Library root name illegal: {}", name));
+        }
+        if path.is_empty() {
+            return Err(format!("Library root path missing: {}", name));
+        }
+        Ok(LibraryRoot::new(name, path))
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.name == DEFAULT_ROOT_NAME
+    }
+
+    pub fn path_prefix(&self) -> String {
+        if self.is_default() {
+            String::new()
+        } else {
+            format!("{}/", self.name)
+        }
+    }
+
+    pub fn is_online(&self) -> bool {
+        matches!(storage::stat(Path::new(&self.path)), Ok(stat) if stat.is_dir)
+    }
+
+    fn holds(&self, file_path: &str) -> bool {
+        self.is_default() || file_path == self.name || file_path.starts_with(&self.path_prefix())
+    }
+
+    fn strip(&self, path: &Path) -> Option<PathBuf> {
+        let folder = Path::new(&self.path);
+        match path.strip_prefix(folder) {
+            Ok(relative) => Some(relative.to_path_buf()),
+            Err(_) => folder
+                .canonicalize()
+                .ok()
+                .and_then(|folder| path.strip_prefix(folder).ok().map(Path::to_path_buf)),
+        }
+    }
+}
+
+impl fmt::Display for LibraryRoot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.path)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LibraryRoots {
+    roots: Vec<LibraryRoot>,
+}
+
+impl LibraryRoots {
+    pub fn new(files_folder_path: &str, others: Vec<LibraryRoot>) -> Result<Self, String> {
+        let mut roots = vec![LibraryRoot::new(DEFAULT_ROOT_NAME, files_folder_path)];
+        for root in others {
+            if roots.iter().any(|known| known.name == root.name) {
+                return Err(format!("Library root given twice: {}", root.name));
+            }
+            roots.push(root);
+        }
+        Ok(LibraryRoots { roots })
+    }
+
+    pub fn mount_buckets(&self) -> Result<(), String> {
+        for root in self.roots.iter() {
+            if root.path.starts_with(S3_SCHEME) {
+                let storage = S3Storage::new(&root.path, S3Settings::from_env()?)?;
+                storage::mount(Path::new(&root.path), Arc::new(storage));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &LibraryRoot> {
+        self.roots.iter()
+    }
+
+    pub fn default_root(&self) -> &LibraryRoot {
+        &self.roots[0]
+    }
+
+    pub fn find(&self, file_path: &str) -> &LibraryRoot {
+        self.roots
+            .iter()
+            .skip(1)
+            .find(|root| root.holds(file_path))
+            .unwrap_or_else(|| self.default_root())
+    }
+
+    pub fn locate(&self, path: &Path) -> Option<(&LibraryRoot, PathBuf)> {
+        self.roots
+            .iter()
+            .filter_map(|root| root.strip(path).map(|relative| (root, relative)))
+            .min_by_key(|(_, relative)| relative.components().count())
+    }
+
+    // Folders of the files folder named like another root are hidden by that root
+    pub fn file_path(&self, path: &Path) -> Option<String> {
+        let (root, relative) = self.locate(path)?;
+        let relative = relative.to_str()?;
+        let file_path = if relative.is_empty() {
+            root.path_prefix().trim_end_matches('/').to_string()
+        } else {
+            format!("{}{}", root.path_prefix(), relative)
+        };
+        if self.find(&file_path) == root {
+            Some(file_path)
+        } else {
+            None
+        }
+    }
+
+    pub fn full_path(&self, file_path: &str) -> PathBuf {
+        let root = self.find(file_path);
+        let relative = file_path
+            .strip_prefix(root.path_prefix().as_str())
+            .unwrap_or_default();
+        Path::new(&root.path).join(relative)
+    }
+
+    pub fn served_path(&self, file_path: &str, symlinks: SymlinkPolicy) -> io::Result<PathBuf> {
+        let folders: Vec<&Path> = self
+            .roots
+            .iter()
+            .map(|root| Path::new(&root.path))
+            .collect();
+        confine(&self.full_path(file_path), &folders, symlinks)
+    }
+}
+
+impl fmt::Display for LibraryRoots {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let roots: Vec<String> = self.roots.iter().map(|root| root.to_string()).collect();
+        write!(f, "{}", roots.join(", "))
+    }
+}
@@ -0,0 +1,13 @@
+pub mod archives;
+pub mod audio_checks;
+pub mod audio_format;
+pub mod audio_tags;
+pub mod file_identity;
+pub mod filename_templates;
+pub mod folder_overrides;
+pub mod ignore_rules;
+pub mod library_roots;
+pub mod safe_paths;
+pub mod scan_progress;
+pub mod storage;
+pub mod worker_pool;
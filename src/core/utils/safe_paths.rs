@@ -0,0 +1,228 @@
+use crate::core::utils::storage;
+use std::fmt;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymlinkPolicy {
+    // Links are followed wherever they point, for libraries linked together from other disks
+    All,
+    // Links are followed as long as they point into an allowed folder
+    Inside,
+    None,
+}
+
+impl SymlinkPolicy {
+    pub fn parse(policy: &str) -> Result<Self, String> {
+        match policy {
+            "all" => Ok(SymlinkPolicy::All),
+            "inside" => Ok(SymlinkPolicy::Inside),
+            "none" => Ok(SymlinkPolicy::None),
+            _ => Err(format!("Symlink policy illegal: {}", policy)),
+        }
+    }
+}
+
+impl fmt::Display for SymlinkPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymlinkPolicy::All => write!(f, "all"),
+            SymlinkPolicy::Inside => write!(f, "inside"),
+            SymlinkPolicy::None => write!(f, "none"),
+        }
+    }
+}
+
+// A crafted row could point anywhere on the host, local files come back with their links resolved
+pub fn confine(path: &Path, folders: &[&Path], symlinks: SymlinkPolicy) -> io::Result<PathBuf> {
+    let escapes = path
+        .components()
+        .any(|component| matches!(component, Component::ParentDir | Component::CurDir));
+    let folder = match folders.iter().find(|folder| path.starts_with(folder)) {
+        Some(folder) if !escapes => folder,
+        _ => return Err(outside(path)),
+    };
+    // Buckets have no links, their keys are only checked as they are
+    if !storage::has_events(path) {
+        return Ok(path.to_path_buf());
+    }
+
+    let canonical_path = path.canonicalize()?;
+    let allowed = match symlinks {
+        SymlinkPolicy::All => true,
+        SymlinkPolicy::None => {
+            let relative = path.strip_prefix(folder).map_err(|_| outside(path))?;
+            folder.canonicalize()?.join(relative) == canonical_path
+        }
+        _ => folders
+            .iter()
+            .filter_map(|folder| folder.canonicalize().ok())
+            .any(|folder| canonical_path.starts_with(folder)),
+    };
+    if allowed {
+        Ok(canonical_path)
+    } else {
+        Err(outside(path))
+    }
+}
+
+fn outside(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("Path is outside of the served folders: {}", path.display()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use tempfile::TempDir;
+
+    const POLICIES: [SymlinkPolicy; 3] = [
+        SymlinkPolicy::All,
+        SymlinkPolicy::Inside,
+        SymlinkPolicy::None,
+    ];
+
+    fn library() -> TempDir {
+        let root = tempfile::tempdir().unwrap();
+        let music = root.path().join("music");
+        let other = root.path().join("other");
+        fs::create_dir_all(&music).unwrap();
+        fs::create_dir_all(&other).unwrap();
+        fs::write(music.join("song.mp3"), b"song").unwrap();
+        fs::write(other.join("secret.mp3"), b"secret").unwrap();
+        symlink(music.join("song.mp3"), music.join("inside.mp3")).unwrap();
+        symlink(other.join("secret.mp3"), music.join("outside.mp3")).unwrap();
+        symlink(&music, root.path().join("linked_music")).unwrap();
+        root
+    }
+
+    fn confined(
+        root: &TempDir,
+        path: &str,
+        folders: &[&str],
+        symlinks: SymlinkPolicy,
+    ) -> Result<PathBuf, io::ErrorKind> {
+        let folders: Vec<PathBuf> = folders.iter().map(|f| root.path().join(f)).collect();
+        let folders: Vec<&Path> = folders.iter().map(|f| f.as_path()).collect();
+        confine(&root.path().join(path), &folders, symlinks).map_err(|e| e.kind())
+    }
+
+    fn canonical(root: &TempDir, path: &str) -> Result<PathBuf, io::ErrorKind> {
+        Ok(root.path().join(path).canonicalize().unwrap())
+    }
+
+    #[test]
+    fn rows_leaving_the_folders_are_refused() {
+        let root = library();
+        let rows = [
+            "music/../other/secret.mp3",
+            "music/sub/../../other/secret.mp3",
+            "other/secret.mp3",
+            "music_other/song.mp3",
+        ];
+        for symlinks in POLICIES {
+            for row in rows {
+                let result = confined(&root, row, &["music"], symlinks);
+                assert_eq!(
+                    result,
+                    Err(io::ErrorKind::PermissionDenied),
+                    "{} {}",
+                    symlinks,
+                    row
+                );
+            }
+            let result = confine(
+                Path::new("/etc/passwd"),
+                &[root.path().join("music").as_path()],
+                symlinks,
+            );
+            assert_eq!(
+                result.map_err(|e| e.kind()),
+                Err(io::ErrorKind::PermissionDenied)
+            );
+        }
+    }
+
+    #[test]
+    fn files_inside_come_back_resolved() {
+        let root = library();
+        for symlinks in POLICIES {
+            assert_eq!(
+                confined(&root, "music/song.mp3", &["music"], symlinks),
+                canonical(&root, "music/song.mp3")
+            );
+            assert_eq!(
+                confined(&root, "music/missing.mp3", &["music"], symlinks),
+                Err(io::ErrorKind::NotFound)
+            );
+            assert_eq!(
+                confined(&root, "linked_music/song.mp3", &["linked_music"], symlinks),
+                canonical(&root, "music/song.mp3")
+            );
+        }
+    }
+
+    #[test]
+    fn links_are_followed_by_policy() {
+        let root = library();
+        let denied = Err(io::ErrorKind::PermissionDenied);
+        let song = canonical(&root, "music/song.mp3");
+        let secret = canonical(&root, "other/secret.mp3");
+        let cases = [
+            (
+                SymlinkPolicy::All,
+                "music/inside.mp3",
+                &["music"][..],
+                &song,
+            ),
+            (SymlinkPolicy::All, "music/outside.mp3", &["music"], &secret),
+            (SymlinkPolicy::Inside, "music/inside.mp3", &["music"], &song),
+            (
+                SymlinkPolicy::Inside,
+                "music/outside.mp3",
+                &["music"],
+                &denied,
+            ),
+            (
+                SymlinkPolicy::Inside,
+                "music/outside.mp3",
+                &["music", "other"],
+                &secret,
+            ),
+            (SymlinkPolicy::None, "music/inside.mp3", &["music"], &denied),
+            (
+                SymlinkPolicy::None,
+                "music/outside.mp3",
+                &["music"],
+                &denied,
+            ),
+            (
+                SymlinkPolicy::None,
+                "music/outside.mp3",
+                &["music", "other"],
+                &denied,
+            ),
+        ];
+        for (symlinks, path, folders, expected) in cases.iter() {
+            assert_eq!(
+                &confined(&root, path, folders, *symlinks),
+                *expected,
+                "{} {}",
+                symlinks,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn policies_are_parsed_by_name() {
+        for symlinks in POLICIES {
+            assert_eq!(SymlinkPolicy::parse(&symlinks.to_string()), Ok(symlinks));
+        }
+        assert!(SymlinkPolicy::parse("some").is_err());
+    }
+}
@@ -0,0 +1,58 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Default)]
+pub struct ScanProgress {
+    discovered: AtomicU64,
+    processed: AtomicU64,
+    added: AtomicU64,
+    updated: AtomicU64,
+    removed: AtomicU64,
+    failed: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScanCounts {
+    pub discovered: u64,
+    pub processed: u64,
+    pub added: u64,
+    pub updated: u64,
+    pub removed: u64,
+    pub failed: u64,
+}
+
+impl ScanProgress {
+    pub fn discovered(&self, count: u64) {
+        self.discovered.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn processed(&self, count: u64) {
+        self.processed.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn added(&self, count: u64) {
+        self.added.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn updated(&self, count: u64) {
+        self.updated.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn removed(&self, count: u64) {
+        self.removed.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn failed(&self, count: u64) {
+        self.failed.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn counts(&self) -> ScanCounts {
+        ScanCounts {
+            discovered: self.discovered.load(Ordering::Relaxed),
+            processed: self.processed.load(Ordering::Relaxed),
+            added: self.added.load(Ordering::Relaxed),
+            updated: self.updated.load(Ordering::Relaxed),
+            removed: self.removed.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use crate::core::utils::storage::{FileStat, Storage, StorageEntry};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+pub struct LocalStorage;
+
+impl Storage for LocalStorage {
+    fn list(&self, folder: &Path) -> io::Result<Vec<StorageEntry>> {
+        Ok(folder
+            .read_dir()?
+            .flatten()
+            .map(|entry| StorageEntry {
+                path: entry.path(),
+                // Symlinked folders are not followed
+                is_dir: entry.file_type().map(|x| x.is_dir()).unwrap_or(false),
+            })
+            .collect())
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<FileStat> {
+        let metadata = path.metadata()?;
+        let modified_at = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_millis() as i64)
+            .unwrap_or(0);
+        Ok(FileStat {
+            size: metadata.len(),
+            modified_at,
+            is_dir: metadata.is_dir(),
+        })
+    }
+
+    fn read_range(&self, path: &Path, offset: u64, length: u64) -> io::Result<Vec<u8>> {
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut data = Vec::new();
+        file.take(length).read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        fs::write(path, data)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn has_events(&self) -> bool {
+        true
+    }
+}
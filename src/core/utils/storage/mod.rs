@@ -0,0 +1,207 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+mod aws_signature;
+mod local_storage;
+mod s3_storage;
+
+pub use local_storage::LocalStorage;
+pub use s3_storage::{S3Settings, S3Storage};
+
+// Remote files are read ahead by this much, as most readers ask for a few bytes at a time
+const READ_AHEAD_LENGTH: u64 = 256 * 1024;
+
+pub trait Storage: Send + Sync {
+    fn list(&self, folder: &Path) -> io::Result<Vec<StorageEntry>>;
+
+    fn stat(&self, path: &Path) -> io::Result<FileStat>;
+
+    fn read_range(&self, path: &Path, offset: u64, length: u64) -> io::Result<Vec<u8>>;
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()>;
+
+    fn remove(&self, path: &Path) -> io::Result<()>;
+
+    fn has_events(&self) -> bool;
+}
+
+#[derive(Debug, Clone)]
+pub struct StorageEntry {
+    pub path: PathBuf,
+    pub is_dir: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FileStat {
+    pub size: u64,
+    pub modified_at: i64,
+    pub is_dir: bool,
+}
+
+lazy_static! {
+    static ref MOUNTS: RwLock<Vec<(PathBuf, Arc<dyn Storage>)>> = RwLock::new(Vec::new());
+    static ref LOCAL_STORAGE: Arc<dyn Storage> = Arc::new(LocalStorage);
+}
+
+pub fn mount(folder: &Path, storage: Arc<dyn Storage>) {
+    if let Ok(mut mounts) = MOUNTS.write() {
+        mounts.push((folder.to_path_buf(), storage));
+    }
+}
+
+pub fn storage_of(path: &Path) -> Arc<dyn Storage> {
+    MOUNTS
+        .read()
+        .ok()
+        .and_then(|mounts| {
+            mounts
+                .iter()
+                .find(|(folder, _)| path.starts_with(folder))
+                .map(|(_, storage)| Arc::clone(storage))
+        })
+        .unwrap_or_else(|| Arc::clone(&LOCAL_STORAGE))
+}
+
+pub fn list(folder: &Path) -> io::Result<Vec<StorageEntry>> {
+    storage_of(folder).list(folder)
+}
+
+pub fn stat(path: &Path) -> io::Result<FileStat> {
+    storage_of(path).stat(path)
+}
+
+pub fn is_file(path: &Path) -> bool {
+    matches!(stat(path), Ok(stat) if !stat.is_dir)
+}
+
+pub fn read_to_string(path: &Path) -> io::Result<String> {
+    let mut text = String::new();
+    open(path)?.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+pub fn write(path: &Path, data: &[u8]) -> io::Result<()> {
+    storage_of(path).write(path, data)
+}
+
+pub fn rename(from: &Path, to: &Path) -> io::Result<()> {
+    let from_storage = storage_of(from);
+    let to_storage = storage_of(to);
+    if from_storage.has_events() && to_storage.has_events() {
+        if fs::rename(from, to).is_ok() {
+            return Ok(());
+        }
+        fs::copy(from, to)?;
+        return fs::remove_file(from);
+    }
+    let length = from_storage.stat(from)?.size;
+    let data = from_storage.read_range(from, 0, length)?;
+    to_storage.write(to, &data)?;
+    from_storage.remove(from)
+}
+
+pub fn has_events(path: &Path) -> bool {
+    storage_of(path).has_events()
+}
+
+pub fn open(path: &Path) -> io::Result<StorageFile> {
+    let storage = storage_of(path);
+    if storage.has_events() {
+        return File::open(path).map(StorageFile::Local);
+    }
+    let stat = storage.stat(path)?;
+    if stat.is_dir {
+        return Err(io::Error::other("Path is a folder"));
+    }
+    Ok(StorageFile::Remote(RemoteFile {
+        storage,
+        path: path.to_path_buf(),
+        length: stat.size,
+        position: 0,
+        buffer: Vec::new(),
+        buffer_offset: 0,
+    }))
+}
+
+pub enum StorageFile {
+    Local(File),
+    Remote(RemoteFile),
+}
+
+pub struct RemoteFile {
+    storage: Arc<dyn Storage>,
+    path: PathBuf,
+    length: u64,
+    position: u64,
+    buffer: Vec<u8>,
+    buffer_offset: u64,
+}
+
+impl StorageFile {
+    pub fn len(&mut self) -> io::Result<u64> {
+        match self {
+            StorageFile::Local(file) => file.metadata().map(|metadata| metadata.len()),
+            StorageFile::Remote(file) => Ok(file.length),
+        }
+    }
+}
+
+impl Read for StorageFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let file = match self {
+            StorageFile::Local(file) => return file.read(buf),
+            StorageFile::Remote(file) => file,
+        };
+        if file.position >= file.length || buf.is_empty() {
+            return Ok(0);
+        }
+        let buffer_end = file.buffer_offset + file.buffer.len() as u64;
+        if file.position < file.buffer_offset || file.position >= buffer_end {
+            let length = READ_AHEAD_LENGTH.max(buf.len() as u64);
+            file.buffer = file.storage.read_range(&file.path, file.position, length)?;
+            file.buffer_offset = file.position;
+            if file.buffer.is_empty() {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+        let start = (file.position - file.buffer_offset) as usize;
+        let read = buf.len().min(file.buffer.len() - start);
+        buf[..read].copy_from_slice(&file.buffer[start..start + read]);
+        file.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for StorageFile {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let file = match self {
+            StorageFile::Local(file) => return file.seek(position),
+            StorageFile::Remote(file) => file,
+        };
+        let position = match position {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => offset_by(file.length, offset),
+            SeekFrom::Current(offset) => offset_by(file.position, offset),
+        };
+        match position {
+            Some(position) => {
+                file.position = position;
+                Ok(position)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Seek to a negative position",
+            )),
+        }
+    }
+}
+
+fn offset_by(position: u64, offset: i64) -> Option<u64> {
+    if offset < 0 {
+        position.checked_sub(offset.unsigned_abs())
+    } else {
+        position.checked_add(offset as u64)
+    }
+}
@@ -0,0 +1,35 @@
+use std::sync::Mutex;
+use std::thread;
+
+// Results of a worker that panicked are left out
+pub fn map_parallel<T, R, F>(items: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync,
+{
+    let workers = thread::available_parallelism()
+        .map(|workers| workers.get())
+        .unwrap_or(1)
+        .min(items.len());
+    let queue = Mutex::new(items.into_iter());
+    let next = || queue.lock().ok().and_then(|mut queue| queue.next());
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    while let Some(item) = next() {
+                        results.push(f(item));
+                    }
+                    results
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap_or_default())
+            .collect()
+    })
+}
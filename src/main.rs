@@ -2,8 +2,9 @@
 extern crate lazy_static;
 
 use crate::config::{init_config, CONFIG};
+use crate::presentation::cli::{scan_issue_report, template_report};
 use crate::presentation::songs_api::startup;
-use crate::presentation::songs_api::utils::auto_updater;
+use crate::presentation::songs_api::utils::{auto_updater, inbox, trash_cleaner, upload_cleaner};
 
 mod config;
 mod core;
@@ -17,14 +18,83 @@ mod song_infos {
     tonic::include_proto!("song_infos");
 }
 
+mod ratings {
+    tonic::include_proto!("ratings");
+}
+
+mod plays {
+    tonic::include_proto!("plays");
+}
+
+mod playback_state {
+    tonic::include_proto!("playback_state");
+}
+
+mod remote_control {
+    tonic::include_proto!("remote_control");
+}
+
+mod listen_together {
+    tonic::include_proto!("listen_together");
+}
+
+mod upload {
+    tonic::include_proto!("upload");
+}
+
+mod library {
+    tonic::include_proto!("library");
+}
+
+mod tags {
+    tonic::include_proto!("tags");
+}
+
+mod overrides {
+    tonic::include_proto!("overrides");
+}
+
+mod trash {
+    tonic::include_proto!("trash");
+}
+
+mod watcher {
+    tonic::include_proto!("watcher");
+}
+
+mod rescan {
+    tonic::include_proto!("rescan");
+}
+
+mod scan_issues {
+    tonic::include_proto!("scan_issues");
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     init_config();
 
-    if CONFIG.update_automatically && !auto_updater::start(CONFIG.files_folder_path.clone()) {
+    if CONFIG.report_templates {
+        template_report::print();
+        return Ok(());
+    }
+
+    if CONFIG.report_scan_issues {
+        scan_issue_report::print();
+        return Ok(());
+    }
+
+    if CONFIG.update_automatically && !auto_updater::start() {
         panic!("Could not start auto-updater");
     }
 
+    if !inbox::start() {
+        panic!("Could not start inbox");
+    }
+
+    upload_cleaner::start();
+    trash_cleaner::start();
+
     if let Err(e) = startup::start(CONFIG.start_locally, CONFIG.port).await {
         eprintln!("Server error occurred: {}", e);
     }
@@ -0,0 +1,2 @@
+pub mod scan_issue_report;
+pub mod template_report;
\ No newline at end of file
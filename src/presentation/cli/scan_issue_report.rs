@@ -0,0 +1,25 @@
+use crate::core::repository::songs_repository::SONGS_REPOSITORY;
+use crate::presentation::songs_api::utils::clock;
+use std::collections::BTreeMap;
+
+pub fn print() {
+    if !SONGS_REPOSITORY.auto_update(clock::now_millis()) {
+        eprintln!("Files folder could not be scanned, showing the issues found before");
+    }
+
+    let issues = SONGS_REPOSITORY.find_scan_issues();
+    let mut by_reason = BTreeMap::new();
+    for issue in &issues {
+        by_reason
+            .entry(issue.reason.to_string())
+            .or_insert_with(Vec::new)
+            .push(issue);
+    }
+    for (reason, issues) in by_reason {
+        println!("{} ({})", reason, issues.len());
+        for issue in issues {
+            println!("    {}\n        {}", issue.file_path, issue.detail);
+        }
+    }
+    println!("{} files could not be added", issues.len());
+}
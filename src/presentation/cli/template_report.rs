@@ -0,0 +1,69 @@
+use crate::config::CONFIG;
+use crate::core::utils::filename_templates::match_templates;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+pub fn print() {
+    let mut files = Vec::new();
+    for root in CONFIG.library_roots.iter() {
+        if root.is_online() {
+            collect_audio_files(Path::new(&root.path), &mut files);
+        } else {
+            println!("Library root {} is offline", root);
+        }
+    }
+    files.sort();
+
+    let mut unmatched = 0;
+    for file in &files {
+        let relative_path = match CONFIG.library_roots.file_path(file) {
+            Some(file_path) => PathBuf::from(file_path),
+            None => file.clone(),
+        };
+        let relative_path = relative_path.as_path();
+        match match_templates(&CONFIG.filename_templates, relative_path) {
+            Some((template, values)) => println!(
+                "{}\n    template: {}\n    title: {}, artist: {}",
+                relative_path.display(),
+                template,
+                values.title.unwrap_or_default(),
+                values.artist.unwrap_or_else(|| String::from("(from tags)"))
+            ),
+            None => {
+                unmatched += 1;
+                println!("{}\n    no template matches", relative_path.display());
+            }
+        }
+    }
+    println!(
+        "{} files, {} without a matching template",
+        files.len(),
+        unmatched
+    );
+}
+
+fn collect_audio_files(folder: &Path, files: &mut Vec<PathBuf>) {
+    let read_dir = match folder.read_dir() {
+        Ok(iterator) => iterator,
+        Err(e) => {
+            eprintln!("Could not read folder {}:\n{}", folder.display(), e);
+            return;
+        }
+    };
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        let is_dir = entry.file_type().map(|x| x.is_dir()).unwrap_or(false);
+        if is_dir {
+            collect_audio_files(&path, files);
+        } else if is_audio_file(&path) {
+            files.push(path);
+        }
+    }
+}
+
+fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .map(|extension| CONFIG.audio_formats.contains(&extension.to_lowercase()))
+        .unwrap_or(false)
+}
@@ -1 +1,2 @@
+pub mod cli;
 pub mod songs_api;
\ No newline at end of file
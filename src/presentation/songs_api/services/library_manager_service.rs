@@ -0,0 +1,70 @@
+use crate::core::data::entity::song::Song;
+use crate::library::{
+    library_service_server::LibraryService, Empty, MoveRequest, RenameRequest, SongReply,
+    SongRequest,
+};
+use crate::presentation::songs_api::utils::library_files;
+use crate::presentation::songs_api::utils::query_threads::query_songs;
+use tonic::{Request, Response, Status};
+
+#[derive(Debug)]
+pub struct LibraryManagerService;
+
+#[tonic::async_trait]
+impl LibraryService for LibraryManagerService {
+    async fn delete(&self, request: Request<SongRequest>) -> Result<Response<Empty>, Status> {
+        let request_ref: &SongRequest = request.get_ref();
+        let song = find_song(&request_ref.name, &request_ref.artist).await?;
+
+        library_files::delete_song(&song).await?;
+
+        println!("Deleted song: {}-{}", song.name, song.artist);
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn rename(&self, request: Request<RenameRequest>) -> Result<Response<SongReply>, Status> {
+        let request_ref: &RenameRequest = request.get_ref();
+        let song = find_song(&request_ref.name, &request_ref.artist).await?;
+
+        let renamed =
+            library_files::rename_song(&song, &request_ref.new_name, &request_ref.new_artist)
+                .await?;
+
+        println!(
+            "Renamed song: {}-{} to {}-{}",
+            song.name, song.artist, renamed.name, renamed.artist
+        );
+        Ok(Response::new(song_reply(renamed)))
+    }
+
+    async fn move_to_folder(
+        &self,
+        request: Request<MoveRequest>,
+    ) -> Result<Response<SongReply>, Status> {
+        let request_ref: &MoveRequest = request.get_ref();
+        let song = find_song(&request_ref.name, &request_ref.artist).await?;
+
+        let moved = library_files::move_song(&song, &request_ref.folder).await?;
+
+        println!(
+            "Moved song: {}-{} to {}",
+            moved.name, moved.artist, moved.file_path
+        );
+        Ok(Response::new(song_reply(moved)))
+    }
+}
+
+async fn find_song(name: &str, artist: &str) -> Result<Song, Status> {
+    let (name, artist) = (name.to_owned(), artist.to_owned());
+    query_songs(move |songs| songs.find_song(&name, &artist))
+        .await
+        .ok_or_else(|| Status::not_found("Song could not be found"))
+}
+
+fn song_reply(song: Song) -> SongReply {
+    SongReply {
+        name: song.name,
+        artist: song.artist,
+        file_path: song.file_path,
+    }
+}
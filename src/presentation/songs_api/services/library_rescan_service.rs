@@ -0,0 +1,78 @@
+use crate::presentation::songs_api::utils::library_rescan::{self, RunningScan};
+use crate::rescan::{rescan_service_server::RescanService, Mode, Progress, RescanRequest};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::interval;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug)]
+pub struct LibraryRescanService;
+
+#[tonic::async_trait]
+impl RescanService for LibraryRescanService {
+    type StartStream = ReceiverStream<Result<Progress, Status>>;
+
+    async fn start(
+        &self,
+        request: Request<RescanRequest>,
+    ) -> Result<Response<Self::StartStream>, Status> {
+        let force = request.get_ref().mode() == Mode::Rescan;
+        let scan = library_rescan::start(force);
+        let (tx, rx) = mpsc::channel(4);
+
+        tokio::spawn(async move {
+            let mut ticker = interval(PROGRESS_INTERVAL);
+            loop {
+                ticker.tick().await;
+                // Read before the counts so the last progress sent holds the final ones.
+                let finished = scan.is_finished();
+                let reply = if finished && !scan.succeeded() {
+                    Err(Status::internal("Files folder could not be scanned"))
+                } else {
+                    Ok(to_progress(&scan, finished))
+                };
+                if let Err(e) = tx.send(reply).await {
+                    eprintln!("Error occurred while sending data:\n{}", e);
+                    break;
+                }
+                if finished {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+fn to_progress(scan: &Arc<RunningScan>, finished: bool) -> Progress {
+    let counts = scan.progress.counts();
+    let remaining = counts.discovered.saturating_sub(counts.processed);
+    // Estimated from the pace so far, files left to extract take about as long as those before
+    let estimated_ms_left = if finished || counts.processed == 0 {
+        0
+    } else {
+        let elapsed_ms = scan.started.elapsed().as_millis() as u64;
+        (elapsed_ms * remaining / counts.processed) as i64
+    };
+    Progress {
+        mode: if scan.force {
+            Mode::Rescan
+        } else {
+            Mode::Validate
+        } as i32,
+        started_at: scan.started_at,
+        discovered: counts.discovered,
+        processed: counts.processed,
+        added: counts.added,
+        updated: counts.updated,
+        removed: counts.removed,
+        failed: counts.failed,
+        estimated_ms_left,
+        finished,
+    }
+}
@@ -0,0 +1,141 @@
+use crate::listen_together::{
+    listen_together_service_server::ListenTogetherService, Action, CloseRequest, CreateRequest,
+    Empty, HostCommand, JoinRequest, Sync as GroupSync,
+};
+use crate::presentation::songs_api::utils::group_sessions::GroupSessions;
+use crate::presentation::songs_api::utils::library_files::path_status;
+use crate::presentation::songs_api::utils::query_threads::query_songs;
+use std::io::ErrorKind;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tokio::time::{interval_at, Instant};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+// Guests get the clock periodically as well, so they can correct their drift
+const SYNC_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Default)]
+pub struct ListenTogetherHostService {
+    sessions: GroupSessions,
+}
+
+impl ListenTogetherHostService {
+    pub fn new() -> Self {
+        ListenTogetherHostService::default()
+    }
+}
+
+#[tonic::async_trait]
+impl ListenTogetherService for ListenTogetherHostService {
+    async fn create(&self, request: Request<CreateRequest>) -> Result<Response<GroupSync>, Status> {
+        let host = &request.get_ref().user;
+        if host.is_empty() {
+            return Err(Status::invalid_argument("User is missing"));
+        }
+
+        match self.sessions.create(host) {
+            Ok(sync) => {
+                println!("{} started listening session {}", host, sync.session);
+                Ok(Response::new(sync))
+            }
+            Err(e) => Err(Status::internal(e)),
+        }
+    }
+
+    async fn control(&self, request: Request<HostCommand>) -> Result<Response<GroupSync>, Status> {
+        let command = request.into_inner();
+        let action = match Action::from_i32(command.action) {
+            Some(action) => action,
+            None => return Err(Status::invalid_argument("Unknown action")),
+        };
+
+        if action == Action::Enqueue {
+            match &command.song {
+                Some(song) => {
+                    let (name, artist) = (song.name.clone(), song.artist.clone());
+                    let song_path =
+                        query_songs(move |songs| songs.find_song_path(&name, &artist)).await;
+                    if let Err(e) = song_path {
+                        return Err(match e.kind() {
+                            ErrorKind::PermissionDenied => path_status(&e),
+                            _ => Status::not_found("Song could not be found"),
+                        });
+                    }
+                }
+                None => return Err(Status::not_found("Song could not be found")),
+            }
+        }
+
+        match self.sessions.apply(
+            &command.session,
+            &command.user,
+            action,
+            command.position_ms,
+            command.song,
+        ) {
+            Ok(sync) => Ok(Response::new(sync)),
+            Err(e) => Err(Status::failed_precondition(e)),
+        }
+    }
+
+    type JoinStream = ReceiverStream<Result<GroupSync, Status>>;
+
+    async fn join(
+        &self,
+        request: Request<JoinRequest>,
+    ) -> Result<Response<Self::JoinStream>, Status> {
+        let JoinRequest { session, user } = request.into_inner();
+        let (sync, mut updates) = match self.sessions.subscribe(&session) {
+            Ok(subscription) => subscription,
+            Err(e) => return Err(Status::not_found(e)),
+        };
+        let sessions = self.sessions.clone();
+        let (tx, rx) = mpsc::channel(4);
+
+        println!("{} joined listening session {}", user, session);
+
+        tokio::spawn(async move {
+            if tx.send(Ok(sync)).await.is_err() {
+                return;
+            }
+
+            let mut ticker = interval_at(Instant::now() + SYNC_INTERVAL, SYNC_INTERVAL);
+            loop {
+                let sync = tokio::select! {
+                    _ = tx.closed() => break,
+                    _ = ticker.tick() => match sessions.current(&session) {
+                        Some(sync) => sync,
+                        None => break,
+                    },
+                    update = updates.recv() => match update {
+                        Ok(sync) => sync,
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    },
+                };
+                if let Err(e) = tx.send(Ok(sync)).await {
+                    eprintln!("Error occurred while sending data:\n{}", e);
+                    break;
+                }
+            }
+
+            println!("{} left listening session {}", user, session);
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn close(&self, request: Request<CloseRequest>) -> Result<Response<Empty>, Status> {
+        let request_ref: &CloseRequest = request.get_ref();
+
+        match self.sessions.close(&request_ref.session, &request_ref.user) {
+            Ok(_) => {
+                println!("Listening session {} closed", request_ref.session);
+                Ok(Response::new(Empty {}))
+            }
+            Err(e) => Err(Status::failed_precondition(e)),
+        }
+    }
+}
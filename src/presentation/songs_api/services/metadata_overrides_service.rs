@@ -0,0 +1,174 @@
+use crate::core::data::entity::metadata_override::MetadataOverride;
+use crate::core::data::entity::song::Song;
+use crate::core::utils::audio_tags::{self, AudioTags};
+use crate::core::utils::file_identity::file_identity;
+use crate::overrides::{
+    overrides_service_server::OverridesService, Empty, Field, Override, OverrideRequest,
+    SongRequest, Values,
+};
+use crate::presentation::songs_api::utils::library_files::full_path;
+use crate::presentation::songs_api::utils::query_threads::query_songs;
+use tokio::sync::mpsc;
+use tokio::task;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+#[derive(Debug)]
+pub struct MetadataOverridesService;
+
+#[tonic::async_trait]
+impl OverridesService for MetadataOverridesService {
+    async fn set(&self, request: Request<OverrideRequest>) -> Result<Response<Override>, Status> {
+        let request = request.into_inner();
+        let song = find_song(&request.name, &request.artist).await?;
+        let file_id = identify(&song).await?;
+        let requested = request.values.unwrap_or_default();
+
+        let mut values = find_metadata_override(&file_id)
+            .await
+            .map(|metadata_override| metadata_override.values)
+            .unwrap_or_default();
+        for field in request.fields {
+            match Field::from_i32(field) {
+                Some(Field::Title) => values.title = non_empty(&requested.title),
+                Some(Field::Artist) => values.artist = non_empty(&requested.artist),
+                Some(Field::Album) => values.album = non_empty(&requested.album),
+                Some(Field::Genre) => values.genre = non_empty(&requested.genre),
+                Some(Field::Year) => values.year = non_zero(requested.year).map(|year| year as i32),
+                Some(Field::Track) => values.track = non_zero(requested.track),
+                None => return Err(Status::invalid_argument("Unknown override field")),
+            }
+        }
+
+        println!("Overriding metadata of song: {}-{}", song.name, song.artist);
+
+        let metadata_override = MetadataOverride::new(file_id, song.file_path.clone(), values);
+        let saved = {
+            let metadata_override = metadata_override.clone();
+            query_songs(move |songs| {
+                if metadata_override.values == AudioTags::default() {
+                    songs.remove_metadata_override(&metadata_override.file_id)
+                } else {
+                    songs.save_metadata_override(&metadata_override)
+                }
+            })
+            .await
+        };
+        if !saved || refresh_song(&song).await.is_none() {
+            return Err(Status::internal("Override could not be saved"));
+        }
+        Ok(Response::new(to_response(metadata_override)))
+    }
+
+    async fn clear(&self, request: Request<SongRequest>) -> Result<Response<Override>, Status> {
+        let request_ref: &SongRequest = request.get_ref();
+        let song = find_song(&request_ref.name, &request_ref.artist).await?;
+        let file_id = identify(&song).await?;
+        let metadata_override = find_metadata_override(&file_id)
+            .await
+            .ok_or_else(|| Status::failed_precondition("Song has no metadata override"))?;
+
+        println!(
+            "Clearing metadata override of song: {}-{}",
+            song.name, song.artist
+        );
+
+        let removed = query_songs(move |songs| songs.remove_metadata_override(&file_id)).await;
+        if !removed {
+            return Err(Status::internal("Override could not be cleared"));
+        }
+        let refreshed = refresh_song(&song)
+            .await
+            .ok_or_else(|| Status::internal("Song could not be updated"))?;
+
+        // Overridden values are kept by scans, so the ones of the file are restored here
+        let path = full_path(&refreshed.file_path);
+        let mut details = task::spawn_blocking(move || audio_tags::read_tags(&path).ok())
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        if let Some(overrides) = &refreshed.overrides {
+            details.apply(overrides);
+        }
+        let saved = query_songs(move |songs| songs.save_song_details(&refreshed, &details)).await;
+        if !saved {
+            return Err(Status::internal("Song could not be updated"));
+        }
+        Ok(Response::new(to_response(metadata_override)))
+    }
+
+    type ListStream = ReceiverStream<Result<Override, Status>>;
+
+    async fn list(&self, _request: Request<Empty>) -> Result<Response<Self::ListStream>, Status> {
+        let overrides = query_songs(|songs| songs.find_metadata_overrides()).await;
+        let (tx, rx) = mpsc::channel(4);
+
+        tokio::spawn(async move {
+            for metadata_override in overrides {
+                if let Err(e) = tx.send(Ok(to_response(metadata_override))).await {
+                    eprintln!("Error occurred while sending data:\n{}", e);
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+async fn find_song(name: &str, artist: &str) -> Result<Song, Status> {
+    let (name, artist) = (name.to_owned(), artist.to_owned());
+    query_songs(move |songs| songs.find_song(&name, &artist))
+        .await
+        .ok_or_else(|| Status::not_found("Song could not be found"))
+}
+
+async fn find_metadata_override(file_id: &str) -> Option<MetadataOverride> {
+    let file_id = file_id.to_owned();
+    query_songs(move |songs| songs.find_metadata_override(&file_id)).await
+}
+
+async fn identify(song: &Song) -> Result<String, Status> {
+    let path = full_path(&song.file_path);
+    task::spawn_blocking(move || file_identity(&path))
+        .await
+        .map_err(|_| Status::internal("File could not be read"))?
+        .map_err(|e| Status::failed_precondition(e.to_string()))
+}
+
+async fn refresh_song(song: &Song) -> Option<Song> {
+    let path = full_path(&song.file_path);
+    query_songs(move |songs| {
+        let refreshed = songs.fetch_song_from_path(path)?;
+        if songs.insert_song(refreshed.clone()) {
+            Some(refreshed)
+        } else {
+            None
+        }
+    })
+    .await
+}
+
+fn non_empty(text: &str) -> Option<String> {
+    Some(text.trim().to_string()).filter(|text| !text.is_empty())
+}
+
+fn non_zero(number: u32) -> Option<u32> {
+    Some(number).filter(|number| *number != 0)
+}
+
+fn to_response(metadata_override: MetadataOverride) -> Override {
+    let values = metadata_override.values;
+    Override {
+        file_path: metadata_override.file_path,
+        values: Some(Values {
+            title: values.title.unwrap_or_default(),
+            artist: values.artist.unwrap_or_default(),
+            album: values.album.unwrap_or_default(),
+            genre: values.genre.unwrap_or_default(),
+            year: values.year.unwrap_or(0).max(0) as u32,
+            track: values.track.unwrap_or(0),
+        }),
+    }
+}
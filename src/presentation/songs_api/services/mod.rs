@@ -1,2 +1,15 @@
+pub mod library_manager_service;
+pub mod library_rescan_service;
+pub mod listen_together_host_service;
+pub mod metadata_overrides_service;
+pub mod playback_state_sync_service;
+pub mod plays_manager_service;
+pub mod ratings_manager_service;
+pub mod remote_control_relay_service;
+pub mod scan_issues_sender_service;
 pub mod songs_sender_service;
 pub mod song_infos_sender_service;
+pub mod tags_editor_service;
+pub mod trash_manager_service;
+pub mod upload_receiver_service;
+pub mod watcher_health_service;
@@ -0,0 +1,192 @@
+use crate::core::data::entity::playback_state::{
+    PlaybackState as SongsPlaybackState, PlaybackStateSave,
+};
+use crate::core::data::entity::queued_song::QueuedSong as SongsQueuedSong;
+use crate::playback_state::{
+    playback_state_service_server::PlaybackStateService, PlaybackState, QueuedSong, StateRequest,
+    UpdateReply,
+};
+use crate::presentation::songs_api::utils::clock;
+use crate::presentation::songs_api::utils::query_threads::query_songs;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+#[derive(Debug, Default)]
+pub struct PlaybackStateSyncService {
+    channels: Mutex<HashMap<String, broadcast::Sender<PlaybackState>>>,
+}
+
+impl PlaybackStateSyncService {
+    pub fn new() -> Self {
+        PlaybackStateSyncService::default()
+    }
+
+    fn publish(&self, state: PlaybackState) {
+        let mut channels = match self.channels.lock() {
+            Ok(lock) => lock,
+            Err(_) => return,
+        };
+        if let Some(sender) = channels.get(&state.user) {
+            let user = state.user.clone();
+            if sender.send(state).is_err() {
+                channels.remove(&user);
+            }
+        }
+    }
+
+    fn subscribe(&self, user: &str) -> Option<broadcast::Receiver<PlaybackState>> {
+        let mut channels = self.channels.lock().ok()?;
+        let sender = channels
+            .entry(user.to_string())
+            .or_insert_with(|| broadcast::channel(16).0);
+        Some(sender.subscribe())
+    }
+}
+
+#[tonic::async_trait]
+impl PlaybackStateService for PlaybackStateSyncService {
+    async fn update(
+        &self,
+        request: Request<PlaybackState>,
+    ) -> Result<Response<UpdateReply>, Status> {
+        let mut state = request.into_inner();
+
+        if state.user.is_empty() {
+            return Err(Status::invalid_argument("User is missing"));
+        }
+        if !state.queue.is_empty() && state.index as usize >= state.queue.len() {
+            return Err(Status::invalid_argument("Queue index is out of range"));
+        }
+        if state.updated_at <= 0 {
+            state.updated_at = clock::now_millis();
+        }
+
+        let songs_state = to_songs_state(&state);
+        let (save, current) = query_songs(move |songs| {
+            let save = songs.save_playback_state(&songs_state);
+            (save, songs.find_playback_state(&songs_state.user))
+        })
+        .await;
+        let accepted = match save {
+            Some(PlaybackStateSave::Saved) => true,
+            Some(PlaybackStateSave::Stale) => false,
+            Some(PlaybackStateSave::UnknownSong) => {
+                return Err(Status::not_found("Queued song could not be found"))
+            }
+            None => return Err(Status::internal("Playback state could not be saved")),
+        };
+        let current = current.map(from_songs_state);
+
+        if accepted {
+            println!(
+                "Playback state of {} updated by {}",
+                state.user, state.device
+            );
+            self.publish(state);
+        }
+
+        Ok(Response::new(UpdateReply { accepted, current }))
+    }
+
+    async fn get(&self, request: Request<StateRequest>) -> Result<Response<PlaybackState>, Status> {
+        let user = request.into_inner().user;
+        match query_songs(move |songs| songs.find_playback_state(&user)).await {
+            Some(state) => Ok(Response::new(from_songs_state(state))),
+            None => Err(Status::not_found("No playback state stored")),
+        }
+    }
+
+    type SubscribeStream = ReceiverStream<Result<PlaybackState, Status>>;
+
+    async fn subscribe(
+        &self,
+        request: Request<StateRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let StateRequest { user, device } = request.into_inner();
+        let mut updates = match self.subscribe(&user) {
+            Some(receiver) => receiver,
+            None => return Err(Status::internal("Could not subscribe to playback state")),
+        };
+        let (tx, rx) = mpsc::channel(4);
+
+        println!("Device {} subscribed to playback state of {}", device, user);
+
+        tokio::spawn(async move {
+            let stored = {
+                let user = user.clone();
+                query_songs(move |songs| songs.find_playback_state(&user)).await
+            };
+            if let Some(state) = stored {
+                if tx.send(Ok(from_songs_state(state))).await.is_err() {
+                    return;
+                }
+            }
+
+            loop {
+                let state = tokio::select! {
+                    _ = tx.closed() => break,
+                    update = updates.recv() => match update {
+                        Ok(state) => state,
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    },
+                };
+                if state.device == device {
+                    continue;
+                }
+                if let Err(e) = tx.send(Ok(state)).await {
+                    eprintln!("Error occurred while sending data:\n{}", e);
+                    break;
+                }
+            }
+
+            println!(
+                "Device {} unsubscribed from playback state of {}",
+                device, user
+            );
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+fn to_songs_state(state: &PlaybackState) -> SongsPlaybackState {
+    let queue = state
+        .queue
+        .iter()
+        .map(|song| SongsQueuedSong::new(song.name.clone(), song.artist.clone()))
+        .collect();
+    SongsPlaybackState::new(
+        state.user.clone(),
+        state.device.clone(),
+        queue,
+        state.index,
+        state.position_ms,
+        state.playing,
+        state.updated_at,
+    )
+}
+
+fn from_songs_state(state: SongsPlaybackState) -> PlaybackState {
+    let queue = state
+        .queue
+        .into_iter()
+        .map(|song| QueuedSong {
+            name: song.name,
+            artist: song.artist,
+        })
+        .collect();
+    PlaybackState {
+        user: state.user,
+        device: state.device,
+        queue,
+        index: state.index,
+        position_ms: state.position_ms,
+        playing: state.playing,
+        updated_at: state.updated_at,
+    }
+}
@@ -0,0 +1,136 @@
+use crate::core::data::entity::play_event::PlayEvent as SongPlayEvent;
+use crate::core::data::entity::song_plays::SongPlays;
+use crate::plays::{
+    plays_service_server::PlaysService, Empty, MostPlayedRequest, NeverPlayedRequest, PlayEvent,
+    RecentlyPlayedRequest, Response as PlaysResponse,
+};
+use crate::presentation::songs_api::utils::clock;
+use crate::presentation::songs_api::utils::query_threads::query_songs;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+const DEFAULT_COUNT: usize = 20;
+const DEFAULT_DAYS: u32 = 30;
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug)]
+pub struct PlaysManagerService;
+
+#[tonic::async_trait]
+impl PlaysService for PlaysManagerService {
+    async fn report(&self, request: Request<PlayEvent>) -> Result<Response<Empty>, Status> {
+        let event = request.into_inner();
+
+        if event.started_at <= 0 {
+            return Err(Status::invalid_argument("Play start time is missing"));
+        }
+
+        println!(
+            "Received play event for song: {}-{}, completed: {}",
+            event.name, event.artist, event.completed
+        );
+
+        let play = SongPlayEvent::new(
+            event.user,
+            event.name,
+            event.artist,
+            event.started_at,
+            event.listened_ms,
+            event.completed,
+        );
+
+        if !query_songs(move |songs| songs.record_play(play)).await {
+            return Err(Status::not_found("Play could not be recorded"));
+        }
+
+        Ok(Response::new(Empty {}))
+    }
+
+    type RecentlyPlayedStream = ReceiverStream<Result<PlaysResponse, Status>>;
+
+    async fn recently_played(
+        &self,
+        request: Request<RecentlyPlayedRequest>,
+    ) -> Result<Response<Self::RecentlyPlayedStream>, Status> {
+        let request = request.into_inner();
+        let count = count_or_default(request.count);
+        let days = if request.days == 0 {
+            DEFAULT_DAYS
+        } else {
+            request.days
+        };
+        let since = clock::now_millis() - i64::from(days) * DAY_MS;
+        let song_plays =
+            query_songs(move |songs| songs.find_recently_played(&request.user, since, count)).await;
+
+        Ok(Response::new(send_song_plays(song_plays)))
+    }
+
+    type MostPlayedStream = ReceiverStream<Result<PlaysResponse, Status>>;
+
+    async fn most_played(
+        &self,
+        request: Request<MostPlayedRequest>,
+    ) -> Result<Response<Self::MostPlayedStream>, Status> {
+        let request = request.into_inner();
+        let to = if request.to > 0 { request.to } else { i64::MAX };
+
+        if request.from >= to {
+            return Err(Status::invalid_argument(
+                "Period start must be before its end",
+            ));
+        }
+
+        let count = count_or_default(request.count);
+        let song_plays = query_songs(move |songs| {
+            songs.find_most_played(&request.user, request.from, to, count)
+        })
+        .await;
+
+        Ok(Response::new(send_song_plays(song_plays)))
+    }
+
+    type NeverPlayedStream = ReceiverStream<Result<PlaysResponse, Status>>;
+
+    async fn never_played(
+        &self,
+        request: Request<NeverPlayedRequest>,
+    ) -> Result<Response<Self::NeverPlayedStream>, Status> {
+        let request = request.into_inner();
+        let count = count_or_default(request.count);
+        let song_plays =
+            query_songs(move |songs| songs.find_never_played(&request.user, count)).await;
+
+        Ok(Response::new(send_song_plays(song_plays)))
+    }
+}
+
+fn count_or_default(count: u32) -> usize {
+    if count == 0 {
+        DEFAULT_COUNT
+    } else {
+        count as usize
+    }
+}
+
+fn send_song_plays(song_plays: Vec<SongPlays>) -> ReceiverStream<Result<PlaysResponse, Status>> {
+    let (tx, rx) = mpsc::channel(4);
+
+    tokio::spawn(async move {
+        for song_play in song_plays {
+            let plays_response = PlaysResponse {
+                name: song_play.name,
+                artist: song_play.artist,
+                play_count: song_play.play_count,
+                last_played_at: song_play.last_played_at.unwrap_or(0),
+            };
+            if let Err(e) = tx.send(Ok(plays_response)).await {
+                eprintln!("Error occurred while sending data:\n{}", e);
+                return;
+            };
+        }
+    });
+
+    ReceiverStream::new(rx)
+}
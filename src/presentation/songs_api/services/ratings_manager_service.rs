@@ -0,0 +1,132 @@
+use crate::core::data::entity::rating_filter::RatingFilter;
+use crate::presentation::songs_api::utils::query_threads::query_songs;
+use crate::ratings::{
+    ratings_service_server::RatingsService, Empty, ListRequest, RatingRequest,
+    Response as RatingsResponse, SongRequest,
+};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+#[derive(Debug)]
+pub struct RatingsManagerService;
+
+#[tonic::async_trait]
+impl RatingsService for RatingsManagerService {
+    async fn set_rating(&self, request: Request<RatingRequest>) -> Result<Response<Empty>, Status> {
+        let request = request.into_inner();
+
+        if !(1..=5).contains(&request.rating) {
+            return Err(Status::invalid_argument("Rating must be between 1 and 5"));
+        }
+
+        println!(
+            "Received rating {} for song: {}-{}",
+            request.rating, request.name, request.artist
+        );
+
+        let rated = query_songs(move |songs| {
+            songs.rate_song(
+                &request.user,
+                &request.name,
+                &request.artist,
+                request.rating as u8,
+            )
+        })
+        .await;
+        if !rated {
+            return Err(Status::not_found("Song could not be rated"));
+        }
+
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn clear_rating(&self, request: Request<SongRequest>) -> Result<Response<Empty>, Status> {
+        let request = request.into_inner();
+
+        let unrated = query_songs(move |songs| {
+            songs.unrate_song(&request.user, &request.name, &request.artist)
+        })
+        .await;
+        if !unrated {
+            return Err(Status::internal("Rating could not be cleared"));
+        }
+
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn set_favourite(
+        &self,
+        request: Request<SongRequest>,
+    ) -> Result<Response<Empty>, Status> {
+        let request = request.into_inner();
+
+        println!(
+            "Received favourite for song: {}-{}",
+            request.name, request.artist
+        );
+
+        let favourited = query_songs(move |songs| {
+            songs.favourite_song(&request.user, &request.name, &request.artist)
+        })
+        .await;
+        if !favourited {
+            return Err(Status::not_found("Song could not be marked as favourite"));
+        }
+
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn clear_favourite(
+        &self,
+        request: Request<SongRequest>,
+    ) -> Result<Response<Empty>, Status> {
+        let request = request.into_inner();
+
+        let unfavourited = query_songs(move |songs| {
+            songs.unfavourite_song(&request.user, &request.name, &request.artist)
+        })
+        .await;
+        if !unfavourited {
+            return Err(Status::internal("Favourite could not be cleared"));
+        }
+
+        Ok(Response::new(Empty {}))
+    }
+
+    type ListStream = ReceiverStream<Result<RatingsResponse, Status>>;
+
+    async fn list(
+        &self,
+        request: Request<ListRequest>,
+    ) -> Result<Response<Self::ListStream>, Status> {
+        let (tx, rx) = mpsc::channel(4);
+
+        tokio::spawn(async move {
+            let request = request.into_inner();
+            let filter = RatingFilter::new(
+                request.min_rating.min(5) as u8,
+                request.favourites_only,
+                request.sort_by_rating,
+            );
+
+            let song_ratings =
+                query_songs(move |songs| songs.find_song_ratings(&request.user, &filter)).await;
+
+            for song_rating in song_ratings {
+                let ratings_response = RatingsResponse {
+                    name: song_rating.name,
+                    artist: song_rating.artist,
+                    rating: song_rating.rating.unwrap_or(0) as u32,
+                    favourite: song_rating.favourite,
+                };
+                if let Err(e) = tx.send(Ok(ratings_response)).await {
+                    eprintln!("Error occurred while sending data:\n{}", e);
+                    return;
+                };
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
@@ -0,0 +1,202 @@
+use crate::presentation::songs_api::utils::query_threads::query_songs;
+use crate::presentation::songs_api::utils::remote_sessions::{MemberSender, RemoteSessions};
+use crate::remote_control::{
+    client_message::Message as ClientMessageKind,
+    remote_control_service_server::RemoteControlService,
+    server_message::Message as ServerMessageKind, Action, ClientMessage, Command, Error, Join,
+    ServerMessage,
+};
+use std::time::Duration;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::time::timeout;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
+
+const MEMBER_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_VOLUME: u32 = 100;
+
+#[derive(Debug, Default)]
+pub struct RemoteControlRelayService {
+    sessions: RemoteSessions,
+}
+
+impl RemoteControlRelayService {
+    pub fn new() -> Self {
+        RemoteControlRelayService::default()
+    }
+}
+
+#[tonic::async_trait]
+impl RemoteControlService for RemoteControlRelayService {
+    type ControlStream = ReceiverStream<Result<ServerMessage, Status>>;
+
+    async fn control(
+        &self,
+        request: Request<Streaming<ClientMessage>>,
+    ) -> Result<Response<Self::ControlStream>, Status> {
+        let mut inbound = request.into_inner();
+        let sessions = self.sessions.clone();
+        let (tx, rx) = mpsc::channel(16);
+
+        let join = match next_message(&mut inbound).await {
+            Some(ClientMessageKind::Join(join)) => join,
+            _ => return Err(Status::invalid_argument("Expected a join message")),
+        };
+        let Join {
+            session,
+            user,
+            device,
+        } = join;
+        if session.is_empty() || device.is_empty() {
+            return Err(Status::invalid_argument("Session and device are required"));
+        }
+        if let Err(e) = sessions.join(&session, &user, &device, tx.clone()) {
+            return Err(Status::failed_precondition(e));
+        }
+
+        println!("Device {} joined remote session {}", device, session);
+        broadcast_membership(&sessions, &session);
+
+        tokio::spawn(async move {
+            while let Some(message) = next_message(&mut inbound).await {
+                // Dropped for lagging behind, or replaced by a new connection of the device
+                if !sessions.is_member(&session, &tx) {
+                    break;
+                }
+                let result = match message {
+                    ClientMessageKind::Join(_) => Err(String::from("Already joined a session")),
+                    ClientMessageKind::Command(command) => {
+                        relay_command(&sessions, &session, &device, command).await
+                    }
+                    ClientMessageKind::State(mut state) => {
+                        state.from_device = device.clone();
+                        match sessions.state_recipients(&session, &device) {
+                            Ok(recipients) => {
+                                send_to_all(
+                                    &sessions,
+                                    &session,
+                                    &recipients,
+                                    ServerMessageKind::State(state),
+                                );
+                                Ok(())
+                            }
+                            Err(e) => Err(e),
+                        }
+                    }
+                    ClientMessageKind::Transfer(transfer) => {
+                        match sessions.transfer(&session, &device, &transfer.device) {
+                            Ok(_) => {
+                                broadcast_membership(&sessions, &session);
+                                Ok(())
+                            }
+                            Err(e) => Err(e),
+                        }
+                    }
+                    ClientMessageKind::Heartbeat(_) => Ok(()),
+                };
+                if let Err(message) = result {
+                    send_error(&tx, message).await;
+                }
+            }
+
+            sessions.leave(&session, &tx);
+            println!("Device {} left remote session {}", device, session);
+            broadcast_membership(&sessions, &session);
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+async fn next_message(inbound: &mut Streaming<ClientMessage>) -> Option<ClientMessageKind> {
+    loop {
+        match timeout(MEMBER_TIMEOUT, inbound.message()).await {
+            Ok(Ok(Some(ClientMessage {
+                message: Some(message),
+            }))) => return Some(message),
+            Ok(Ok(Some(_))) => continue,
+            _ => return None,
+        }
+    }
+}
+
+async fn relay_command(
+    sessions: &RemoteSessions,
+    session: &str,
+    device: &str,
+    mut command: Command,
+) -> Result<(), String> {
+    let action = Action::from_i32(command.action).ok_or("Unknown action")?;
+    match action {
+        Action::SetVolume if command.volume > MAX_VOLUME => {
+            return Err(format!("Volume must be at most {}", MAX_VOLUME));
+        }
+        Action::Enqueue => {
+            let (name, artist) = (command.name.clone(), command.artist.clone());
+            if query_songs(move |songs| songs.find_song_path(&name, &artist))
+                .await
+                .is_err()
+            {
+                return Err(format!(
+                    "Song could not be found: {}-{}",
+                    command.name, command.artist
+                ));
+            }
+        }
+        _ => {}
+    }
+    let recipient = sessions.command_recipient(session, device, &command.target_device)?;
+    command.from_device = device.to_string();
+    send_to_all(
+        sessions,
+        session,
+        &[recipient],
+        ServerMessageKind::Command(command),
+    );
+    Ok(())
+}
+
+fn broadcast_membership(sessions: &RemoteSessions, session: &str) {
+    if let Some((membership, recipients)) = sessions.membership(session) {
+        send_to_all(sessions, session, &recipients, membership);
+    }
+}
+
+fn send_to_all(
+    sessions: &RemoteSessions,
+    session: &str,
+    recipients: &[MemberSender],
+    message: ServerMessageKind,
+) {
+    let mut dropped = false;
+    for recipient in recipients {
+        let server_message = ServerMessage {
+            message: Some(message.clone()),
+        };
+        match recipient.try_send(Ok(server_message)) {
+            Ok(_) => {}
+            Err(TrySendError::Full(_)) => {
+                if let Some(device) = sessions.remove_member(session, recipient) {
+                    println!(
+                        "Device {} fell behind in remote session {}",
+                        device, session
+                    );
+                    dropped = true;
+                }
+            }
+            Err(e) => eprintln!("Error occurred while sending data:\n{}", e),
+        }
+    }
+    if dropped {
+        broadcast_membership(sessions, session);
+    }
+}
+
+async fn send_error(tx: &MemberSender, message: String) {
+    let server_message = ServerMessage {
+        message: Some(ServerMessageKind::Error(Error { message })),
+    };
+    if let Err(e) = tx.send(Ok(server_message)).await {
+        eprintln!("Error occurred while sending data:\n{}", e);
+    }
+}
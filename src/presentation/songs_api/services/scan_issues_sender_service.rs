@@ -0,0 +1,45 @@
+use crate::core::data::entity::scan_issue::ScanIssue as ScanIssueEntity;
+use crate::core::utils::audio_checks::IssueReason;
+use crate::presentation::songs_api::utils::query_threads::query_songs;
+use crate::scan_issues::{scan_issues_service_server::ScanIssuesService, Empty, Reason, ScanIssue};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+#[derive(Debug)]
+pub struct ScanIssuesSenderService;
+
+#[tonic::async_trait]
+impl ScanIssuesService for ScanIssuesSenderService {
+    type ListStream = ReceiverStream<Result<ScanIssue, Status>>;
+
+    async fn list(&self, _request: Request<Empty>) -> Result<Response<Self::ListStream>, Status> {
+        let issues = query_songs(|songs| songs.find_scan_issues()).await;
+        let (tx, rx) = mpsc::channel(4);
+
+        tokio::spawn(async move {
+            for issue in issues {
+                if let Err(e) = tx.send(Ok(to_response(issue))).await {
+                    eprintln!("Error occurred while sending data:\n{}", e);
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+fn to_response(issue: ScanIssueEntity) -> ScanIssue {
+    ScanIssue {
+        file_path: issue.file_path,
+        reason: match issue.reason {
+            IssueReason::BadHeader => Reason::BadHeader,
+            IssueReason::Truncated => Reason::Truncated,
+            IssueReason::UnsupportedCodec => Reason::UnsupportedCodec,
+            IssueReason::NoMetadata => Reason::NoMetadata,
+        } as i32,
+        detail: issue.detail,
+        found_at: issue.found_at,
+    }
+}
@@ -1,9 +1,15 @@
-use crate::core::repository::songs_repository::SONGS_REPOSITORY;
+use crate::config::CONFIG;
+use crate::core::data::entity::rating_filter::RatingFilter;
+use crate::core::utils::safe_paths::confine;
 use crate::presentation::songs_api::utils::async_file_reader::AsyncFileReader;
+use crate::presentation::songs_api::utils::query_threads::query_songs;
 use crate::song_infos::{
     song_infos_service_server::SongInfosService, Request as SongInfosRequest,
     Response as SongInfosResponse,
 };
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::Path;
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status};
@@ -22,11 +28,21 @@ impl SongInfosService for SongInfosSenderService {
         let (tx, rx) = mpsc::channel(4);
 
         tokio::spawn(async move {
-            let keyword: &String = &request.get_ref().name;
+            let request = request.into_inner();
+            let keyword = request.name;
+            let user = request.user;
+            let filter = RatingFilter::new(
+                request.min_rating.min(5) as u8,
+                request.favourites_only,
+                request.sort_by_rating,
+            );
 
             println!("Received request for song info: {}", keyword);
 
-            let song_infos = SONGS_REPOSITORY.find_song_infos(keyword, 8);
+            let song_infos = {
+                let keyword = keyword.clone();
+                query_songs(move |songs| songs.find_song_infos(&keyword, &user, &filter, 8)).await
+            };
 
             if song_infos.is_empty() {
                 println!("No matches found for: {}", keyword);
@@ -35,17 +51,34 @@ impl SongInfosService for SongInfosSenderService {
 
             println!("Starting to send song infos for: {}", keyword);
 
+            let mut online_roots = HashMap::new();
             for song_info in song_infos {
+                let root = CONFIG.library_roots.find(&song_info.file_path);
+                let online = *online_roots
+                    .entry(root.name.clone())
+                    .or_insert_with(|| root.is_online());
                 let mut image_bytes: Vec<u8> = Vec::new();
                 if let Some(path) = song_info.image_path {
-                    if let Some(mut reader) = AsyncFileReader::new(&path).await {
-                        image_bytes = reader.read_at_once().await;
+                    let covers_folder = Path::new(&CONFIG.covers_folder_path);
+                    match confine(Path::new(&path), &[covers_folder], CONFIG.symlink_policy) {
+                        Ok(cover_path) => {
+                            let cover_path = cover_path.to_string_lossy();
+                            if let Some(mut reader) = AsyncFileReader::new(&cover_path).await {
+                                image_bytes = reader.read_at_once().await;
+                            }
+                        }
+                        Err(e) if e.kind() == ErrorKind::PermissionDenied => eprintln!("{}", e),
+                        Err(_) => {}
                     }
                 }
                 let song_infos_response = SongInfosResponse {
                     name: song_info.name,
                     artist: song_info.artist,
                     image: image_bytes,
+                    rating: song_info.rating.unwrap_or(0) as u32,
+                    favourite: song_info.favourite,
+                    root: root.name.clone(),
+                    offline: !online,
                 };
                 if let Err(e) = tx.send(Ok(song_infos_response)).await {
                     eprintln!("Error occurred while sending data:\n{}", e);
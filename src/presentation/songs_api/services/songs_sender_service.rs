@@ -1,8 +1,10 @@
-use crate::core::repository::songs_repository::SONGS_REPOSITORY;
 use crate::presentation::songs_api::utils::async_file_reader::AsyncFileReader;
+use crate::presentation::songs_api::utils::library_files::path_status;
+use crate::presentation::songs_api::utils::query_threads::query_songs;
 use crate::songs::{
     songs_service_server::SongsService, Chunk as SongChunk, Request as SongRequest,
 };
+use std::io::ErrorKind;
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status};
@@ -21,15 +23,24 @@ impl SongsService for SongsSenderService {
         let (tx, rx) = mpsc::channel(4);
 
         tokio::spawn(async move {
-            let request_ref: &SongRequest = request.get_ref();
-            let name: &String = &request_ref.name;
-            let artist: &String = &request_ref.artist;
+            let SongRequest { name, artist } = request.into_inner();
 
             println!("Received request for song: {}-{}", name, artist);
 
-            let song_path = match SONGS_REPOSITORY.find_song_path(name, artist) {
-                Some(path) => path,
-                None => {
+            let song_path = {
+                let (name, artist) = (name.clone(), artist.clone());
+                query_songs(move |songs| songs.find_song_path(&name, &artist)).await
+            };
+            let song_path = match song_path {
+                Ok(path) => path,
+                Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+                    eprintln!("Song is outside of the library: {}-{}", name, artist);
+                    if let Err(e) = tx.send(Err(path_status(&e))).await {
+                        eprintln!("Error occurred while sending data:\n{}", e);
+                    }
+                    return;
+                }
+                Err(_) => {
                     eprintln!("Song could not be found: {}-{}", name, artist);
                     return;
                 }
@@ -0,0 +1,140 @@
+use crate::core::data::entity::song::Song;
+use crate::core::utils::audio_tags::{detect_image_mime_type, AudioTags, Cover};
+use crate::presentation::songs_api::utils::query_threads::query_songs;
+use crate::presentation::songs_api::utils::tag_editor::{apply_tags, read_song_tags, TagChange};
+use crate::tags::{
+    tags_service_server::TagsService, Field, SongRequest, TagEdit as TagEditResponse, Tags,
+    UpdateRequest,
+};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+const HISTORY_LENGTH: usize = 20;
+
+#[derive(Debug)]
+pub struct TagsEditorService;
+
+#[tonic::async_trait]
+impl TagsService for TagsEditorService {
+    async fn get(&self, request: Request<SongRequest>) -> Result<Response<Tags>, Status> {
+        let request_ref: &SongRequest = request.get_ref();
+        let song = find_song(&request_ref.name, &request_ref.artist).await?;
+
+        let tags = read_song_tags(&song).await?;
+        Ok(Response::new(to_response(tags)))
+    }
+
+    async fn update(&self, request: Request<UpdateRequest>) -> Result<Response<Tags>, Status> {
+        let request = request.into_inner();
+        let song = find_song(&request.name, &request.artist).await?;
+        let requested = request.tags.unwrap_or_default();
+
+        let previous = read_song_tags(&song).await?;
+        let mut tags = previous.clone();
+        for field in request.fields {
+            match Field::from_i32(field) {
+                Some(Field::Title) => tags.title = Some(requested.title.clone()),
+                Some(Field::Artist) => tags.artist = Some(requested.artist.clone()),
+                Some(Field::Album) => tags.album = non_empty(&requested.album),
+                Some(Field::Genre) => tags.genre = non_empty(&requested.genre),
+                Some(Field::Year) => tags.year = non_zero(requested.year).map(|year| year as i32),
+                Some(Field::Track) => tags.track = non_zero(requested.track),
+                Some(Field::Cover) => {
+                    tags.cover = to_cover(&requested.cover).map_err(Status::invalid_argument)?
+                }
+                None => return Err(Status::invalid_argument("Unknown tag field")),
+            }
+        }
+
+        println!("Editing tags of song: {}-{}", song.name, song.artist);
+
+        apply_tags(&song, &previous, &tags, TagChange::Edit).await?;
+        Ok(Response::new(to_response(tags)))
+    }
+
+    type HistoryStream = ReceiverStream<Result<TagEditResponse, Status>>;
+
+    async fn history(
+        &self,
+        request: Request<SongRequest>,
+    ) -> Result<Response<Self::HistoryStream>, Status> {
+        let request = request.into_inner();
+        let edits = query_songs(move |songs| {
+            songs.find_tag_edits(&request.name, &request.artist, HISTORY_LENGTH)
+        })
+        .await;
+        let (tx, rx) = mpsc::channel(4);
+
+        tokio::spawn(async move {
+            for edit in edits {
+                let response = TagEditResponse {
+                    edited_at: edit.edited_at,
+                    previous: Some(to_response(edit.previous)),
+                };
+                if let Err(e) = tx.send(Ok(response)).await {
+                    eprintln!("Error occurred while sending data:\n{}", e);
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn undo(&self, request: Request<SongRequest>) -> Result<Response<Tags>, Status> {
+        let request_ref: &SongRequest = request.get_ref();
+        let song = find_song(&request_ref.name, &request_ref.artist).await?;
+        let (name, artist) = (song.name.clone(), song.artist.clone());
+        let edit = query_songs(move |songs| songs.find_tag_edits(&name, &artist, 1))
+            .await
+            .pop()
+            .ok_or_else(|| Status::failed_precondition("There is no tag edit to undo"))?;
+
+        println!("Undoing tag edit of song: {}-{}", song.name, song.artist);
+
+        let current = read_song_tags(&song).await?;
+        apply_tags(&song, &current, &edit.previous, TagChange::Undo(edit.id)).await?;
+        Ok(Response::new(to_response(edit.previous)))
+    }
+}
+
+async fn find_song(name: &str, artist: &str) -> Result<Song, Status> {
+    let (name, artist) = (name.to_owned(), artist.to_owned());
+    query_songs(move |songs| songs.find_song(&name, &artist))
+        .await
+        .ok_or_else(|| Status::not_found("Song could not be found"))
+}
+
+fn non_empty(text: &str) -> Option<String> {
+    Some(text.trim().to_string()).filter(|text| !text.is_empty())
+}
+
+fn non_zero(number: u32) -> Option<u32> {
+    Some(number).filter(|number| *number != 0)
+}
+
+fn to_cover(data: &[u8]) -> Result<Option<Cover>, String> {
+    if data.is_empty() {
+        return Ok(None);
+    }
+    match detect_image_mime_type(data) {
+        Some(mime_type) => Ok(Some(Cover {
+            mime_type: mime_type.to_string(),
+            data: data.to_vec(),
+        })),
+        None => Err(String::from("Cover must be a JPEG or PNG image")),
+    }
+}
+
+fn to_response(tags: AudioTags) -> Tags {
+    Tags {
+        title: tags.title.unwrap_or_default(),
+        artist: tags.artist.unwrap_or_default(),
+        album: tags.album.unwrap_or_default(),
+        genre: tags.genre.unwrap_or_default(),
+        year: tags.year.unwrap_or(0).max(0) as u32,
+        track: tags.track.unwrap_or(0),
+        cover: tags.cover.map(|cover| cover.data).unwrap_or_default(),
+    }
+}
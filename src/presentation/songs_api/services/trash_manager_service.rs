@@ -0,0 +1,74 @@
+use crate::config::CONFIG;
+use crate::core::data::entity::trashed_song::TrashedSong as TrashedSongEntity;
+use crate::presentation::songs_api::utils::library_files::full_path;
+use crate::presentation::songs_api::utils::query_threads::query_songs;
+use crate::trash::{
+    trash_service_server::TrashService, Empty, SongReply, SongRequest, TrashedSong,
+};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+#[derive(Debug)]
+pub struct TrashManagerService;
+
+#[tonic::async_trait]
+impl TrashService for TrashManagerService {
+    type ListStream = ReceiverStream<Result<TrashedSong, Status>>;
+
+    async fn list(&self, _request: Request<Empty>) -> Result<Response<Self::ListStream>, Status> {
+        let songs = query_songs(|songs| songs.find_missing_songs()).await;
+        let (tx, rx) = mpsc::channel(4);
+
+        tokio::spawn(async move {
+            for song in songs {
+                if let Err(e) = tx.send(Ok(to_response(song))).await {
+                    eprintln!("Error occurred while sending data:\n{}", e);
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn restore(&self, request: Request<SongRequest>) -> Result<Response<SongReply>, Status> {
+        let request = request.into_inner();
+        let trashed =
+            query_songs(move |songs| songs.find_missing_song(&request.name, &request.artist))
+                .await
+                .ok_or_else(|| Status::not_found("Song is not in the trash"))?;
+
+        let path = full_path(&trashed.file_path);
+        if !path.is_file() {
+            return Err(Status::failed_precondition("Song file is still missing"));
+        }
+        let song = query_songs(|songs| songs.fetch_song_from_path(path))
+            .await
+            .ok_or_else(|| Status::failed_precondition("Song file is no longer accepted"))?;
+        let restored = {
+            let song = song.clone();
+            query_songs(move |songs| songs.insert_song(song)).await
+        };
+        if !restored {
+            return Err(Status::internal("Song could not be restored"));
+        }
+
+        println!("Restored song: {}-{}", song.name, song.artist);
+        Ok(Response::new(SongReply {
+            name: song.name,
+            artist: song.artist,
+            file_path: song.file_path,
+        }))
+    }
+}
+
+fn to_response(song: TrashedSongEntity) -> TrashedSong {
+    TrashedSong {
+        name: song.name,
+        artist: song.artist,
+        file_path: song.file_path,
+        missing_since: song.missing_since,
+        purge_at: song.missing_since + CONFIG.missing_grace_ms,
+    }
+}
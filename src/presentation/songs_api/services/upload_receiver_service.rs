@@ -0,0 +1,429 @@
+use crate::config::CONFIG;
+use crate::core::data::entity::upload_session::UploadSession;
+use crate::core::utils::audio_format::AUDIO_HEADER_LENGTH;
+use crate::presentation::songs_api::utils::clock;
+use crate::presentation::songs_api::utils::query_threads::query_songs;
+use crate::presentation::songs_api::utils::upload_library::{
+    add_to_library, check_format, file_sha256, library_path, remove_session, session_path,
+    validate_metadata,
+};
+use crate::upload::{
+    upload_chunk::Data, upload_service_server::UploadService, Empty, Metadata, OffsetChunk,
+    SessionRequest, SessionStatus, UploadChunk, UploadReply,
+};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tonic::{Request, Response, Status, Streaming};
+
+const CONFIRM_INTERVAL: u64 = 1024 * 1024;
+
+#[derive(Debug, Default)]
+pub struct UploadReceiverService {
+    next_upload: AtomicU64,
+    busy_sessions: Mutex<HashSet<String>>,
+}
+
+impl UploadReceiverService {
+    pub fn new() -> Self {
+        UploadReceiverService::default()
+    }
+
+    fn next_id(&self) -> String {
+        format!(
+            "{}-{}",
+            clock::now_millis(),
+            self.next_upload.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+
+    fn claim_session(&self, session: &str) -> bool {
+        match self.busy_sessions.lock() {
+            Ok(mut sessions) => sessions.insert(session.to_string()),
+            Err(_) => false,
+        }
+    }
+
+    fn release_session(&self, session: &str) {
+        if let Ok(mut sessions) = self.busy_sessions.lock() {
+            sessions.remove(session);
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl UploadService for UploadReceiverService {
+    async fn upload(
+        &self,
+        request: Request<Streaming<UploadChunk>>,
+    ) -> Result<Response<UploadReply>, Status> {
+        let mut inbound = request.into_inner();
+
+        let metadata = match inbound.message().await? {
+            Some(UploadChunk {
+                data: Some(Data::Metadata(metadata)),
+            }) => metadata,
+            _ => return Err(Status::invalid_argument("Expected upload metadata first")),
+        };
+        validate_metadata(&metadata).map_err(Status::invalid_argument)?;
+
+        if library_path(&metadata).exists() {
+            return Err(Status::already_exists("Song already exists"));
+        }
+
+        println!("Receiving upload: {}-{}", metadata.name, metadata.artist);
+
+        let temp_path =
+            Path::new(&CONFIG.uploads_folder_path).join(format!("{}.part", self.next_id()));
+
+        let mut result = receive_file(&mut inbound, &temp_path, &metadata).await;
+        if result.is_ok() {
+            result = add_to_library(&temp_path, &metadata).await;
+        }
+        if let Err(status) = result {
+            eprintln!("Upload failed: {}", status.message());
+            if let Err(e) = fs::remove_file(&temp_path).await {
+                eprintln!("Could not remove partial upload:\n{}", e);
+            }
+            return Err(status);
+        }
+
+        println!(
+            "Successfully received upload: {}-{}",
+            metadata.name, metadata.artist
+        );
+
+        Ok(Response::new(UploadReply {
+            name: metadata.name,
+            artist: metadata.artist,
+            size: metadata.size,
+        }))
+    }
+
+    async fn open_session(
+        &self,
+        request: Request<Metadata>,
+    ) -> Result<Response<SessionStatus>, Status> {
+        let metadata = request.into_inner();
+        validate_metadata(&metadata).map_err(Status::invalid_argument)?;
+
+        if library_path(&metadata).exists() {
+            return Err(Status::already_exists("Song already exists"));
+        }
+
+        let mut id = [0; 16];
+        OsRng.fill_bytes(&mut id);
+        let id = hex::encode(id);
+
+        let session = UploadSession::new(
+            id,
+            metadata.name,
+            metadata.artist,
+            metadata.format,
+            metadata.size,
+            metadata.sha256.to_lowercase(),
+            0,
+            clock::now_millis() + CONFIG.upload_expiry_ms,
+        );
+
+        let opened = File::create(session_path(&session.id)).await.is_ok() && {
+            let session = session.clone();
+            query_songs(move |songs| songs.open_upload_session(&session)).await
+        };
+        if !opened {
+            remove_session(&session.id).await;
+            return Err(Status::internal("Upload session could not be opened"));
+        }
+
+        println!(
+            "Opened upload session {} for: {}-{}",
+            session.id, session.name, session.artist
+        );
+
+        Ok(Response::new(session_status(&session)))
+    }
+
+    async fn write_chunks(
+        &self,
+        request: Request<Streaming<OffsetChunk>>,
+    ) -> Result<Response<SessionStatus>, Status> {
+        let mut inbound = request.into_inner();
+
+        let first_chunk = match inbound.message().await? {
+            Some(chunk) => chunk,
+            None => return Err(Status::invalid_argument("No chunks sent")),
+        };
+        let id = first_chunk.session.clone();
+
+        // Read once claimed, as a stream resumed before may have confirmed more data meanwhile
+        if !self.claim_session(&id) {
+            return Err(Status::aborted("Upload session is already in use"));
+        }
+        let mut session = match find_session(&id).await {
+            Some(session) => session,
+            None => {
+                self.release_session(&id);
+                return Err(session_not_found());
+            }
+        };
+        let result = write_session(&mut inbound, first_chunk, &mut session).await;
+        self.release_session(&id);
+
+        result.map(|_| Response::new(session_status(&session)))
+    }
+
+    async fn query_offset(
+        &self,
+        request: Request<SessionRequest>,
+    ) -> Result<Response<SessionStatus>, Status> {
+        let session = find_session(&request.get_ref().session)
+            .await
+            .ok_or_else(session_not_found)?;
+        Ok(Response::new(session_status(&session)))
+    }
+
+    async fn commit(
+        &self,
+        request: Request<SessionRequest>,
+    ) -> Result<Response<UploadReply>, Status> {
+        let id = &request.get_ref().session;
+        if !self.claim_session(id) {
+            return Err(Status::aborted("Upload session is already in use"));
+        }
+        let result = match find_session(id).await {
+            Some(session) if session.offset != session.size => {
+                Err(Status::failed_precondition(format!(
+                    "Upload is incomplete, {} of {} bytes confirmed",
+                    session.offset, session.size
+                )))
+            }
+            Some(session) => commit_session(&session).await.map(|_| session),
+            None => Err(session_not_found()),
+        };
+        self.release_session(id);
+        let session = result?;
+
+        println!(
+            "Committed upload session {} for: {}-{}",
+            session.id, session.name, session.artist
+        );
+
+        Ok(Response::new(UploadReply {
+            name: session.name,
+            artist: session.artist,
+            size: session.size,
+        }))
+    }
+
+    async fn abort(&self, request: Request<SessionRequest>) -> Result<Response<Empty>, Status> {
+        let id = &request.get_ref().session;
+        if !self.claim_session(id) {
+            return Err(Status::aborted("Upload session is already in use"));
+        }
+        let removed = match find_session(id).await {
+            Some(_) => Ok(remove_session(id).await),
+            None => Err(session_not_found()),
+        };
+        self.release_session(id);
+
+        if !removed? {
+            return Err(Status::internal("Upload session could not be removed"));
+        }
+
+        println!("Aborted upload session {}", id);
+        Ok(Response::new(Empty {}))
+    }
+}
+
+async fn receive_file(
+    inbound: &mut Streaming<UploadChunk>,
+    temp_path: &Path,
+    metadata: &Metadata,
+) -> Result<(), Status> {
+    let mut file = File::create(temp_path)
+        .await
+        .map_err(|_| Status::internal("Could not create upload file"))?;
+    let mut hasher = Sha256::new();
+    let mut header: Vec<u8> = Vec::with_capacity(AUDIO_HEADER_LENGTH);
+    let mut received: u64 = 0;
+
+    while let Some(upload_chunk) = inbound.message().await? {
+        let chunk = match upload_chunk.data {
+            Some(Data::Chunk(chunk)) => chunk,
+            _ => return Err(Status::invalid_argument("Expected a file chunk")),
+        };
+        received += chunk.len() as u64;
+        if received > metadata.size {
+            return Err(Status::invalid_argument("Received more data than declared"));
+        }
+
+        if header.len() < AUDIO_HEADER_LENGTH {
+            let missing = (AUDIO_HEADER_LENGTH - header.len()).min(chunk.len());
+            header.extend_from_slice(&chunk[..missing]);
+            if header.len() == AUDIO_HEADER_LENGTH || received == metadata.size {
+                check_format(&header, &metadata.format).map_err(Status::invalid_argument)?;
+            }
+        }
+
+        hasher.update(&chunk);
+        file.write_all(&chunk)
+            .await
+            .map_err(|_| Status::internal("Could not write upload file"))?;
+    }
+
+    if received != metadata.size {
+        return Err(Status::invalid_argument("Upload is incomplete"));
+    }
+    file.sync_all()
+        .await
+        .map_err(|_| Status::internal("Could not write upload file"))?;
+
+    let hash = hex::encode(hasher.finalize());
+    if !hash.eq_ignore_ascii_case(&metadata.sha256) {
+        return Err(Status::data_loss("Hash does not match the uploaded data"));
+    }
+    Ok(())
+}
+
+async fn write_session(
+    inbound: &mut Streaming<OffsetChunk>,
+    first_chunk: OffsetChunk,
+    session: &mut UploadSession,
+) -> Result<(), Status> {
+    let mut file = match open_at_offset(session).await {
+        Ok(file) => file,
+        Err(_) => return Err(Status::internal("Could not open upload file")),
+    };
+
+    let mut written = session.offset;
+    let mut next_chunk = Some(first_chunk);
+    let result = loop {
+        let chunk = match next_chunk.take() {
+            Some(chunk) => chunk,
+            None => match inbound.message().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break Ok(()),
+                Err(status) => break Err(status),
+            },
+        };
+        if chunk.session != session.id {
+            break Err(Status::invalid_argument(
+                "Chunks must belong to one session",
+            ));
+        }
+        let chunk_end = match chunk.offset.checked_add(chunk.data.len() as u64) {
+            Some(chunk_end) if chunk_end <= session.size => chunk_end,
+            _ => {
+                break Err(Status::invalid_argument(
+                    "Chunk ends past the declared size",
+                ))
+            }
+        };
+        if chunk.offset > written {
+            break Err(Status::out_of_range(format!(
+                "Expected a chunk at offset {}",
+                written
+            )));
+        }
+        if chunk_end <= written {
+            continue;
+        }
+
+        let new_data = &chunk.data[(written - chunk.offset) as usize..];
+        if file.write_all(new_data).await.is_err() {
+            break Err(Status::internal("Could not write upload file"));
+        }
+        written = chunk_end;
+
+        if written - session.offset >= CONFIRM_INTERVAL
+            && !confirm(&mut file, session, written).await
+        {
+            break Err(Status::internal("Could not confirm received data"));
+        }
+    };
+
+    // Whatever arrived before a failure is kept, so the client can resume from there
+    if written != session.offset && !confirm(&mut file, session, written).await {
+        return Err(Status::internal("Could not confirm received data"));
+    }
+    result
+}
+
+// Anything past the confirmed offset may not have reached the disk, so it gets written again
+async fn open_at_offset(session: &UploadSession) -> std::io::Result<File> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(session_path(&session.id))
+        .await?;
+    file.set_len(session.offset).await?;
+    file.seek(SeekFrom::Start(session.offset)).await?;
+    Ok(file)
+}
+
+async fn confirm(file: &mut File, session: &mut UploadSession, offset: u64) -> bool {
+    if file.sync_data().await.is_err() {
+        return false;
+    }
+    let expires_at = clock::now_millis() + CONFIG.upload_expiry_ms;
+    let id = session.id.clone();
+    let confirmed =
+        query_songs(move |songs| songs.confirm_upload_offset(&id, offset, expires_at)).await;
+    if !confirmed {
+        return false;
+    }
+    session.offset = offset;
+    session.expires_at = expires_at;
+    true
+}
+
+async fn commit_session(session: &UploadSession) -> Result<(), Status> {
+    let path = session_path(&session.id);
+    let hash = file_sha256(&path)
+        .await
+        .map_err(|_| Status::internal("Could not read upload file"))?;
+    if hash != session.sha256 {
+        // The received data is unusable, the client has to start over
+        remove_session(&session.id).await;
+        return Err(Status::data_loss("Hash does not match the uploaded data"));
+    }
+
+    let metadata = Metadata {
+        name: session.name.clone(),
+        artist: session.artist.clone(),
+        format: session.format.clone(),
+        size: session.size,
+        sha256: session.sha256.clone(),
+    };
+    add_to_library(&path, &metadata).await?;
+
+    let id = session.id.clone();
+    query_songs(move |songs| songs.close_upload_session(&id)).await;
+    Ok(())
+}
+
+async fn find_session(id: &str) -> Option<UploadSession> {
+    let id = id.to_owned();
+    query_songs(move |songs| songs.find_upload_session(&id))
+        .await
+        .filter(|session| session.expires_at > clock::now_millis())
+}
+
+fn session_not_found() -> Status {
+    Status::not_found("Upload session not found or expired")
+}
+
+fn session_status(session: &UploadSession) -> SessionStatus {
+    SessionStatus {
+        session: session.id.clone(),
+        offset: session.offset,
+        size: session.size,
+        expires_at: session.expires_at,
+    }
+}
@@ -0,0 +1,41 @@
+use crate::config::CONFIG;
+use crate::core::utils::library_roots::LibraryRoot;
+use crate::presentation::songs_api::utils::auto_updater::{self, WatcherState};
+use crate::watcher::{watcher_service_server::WatcherService, Empty, State, WatcherHealth};
+use tonic::{Request, Response, Status};
+
+#[derive(Debug)]
+pub struct WatcherHealthService;
+
+#[tonic::async_trait]
+impl WatcherService for WatcherHealthService {
+    async fn health(&self, _request: Request<Empty>) -> Result<Response<WatcherHealth>, Status> {
+        let mut reply = root_health(CONFIG.library_roots.default_root());
+        reply.roots = CONFIG.library_roots.iter().map(root_health).collect();
+        Ok(Response::new(reply))
+    }
+}
+
+fn root_health(root: &LibraryRoot) -> WatcherHealth {
+    match auto_updater::health(&root.name) {
+        Some(health) => WatcherHealth {
+            state: match health.state {
+                WatcherState::Watching => State::Watching,
+                WatcherState::Polling => State::Polling,
+                WatcherState::Restarting => State::Restarting,
+                WatcherState::Offline => State::Offline,
+            } as i32,
+            since: health.since,
+            restarts: health.restarts,
+            last_error: health.last_error.unwrap_or_default(),
+            last_reconciled_at: health.last_reconciled_at,
+            root: root.name.clone(),
+            roots: Vec::new(),
+        },
+        None => WatcherHealth {
+            state: State::Disabled as i32,
+            root: root.name.clone(),
+            ..WatcherHealth::default()
+        },
+    }
+}
@@ -1,10 +1,37 @@
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use tonic::transport::Server;
 
+use crate::library::library_service_server::LibraryServiceServer as LibraryServiceBuilder;
+use crate::listen_together::listen_together_service_server::ListenTogetherServiceServer as ListenTogetherServiceBuilder;
+use crate::overrides::overrides_service_server::OverridesServiceServer as OverridesServiceBuilder;
+use crate::playback_state::playback_state_service_server::PlaybackStateServiceServer as PlaybackStateServiceBuilder;
+use crate::plays::plays_service_server::PlaysServiceServer as PlaysServiceBuilder;
+use crate::presentation::songs_api::services::library_manager_service::LibraryManagerService;
+use crate::presentation::songs_api::services::library_rescan_service::LibraryRescanService;
+use crate::presentation::songs_api::services::listen_together_host_service::ListenTogetherHostService;
+use crate::presentation::songs_api::services::metadata_overrides_service::MetadataOverridesService;
+use crate::presentation::songs_api::services::playback_state_sync_service::PlaybackStateSyncService;
+use crate::presentation::songs_api::services::plays_manager_service::PlaysManagerService;
+use crate::presentation::songs_api::services::ratings_manager_service::RatingsManagerService;
+use crate::presentation::songs_api::services::remote_control_relay_service::RemoteControlRelayService;
+use crate::presentation::songs_api::services::scan_issues_sender_service::ScanIssuesSenderService;
 use crate::presentation::songs_api::services::song_infos_sender_service::SongInfosSenderService;
 use crate::presentation::songs_api::services::songs_sender_service::SongsSenderService;
+use crate::presentation::songs_api::services::tags_editor_service::TagsEditorService;
+use crate::presentation::songs_api::services::trash_manager_service::TrashManagerService;
+use crate::presentation::songs_api::services::upload_receiver_service::UploadReceiverService;
+use crate::presentation::songs_api::services::watcher_health_service::WatcherHealthService;
+use crate::presentation::songs_api::utils::admin_token;
+use crate::ratings::ratings_service_server::RatingsServiceServer as RatingsServiceBuilder;
+use crate::remote_control::remote_control_service_server::RemoteControlServiceServer as RemoteControlServiceBuilder;
+use crate::rescan::rescan_service_server::RescanServiceServer as RescanServiceBuilder;
+use crate::scan_issues::scan_issues_service_server::ScanIssuesServiceServer as ScanIssuesServiceBuilder;
 use crate::song_infos::song_infos_service_server::SongInfosServiceServer as SongInfosServiceBuilder;
 use crate::songs::songs_service_server::SongsServiceServer as SongsServiceBuilder;
+use crate::tags::tags_service_server::TagsServiceServer as TagsServiceBuilder;
+use crate::trash::trash_service_server::TrashServiceServer as TrashServiceBuilder;
+use crate::upload::upload_service_server::UploadServiceServer as UploadServiceBuilder;
+use crate::watcher::watcher_service_server::WatcherServiceServer as WatcherServiceBuilder;
 
 pub async fn start(start_locally: bool, port: u16) -> Result<(), Box<dyn std::error::Error>> {
     let ip_address = if start_locally {
@@ -16,9 +43,36 @@ pub async fn start(start_locally: bool, port: u16) -> Result<(), Box<dyn std::er
     println!("Starting server on {}", address);
     let songs_svc = SongsServiceBuilder::new(SongsSenderService);
     let song_infos_svc = SongInfosServiceBuilder::new(SongInfosSenderService);
+    let ratings_svc = RatingsServiceBuilder::new(RatingsManagerService);
+    let plays_svc = PlaysServiceBuilder::new(PlaysManagerService);
+    let playback_state_svc = PlaybackStateServiceBuilder::new(PlaybackStateSyncService::new());
+    let remote_control_svc = RemoteControlServiceBuilder::new(RemoteControlRelayService::new());
+    let listen_together_svc = ListenTogetherServiceBuilder::new(ListenTogetherHostService::new());
+    let upload_svc = UploadServiceBuilder::new(UploadReceiverService::new());
+    let library_svc =
+        LibraryServiceBuilder::with_interceptor(LibraryManagerService, admin_token::check);
+    let tags_svc = TagsServiceBuilder::new(TagsEditorService);
+    let overrides_svc = OverridesServiceBuilder::new(MetadataOverridesService);
+    let trash_svc = TrashServiceBuilder::new(TrashManagerService);
+    let watcher_svc = WatcherServiceBuilder::new(WatcherHealthService);
+    let rescan_svc = RescanServiceBuilder::new(LibraryRescanService);
+    let scan_issues_svc = ScanIssuesServiceBuilder::new(ScanIssuesSenderService);
     Server::builder()
         .add_service(songs_svc)
         .add_service(song_infos_svc)
+        .add_service(ratings_svc)
+        .add_service(plays_svc)
+        .add_service(playback_state_svc)
+        .add_service(remote_control_svc)
+        .add_service(listen_together_svc)
+        .add_service(upload_svc)
+        .add_service(library_svc)
+        .add_service(tags_svc)
+        .add_service(overrides_svc)
+        .add_service(trash_svc)
+        .add_service(watcher_svc)
+        .add_service(rescan_svc)
+        .add_service(scan_issues_svc)
         .serve(address)
         .await?;
 
@@ -0,0 +1,72 @@
+use crate::config::CONFIG;
+use tonic::metadata::MetadataMap;
+use tonic::{Request, Status};
+
+#[allow(clippy::result_large_err)] // The signature tonic takes
+pub fn check(request: Request<()>) -> Result<Request<()>, Status> {
+    match refusal(CONFIG.admin_token.as_deref(), request.metadata()) {
+        Some(status) => Err(status),
+        None => Ok(request),
+    }
+}
+
+fn refusal(admin_token: Option<&str>, metadata: &MetadataMap) -> Option<Status> {
+    let admin_token = match admin_token {
+        Some(admin_token) => admin_token,
+        None => return Some(Status::permission_denied("Admin operations are disabled")),
+    };
+    let token = metadata
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match token {
+        None => Some(Status::unauthenticated("Admin token is missing")),
+        Some(token) if !equal_in_constant_time(token.as_bytes(), admin_token.as_bytes()) => {
+            Some(Status::unauthenticated("Admin token is wrong"))
+        }
+        Some(_) => None,
+    }
+}
+
+fn equal_in_constant_time(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Code;
+
+    fn request(authorization: Option<&str>) -> Request<()> {
+        let mut request = Request::new(());
+        if let Some(authorization) = authorization {
+            let value = authorization.parse().unwrap();
+            request.metadata_mut().insert("authorization", value);
+        }
+        request
+    }
+
+    fn code(admin_token: Option<&str>, authorization: Option<&str>) -> Option<Code> {
+        refusal(admin_token, request(authorization).metadata()).map(|status| status.code())
+    }
+
+    #[test]
+    fn requests_need_the_admin_token() {
+        let token = Some("secret");
+        assert_eq!(code(token, None), Some(Code::Unauthenticated));
+        assert_eq!(code(token, Some("secret")), Some(Code::Unauthenticated));
+        assert_eq!(
+            code(token, Some("Bearer other")),
+            Some(Code::Unauthenticated)
+        );
+        assert_eq!(
+            code(token, Some("Bearer secret2")),
+            Some(Code::Unauthenticated)
+        );
+        assert_eq!(code(token, Some("Bearer secret")), None);
+        assert_eq!(
+            code(None, Some("Bearer secret")),
+            Some(Code::PermissionDenied)
+        );
+    }
+}
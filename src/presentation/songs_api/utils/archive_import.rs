@@ -0,0 +1,199 @@
+use crate::config::{ArchiveHandling, CONFIG};
+use crate::core::repository::songs_repository::SONGS_REPOSITORY;
+use crate::core::utils::archives::{archive_stem, extract, is_archive};
+use crate::presentation::songs_api::utils::clock;
+use crate::presentation::songs_api::utils::inbox::wait_until_settled;
+use crate::presentation::songs_api::utils::library_changes;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+const IMAGE_FORMATS: [&str; 3] = ["jpg", "jpeg", "png"];
+
+lazy_static! {
+    // Archives that failed, kept until they are replaced
+    static ref FAILED_ARCHIVES: Mutex<HashMap<PathBuf, SystemTime>> = Mutex::new(HashMap::new());
+}
+
+pub fn import_all(folder: &Path) {
+    let read_dir = match folder.read_dir() {
+        Ok(iterator) => iterator,
+        Err(_) => return,
+    };
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        let is_dir = entry.file_type().map(|x| x.is_dir()).unwrap_or(false);
+        if !(is_dir || is_archive(&path)) || SONGS_REPOSITORY.is_ignored(&path) {
+            continue;
+        }
+        if is_dir {
+            import_all(&path);
+        } else {
+            import(&path);
+        }
+    }
+}
+
+// Extracted outside of the library first, so the watcher never sees a partial album
+pub fn import(path: &Path) -> bool {
+    let (stem, folder) = match (archive_stem(path), path.parent()) {
+        (Some(stem), Some(folder)) => (stem, folder),
+        _ => return false,
+    };
+    let album_folder = folder.join(&stem);
+    // Archives that are kept were imported before once their folder exists
+    if album_folder.exists() {
+        return false;
+    }
+    wait_until_settled(path);
+
+    let modified = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok();
+    if let (Some(modified), Ok(failed)) = (modified, FAILED_ARCHIVES.lock()) {
+        if failed.get(path) == Some(&modified) {
+            return false;
+        }
+    }
+
+    if let Err(e) = import_settled(path, &stem, &album_folder) {
+        eprintln!("Could not import archive {}:\n{}", path.display(), e);
+        if let (Some(modified), Ok(mut failed)) = (modified, FAILED_ARCHIVES.lock()) {
+            failed.insert(path.to_path_buf(), modified);
+        }
+        return false;
+    }
+    if let Ok(mut failed) = FAILED_ARCHIVES.lock() {
+        failed.remove(path);
+    }
+
+    SONGS_REPOSITORY.update_folder(&album_folder, clock::now_millis());
+    println!("Imported archive: {}", path.display());
+
+    handle_original(path);
+    true
+}
+
+fn import_settled(path: &Path, stem: &str, album_folder: &Path) -> Result<(), String> {
+    let temp_folder = Path::new(&CONFIG.uploads_folder_path).join(format!(
+        "{}-{}.extracting",
+        clock::now_millis(),
+        stem
+    ));
+    let files = match extract(path, &temp_folder, &is_wanted, CONFIG.max_archive_size) {
+        Ok(files) => files,
+        Err(e) => {
+            remove_temp_folder(&temp_folder);
+            return Err(e.to_string());
+        }
+    };
+    if !files.iter().any(|file| is_audio_file(file)) {
+        remove_temp_folder(&temp_folder);
+        return Err(String::from("It holds no accepted audio files"));
+    }
+
+    // Albums are often zipped inside a folder of their own, which is left out
+    let content = single_folder(&temp_folder).unwrap_or_else(|| temp_folder.clone());
+    library_changes::record(album_folder);
+    if let Err(e) = move_folder(&content, album_folder) {
+        remove_temp_folder(&temp_folder);
+        return Err(format!(
+            "Content could not be moved into the library: {}",
+            e
+        ));
+    }
+    if temp_folder.exists() {
+        remove_temp_folder(&temp_folder);
+    }
+    Ok(())
+}
+
+fn handle_original(path: &Path) {
+    let result = match CONFIG.archive_handling {
+        ArchiveHandling::Keep => return,
+        ArchiveHandling::Delete => {
+            library_changes::record(path);
+            fs::remove_file(path)
+        }
+        ArchiveHandling::Archive => {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            let mut archived_path = Path::new(&CONFIG.archives_folder_path).join(&*file_name);
+            if archived_path.exists() {
+                archived_path.set_file_name(format!("{}-{}", clock::now_millis(), file_name));
+            }
+            library_changes::record(path);
+            fs::create_dir_all(&CONFIG.archives_folder_path)
+                .and_then(|_| fs::rename(path, &archived_path))
+        }
+    };
+    if let Err(e) = result {
+        eprintln!(
+            "Could not {} imported archive:\n{}",
+            CONFIG.archive_handling, e
+        );
+    }
+}
+
+fn is_wanted(relative_path: &Path) -> bool {
+    let is_hidden = relative_path.components().any(|component| match component {
+        Component::Normal(name) => {
+            let name = name.to_string_lossy();
+            name.starts_with('.') || name == "__MACOSX"
+        }
+        _ => false,
+    });
+    let is_image = matches!(extension(relative_path), Some(extension) if IMAGE_FORMATS.contains(&extension.as_str()));
+    !is_hidden && (is_image || is_audio_file(relative_path))
+}
+
+fn is_audio_file(path: &Path) -> bool {
+    matches!(extension(path), Some(extension) if CONFIG.audio_formats.contains(&extension))
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_lowercase)
+}
+
+fn single_folder(folder: &Path) -> Option<PathBuf> {
+    let mut entries = folder.read_dir().ok()?.flatten();
+    let only = entries.next()?;
+    if entries.next().is_some() || !only.file_type().ok()?.is_dir() {
+        return None;
+    }
+    Some(only.path())
+}
+
+fn move_folder(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    copy_folder(from, to).inspect_err(|_| remove_temp_folder(to))
+}
+
+fn copy_folder(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in from.read_dir()? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_folder(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+fn remove_temp_folder(folder: &Path) {
+    if let Err(e) = fs::remove_dir_all(folder) {
+        if folder.exists() {
+            eprintln!("Could not remove extracted archive content:\n{}", e);
+        }
+    }
+}
@@ -1,40 +1,50 @@
-use std::fs::Metadata;
+use crate::core::utils::storage::{self, StorageFile};
 use std::future::Future;
-use std::path::Path;
-use tokio::fs::File;
-use tokio::io::AsyncReadExt;
+use std::io::Read;
+use std::path::PathBuf;
+use tokio::task;
 
 pub struct AsyncFileReader {
-    file: File,
-    metadata: Metadata,
+    file: Option<StorageFile>,
+    length: u64,
 }
 
 impl AsyncFileReader {
     pub async fn new(path: &str) -> Option<AsyncFileReader> {
-        let file_path = Path::new(path);
-        if !file_path.exists() {
-            return None;
-        }
-        let file = File::open(file_path).await.ok()?;
-        let metadata = file.metadata().await.ok()?;
-        Some(AsyncFileReader { file, metadata })
+        let file_path = PathBuf::from(path);
+        let (file, length) = task::spawn_blocking(move || {
+            let mut file = storage::open(&file_path)?;
+            let length = file.len()?;
+            Ok::<_, std::io::Error>((file, length))
+        })
+        .await
+        .ok()?
+        .ok()?;
+        Some(AsyncFileReader {
+            file: Some(file),
+            length,
+        })
+    }
+
+    async fn read_chunk(&mut self, length: usize) -> Option<Vec<u8>> {
+        let mut file = self.file.take()?;
+        let (file, output) = task::spawn_blocking(move || {
+            let mut output = vec![0; length];
+            let result = file.read_exact(&mut output).map(|_| output);
+            (file, result)
+        })
+        .await
+        .ok()?;
+        self.file = Some(file);
+        output.ok()
     }
 
     pub async fn read_at_once(&mut self) -> Vec<u8> {
-        let available_bytes = self.metadata.len() as usize;
+        let available_bytes = self.length as usize;
         if available_bytes == 0 {
             return Vec::new();
         }
-        let mut output = vec![0; available_bytes];
-        let read_size = match self.file.read(&mut output).await {
-            Ok(read_size) => read_size,
-            Err(_) => return Vec::new(),
-        };
-        if read_size == 0 || read_size != available_bytes {
-            Vec::new()
-        } else {
-            output
-        }
+        self.read_chunk(available_bytes).await.unwrap_or_default()
     }
 
     pub async fn start_reading<F, Fut>(&mut self, callback: F) -> bool
@@ -44,27 +54,22 @@ impl AsyncFileReader {
     {
         let mut read_bytes: u64 = 0;
         let res = loop {
-            let available_bytes = self.metadata.len() - read_bytes;
+            let available_bytes = self.length - read_bytes;
             if available_bytes == 0 {
-                break read_bytes == self.metadata.len();
+                break read_bytes == self.length;
             }
             let bytes_to_read = if available_bytes >= 16384 {
                 16384
             } else {
                 available_bytes
             } as usize;
-            let mut output = vec![0; bytes_to_read];
-            let read_size = match self.file.read(&mut output).await {
-                Ok(read_size) => read_size,
-                Err(_) => break false,
+            let output = match self.read_chunk(bytes_to_read).await {
+                Some(output) => output,
+                None => break false,
             };
-            if read_size == 0 || read_size != bytes_to_read {
+            read_bytes += output.len() as u64;
+            if !callback(output).await {
                 break false;
-            } else {
-                read_bytes += read_size as u64;
-                if !callback(output).await {
-                    break false;
-                }
             }
         };
         res
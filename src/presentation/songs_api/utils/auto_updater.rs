@@ -1,35 +1,313 @@
+use crate::config::CONFIG;
 use crate::core::repository::songs_repository::SONGS_REPOSITORY;
+use crate::core::utils::archives::is_archive;
+use crate::core::utils::folder_overrides::is_overrides_file;
+use crate::core::utils::ignore_rules::is_ignore_file;
+use crate::core::utils::library_roots::LibraryRoot;
+use crate::core::utils::storage;
+use crate::presentation::songs_api::utils::{archive_import, clock, library_changes};
 use hotwatch::blocking::{Flow, Hotwatch};
 use hotwatch::notify::DebouncedEvent;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
-pub fn start(path: String) -> bool {
-    if !SONGS_REPOSITORY.validate() || !SONGS_REPOSITORY.auto_update() {
-        return false;
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+// A watcher that ran this long failed on its own, not because of the previous failure
+const STABLE_RUN: Duration = Duration::from_secs(10 * 60);
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+const OFFLINE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum WatcherState {
+    Watching,
+    Polling,
+    Restarting,
+    Offline,
+}
+
+#[derive(Clone)]
+pub struct WatcherHealth {
+    pub state: WatcherState,
+    pub since: i64,
+    pub restarts: u32,
+    pub last_error: Option<String>,
+    pub last_reconciled_at: i64,
+}
+
+lazy_static! {
+    static ref HEALTH: Mutex<HashMap<String, WatcherHealth>> = Mutex::new(HashMap::new());
+}
+
+pub fn start() -> bool {
+    lazy_static::initialize(&SONGS_REPOSITORY);
+    for root in CONFIG.library_roots.iter() {
+        if !root.is_online() {
+            println!("Library root {} is offline, its songs are kept", root);
+            set_state(root, WatcherState::Offline, Some(offline_error()));
+            continue;
+        }
+        if !reconcile(root) {
+            if root.is_default() {
+                return false;
+            }
+            eprintln!("Library root {} could not be scanned", root);
+        }
+        let state = if storage::has_events(Path::new(&root.path)) {
+            WatcherState::Watching
+        } else {
+            WatcherState::Polling
+        };
+        set_state(root, state, None);
     }
 
-    thread::spawn(|| {
-        let mut hotwatch = Hotwatch::new().expect("Could not start auto-updater");
-        hotwatch
-            .watch(path, |event| {
-                match event {
-                    DebouncedEvent::Create(path) => {
-                        if let Some(song) = SONGS_REPOSITORY.fetch_song_from_path(path) {
-                            SONGS_REPOSITORY.insert_song(song);
-                        }
-                    }
-                    DebouncedEvent::Remove(path) => {
-                        if let Some(song) = SONGS_REPOSITORY.fetch_song_from_path(path) {
-                            SONGS_REPOSITORY.delete_song(song);
-                        }
-                    }
-                    _ => (),
-                };
-                Flow::Continue
-            })
-            .expect("Could not start auto-updater");
-        hotwatch.run();
-    });
+    for root in CONFIG.library_roots.iter() {
+        let root = root.clone();
+        thread::spawn(move || supervise(root));
+    }
 
     true
 }
+
+pub fn health(root: &str) -> Option<WatcherHealth> {
+    HEALTH
+        .lock()
+        .ok()
+        .and_then(|health| health.get(root).cloned())
+}
+
+fn supervise(root: LibraryRoot) {
+    let mut backoff = MIN_BACKOFF;
+    // Changes made before the watch started would be missed otherwise
+    let mut catch_up = false;
+    loop {
+        if !root.is_online() {
+            wait_online(&root);
+            backoff = MIN_BACKOFF;
+            catch_up = true;
+        }
+        if !storage::has_events(Path::new(&root.path)) {
+            poll(&root, catch_up);
+            catch_up = true;
+            continue;
+        }
+        let started_at = Instant::now();
+        let watched = panic::catch_unwind(AssertUnwindSafe(|| watch(&root, catch_up)));
+        catch_up = true;
+        if !root.is_online() {
+            continue;
+        }
+        let (state, error) = match watched {
+            Ok(Ok(reason)) => (WatcherState::Restarting, reason),
+            Ok(Err(reason)) => (WatcherState::Polling, reason),
+            Err(_) => (
+                WatcherState::Restarting,
+                String::from("File watcher panicked"),
+            ),
+        };
+        if started_at.elapsed() >= STABLE_RUN {
+            backoff = MIN_BACKOFF;
+        }
+        eprintln!(
+            "File watcher of {} stopped, retrying in {}s:\n{}",
+            root.name,
+            backoff.as_secs(),
+            error
+        );
+        set_state(&root, state, Some(error));
+        if let Ok(mut health) = HEALTH.lock() {
+            if let Some(health) = health.get_mut(&root.name) {
+                health.restarts += 1;
+            }
+        }
+
+        wait_polling(&root, backoff);
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+fn wait_online(root: &LibraryRoot) {
+    set_state(root, WatcherState::Offline, Some(offline_error()));
+    while !root.is_online() {
+        thread::sleep(OFFLINE_CHECK_INTERVAL);
+    }
+    println!("Library root {} is back online", root);
+}
+
+fn watch(library_root: &LibraryRoot, catch_up: bool) -> Result<String, String> {
+    let root = Path::new(&library_root.path)
+        .canonicalize()
+        .map_err(|e| format!("Library root cannot be watched: {}", e))?;
+    let mut hotwatch =
+        Hotwatch::new().map_err(|e| format!("File watcher cannot be created: {}", e))?;
+
+    let stopped = Arc::new(Mutex::new(String::from("File watcher disconnected")));
+    let handler_stopped = Arc::clone(&stopped);
+    let handler_root = root.clone();
+    hotwatch
+        .watch(&root, move |event| {
+            // The watch ends with the folder, one created in its place is watched anew
+            let root_gone = match &event {
+                DebouncedEvent::Remove(path) | DebouncedEvent::Rename(path, _) => {
+                    *path == handler_root
+                }
+                _ => false,
+            };
+            handle(event);
+            if root_gone {
+                if let Ok(mut stopped) = handler_stopped.lock() {
+                    *stopped = String::from("Library root folder was removed");
+                }
+                return Flow::Exit;
+            }
+            Flow::Continue
+        })
+        .map_err(|e| format!("Library root cannot be watched: {}", e))?;
+
+    if catch_up {
+        reconcile(library_root);
+    }
+    set_state(library_root, WatcherState::Watching, None);
+    println!("Watching library root {} for changes", library_root);
+    hotwatch.run();
+
+    let reason = stopped
+        .lock()
+        .map(|reason| reason.clone())
+        .unwrap_or_default();
+    Ok(reason)
+}
+
+fn handle(event: DebouncedEvent) {
+    match event {
+        // The server keeps the database up to date for its own changes
+        DebouncedEvent::Create(path) | DebouncedEvent::Remove(path)
+            if library_changes::is_own_change(&path) => {}
+        DebouncedEvent::Rename(from, _) if library_changes::is_own_change(&from) => {}
+        DebouncedEvent::Create(path)
+        | DebouncedEvent::Write(path)
+        | DebouncedEvent::Remove(path)
+            if is_ignore_file(&path) =>
+        {
+            if let Some(folder) = path.parent() {
+                SONGS_REPOSITORY.sync_folder(folder, clock::now_millis());
+            }
+        }
+        DebouncedEvent::Rename(from, to) if SONGS_REPOSITORY.is_ignored(&to) => {
+            SONGS_REPOSITORY.mark_missing(from, clock::now_millis());
+        }
+        DebouncedEvent::Create(path)
+        | DebouncedEvent::Write(path)
+        | DebouncedEvent::Chmod(path)
+            if SONGS_REPOSITORY.is_ignored(&path) => {}
+        DebouncedEvent::Create(path)
+        | DebouncedEvent::Write(path)
+        | DebouncedEvent::Rename(_, path)
+            if is_archive(&path) =>
+        {
+            archive_import::import(&path);
+        }
+        DebouncedEvent::Create(path)
+        | DebouncedEvent::Write(path)
+        | DebouncedEvent::Remove(path)
+            if is_overrides_file(&path) =>
+        {
+            if let Some(folder) = path.parent() {
+                SONGS_REPOSITORY.update_folder(folder, clock::now_millis());
+            }
+        }
+        // Folders moved into the library arrive without events for their files
+        DebouncedEvent::Create(path) if path.is_dir() => {
+            SONGS_REPOSITORY.update_folder(&path, clock::now_millis());
+        }
+        // Edits by the server itself are read back too, which keeps the fingerprint current
+        DebouncedEvent::Create(path) | DebouncedEvent::Write(path) => {
+            SONGS_REPOSITORY.refresh_song(path, clock::now_millis());
+        }
+        DebouncedEvent::Chmod(path) => {
+            SONGS_REPOSITORY.update_access(path, clock::now_millis());
+        }
+        DebouncedEvent::Rename(from, to) => {
+            SONGS_REPOSITORY.follow_rename(from, to, clock::now_millis());
+        }
+        DebouncedEvent::Remove(path) => {
+            SONGS_REPOSITORY.mark_missing(path, clock::now_millis());
+        }
+        _ => (),
+    }
+}
+
+fn poll(root: &LibraryRoot, catch_up: bool) {
+    if catch_up {
+        reconcile(root);
+    }
+    set_state(root, WatcherState::Polling, None);
+    println!("Polling library root {} for changes", root);
+    while root.is_online() {
+        thread::sleep(POLL_INTERVAL);
+        reconcile(root);
+    }
+}
+
+fn wait_polling(root: &LibraryRoot, backoff: Duration) {
+    let deadline = Instant::now() + backoff;
+    while Instant::now() < deadline && root.is_online() {
+        thread::sleep((deadline - Instant::now()).min(POLL_INTERVAL));
+        if Instant::now() < deadline {
+            reconcile(root);
+        }
+    }
+}
+
+fn reconcile(root: &LibraryRoot) -> bool {
+    let folder = Path::new(&root.path);
+    archive_import::import_all(folder);
+    if !SONGS_REPOSITORY.sync_folder(folder, clock::now_millis()) {
+        return false;
+    }
+    if let Ok(mut health) = HEALTH.lock() {
+        if let Some(health) = health.get_mut(&root.name) {
+            health.last_reconciled_at = clock::now_millis();
+        }
+    }
+    true
+}
+
+fn offline_error() -> String {
+    String::from("Library root folder is missing")
+}
+
+fn set_state(root: &LibraryRoot, state: WatcherState, error: Option<String>) {
+    let mut health = match HEALTH.lock() {
+        Ok(lock) => lock,
+        Err(_) => return,
+    };
+    let now = clock::now_millis();
+    match health.get_mut(&root.name) {
+        Some(health) => {
+            if health.state != state {
+                health.state = state;
+                health.since = now;
+            }
+            if error.is_some() {
+                health.last_error = error;
+            }
+        }
+        None => {
+            health.insert(
+                root.name.clone(),
+                WatcherHealth {
+                    state,
+                    since: now,
+                    restarts: 0,
+                    last_error: error,
+                    last_reconciled_at: now,
+                },
+            );
+        }
+    }
+}
@@ -0,0 +1,329 @@
+use crate::listen_together::{Action, Song, Sync as GroupSync};
+use crate::presentation::songs_api::utils::clock;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+const SESSION_TIMEOUT_MS: i64 = 60 * 60 * 1000;
+const RESTART_THRESHOLD_MS: u64 = 3000;
+
+#[derive(Debug)]
+struct GroupSession {
+    host: String,
+    queue: Vec<Song>,
+    index: usize,
+    playing: bool,
+    // The playback clock runs from this position and time while playing
+    anchor_position_ms: u64,
+    anchor_at: i64,
+    last_activity: i64,
+    updates: broadcast::Sender<GroupSync>,
+}
+
+impl GroupSession {
+    fn position_at(&self, now: i64) -> u64 {
+        if self.playing {
+            self.anchor_position_ms + (now - self.anchor_at).max(0) as u64
+        } else {
+            self.anchor_position_ms
+        }
+    }
+
+    fn anchor(&mut self, position_ms: u64, now: i64) {
+        self.anchor_position_ms = position_ms;
+        self.anchor_at = now;
+    }
+
+    fn duration(&self) -> Option<u64> {
+        let song = self.queue.get(self.index)?;
+        Some(song.duration_ms).filter(|duration_ms| *duration_ms > 0)
+    }
+
+    fn settle(&mut self, now: i64) {
+        while self.playing {
+            let duration_ms = match self.duration() {
+                Some(duration_ms) if self.position_at(now) >= duration_ms => duration_ms,
+                _ => return,
+            };
+            let remaining_ms = duration_ms.saturating_sub(self.anchor_position_ms);
+            let ended_at = self.anchor_at + remaining_ms as i64;
+            if self.index + 1 < self.queue.len() {
+                self.index += 1;
+                self.anchor(0, ended_at);
+            } else {
+                self.anchor(duration_ms, ended_at);
+                self.playing = false;
+            }
+            self.last_activity = self.last_activity.max(ended_at);
+        }
+    }
+
+    fn is_abandoned(&self, now: i64) -> bool {
+        now - self.last_activity >= SESSION_TIMEOUT_MS
+    }
+
+    fn sync(&self, session: &str, now: i64) -> GroupSync {
+        GroupSync {
+            session: session.to_string(),
+            host: self.host.clone(),
+            song: self.queue.get(self.index).cloned(),
+            position_ms: self.position_at(now),
+            playing: self.playing,
+            server_timestamp: now,
+            index: self.index as u32,
+            queue: self.queue.clone(),
+        }
+    }
+
+    fn apply(
+        &mut self,
+        action: Action,
+        position_ms: u64,
+        song: Option<Song>,
+        now: i64,
+    ) -> Result<(), String> {
+        match action {
+            Action::Play => {
+                if self.queue.is_empty() {
+                    return Err(String::from("Queue is empty"));
+                }
+                let position = Some(self.position_at(now))
+                    .filter(|position| Some(*position) != self.duration())
+                    .unwrap_or(0);
+                self.anchor(position, now);
+                self.playing = true;
+            }
+            Action::Pause => {
+                let position = self.position_at(now);
+                self.anchor(position, now);
+                self.playing = false;
+            }
+            Action::Seek => {
+                let position_ms = self.duration().map_or(position_ms, |d| position_ms.min(d));
+                self.anchor(position_ms, now);
+            }
+            Action::Next => {
+                if self.index + 1 >= self.queue.len() {
+                    return Err(String::from("Already at the end of the queue"));
+                }
+                self.index += 1;
+                self.anchor(0, now);
+            }
+            Action::Previous => {
+                if self.position_at(now) < RESTART_THRESHOLD_MS && self.index > 0 {
+                    self.index -= 1;
+                }
+                self.anchor(0, now);
+            }
+            Action::Enqueue => {
+                let song = song.ok_or("Song is missing")?;
+                if self.queue.is_empty() {
+                    self.index = 0;
+                    self.anchor(0, now);
+                }
+                self.queue.push(song);
+            }
+        }
+        self.last_activity = now;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GroupSessions {
+    sessions: Arc<Mutex<HashMap<String, GroupSession>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl GroupSessions {
+    pub fn create(&self, host: &str) -> Result<GroupSync, String> {
+        let mut sessions = self.sessions.lock().map_err(|_| "Sessions poisoned")?;
+        let now = clock::now_millis();
+        settle(&mut sessions, now);
+
+        let id = format!(
+            "{:x}{:04x}",
+            now,
+            self.next_id.fetch_add(1, Ordering::Relaxed)
+        );
+        let group_session = GroupSession {
+            host: host.to_string(),
+            queue: Vec::new(),
+            index: 0,
+            playing: false,
+            anchor_position_ms: 0,
+            anchor_at: now,
+            last_activity: now,
+            updates: broadcast::channel(16).0,
+        };
+        let sync = group_session.sync(&id, now);
+        sessions.insert(id, group_session);
+        Ok(sync)
+    }
+
+    pub fn apply(
+        &self,
+        session: &str,
+        user: &str,
+        action: Action,
+        position_ms: u64,
+        song: Option<Song>,
+    ) -> Result<GroupSync, String> {
+        let mut sessions = self.sessions.lock().map_err(|_| "Sessions poisoned")?;
+        let now = clock::now_millis();
+        settle(&mut sessions, now);
+        let group_session = sessions.get_mut(session).ok_or("Session not found")?;
+        if group_session.host != user {
+            return Err(String::from("Only the host can control the session"));
+        }
+        group_session.apply(action, position_ms, song, now)?;
+
+        let sync = group_session.sync(session, now);
+        // Nobody listening yet is not an error
+        let _ = group_session.updates.send(sync.clone());
+        Ok(sync)
+    }
+
+    pub fn subscribe(
+        &self,
+        session: &str,
+    ) -> Result<(GroupSync, broadcast::Receiver<GroupSync>), String> {
+        let mut sessions = self.sessions.lock().map_err(|_| "Sessions poisoned")?;
+        let now = clock::now_millis();
+        settle(&mut sessions, now);
+        let group_session = sessions.get(session).ok_or("Session not found")?;
+        Ok((
+            group_session.sync(session, now),
+            group_session.updates.subscribe(),
+        ))
+    }
+
+    pub fn current(&self, session: &str) -> Option<GroupSync> {
+        let mut sessions = self.sessions.lock().ok()?;
+        let now = clock::now_millis();
+        settle(&mut sessions, now);
+        let group_session = sessions.get(session)?;
+        Some(group_session.sync(session, now))
+    }
+
+    pub fn close(&self, session: &str, user: &str) -> Result<(), String> {
+        let mut sessions = self.sessions.lock().map_err(|_| "Sessions poisoned")?;
+        settle(&mut sessions, clock::now_millis());
+        let group_session = sessions.get(session).ok_or("Session not found")?;
+        if group_session.host != user {
+            return Err(String::from("Only the host can close the session"));
+        }
+        sessions.remove(session);
+        Ok(())
+    }
+}
+
+// Runs on every access, guests joined to a session access it regularly
+fn settle(sessions: &mut HashMap<String, GroupSession>, now: i64) {
+    for group_session in sessions.values_mut() {
+        group_session.settle(now);
+    }
+    sessions.retain(|_, group_session| !group_session.is_abandoned(now));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(name: &str, duration_ms: u64) -> Song {
+        Song {
+            name: name.to_string(),
+            artist: String::from("Artist"),
+            duration_ms,
+        }
+    }
+
+    fn playing(queue: Vec<Song>) -> GroupSession {
+        let mut group_session = GroupSession {
+            host: String::from("host"),
+            queue,
+            index: 0,
+            playing: false,
+            anchor_position_ms: 0,
+            anchor_at: 0,
+            last_activity: 0,
+            updates: broadcast::channel(1).0,
+        };
+        group_session.apply(Action::Play, 0, None, 0).unwrap();
+        group_session
+    }
+
+    fn state(group_session: &mut GroupSession, now: i64) -> (usize, u64, bool) {
+        group_session.settle(now);
+        let sync = group_session.sync("session", now);
+        (sync.index as usize, sync.position_ms, sync.playing)
+    }
+
+    #[test]
+    fn clock_moves_on_to_the_next_songs() {
+        let mut group_session = playing(vec![
+            song("One", 1000),
+            song("Two", 2000),
+            song("Three", 500),
+        ]);
+        assert_eq!(state(&mut group_session, 400), (0, 400, true));
+        assert_eq!(state(&mut group_session, 1500), (1, 500, true));
+        let mut group_session = playing(vec![
+            song("One", 1000),
+            song("Two", 2000),
+            song("Three", 500),
+        ]);
+        assert_eq!(state(&mut group_session, 3200), (2, 200, true));
+        assert_eq!(group_session.last_activity, 3000);
+    }
+
+    #[test]
+    fn clock_stops_at_the_end_of_the_queue() {
+        let mut group_session = playing(vec![song("One", 1000), song("Two", 2000)]);
+        assert_eq!(state(&mut group_session, 10_000), (1, 2000, false));
+        assert_eq!(state(&mut group_session, 20_000), (1, 2000, false));
+        assert_eq!(group_session.last_activity, 3000);
+
+        group_session.apply(Action::Play, 0, None, 20_000).unwrap();
+        assert_eq!(state(&mut group_session, 20_500), (1, 500, true));
+    }
+
+    #[test]
+    fn clock_runs_on_without_a_duration() {
+        let mut group_session = playing(vec![song("One", 0), song("Two", 1000)]);
+        assert_eq!(state(&mut group_session, 10_000), (0, 10_000, true));
+    }
+
+    #[test]
+    fn seeks_stay_within_the_song() {
+        let mut group_session = playing(vec![song("One", 1000), song("Two", 0)]);
+        group_session.apply(Action::Pause, 0, None, 100).unwrap();
+        group_session.apply(Action::Seek, 5000, None, 200).unwrap();
+        assert_eq!(state(&mut group_session, 300), (0, 1000, false));
+
+        group_session.apply(Action::Next, 0, None, 400).unwrap();
+        group_session.apply(Action::Seek, 5000, None, 500).unwrap();
+        assert_eq!(state(&mut group_session, 600), (1, 5000, false));
+    }
+
+    #[test]
+    fn abandoned_sessions_are_dropped() {
+        let mut sessions = HashMap::new();
+        let mut paused = playing(vec![song("One", 0)]);
+        paused.apply(Action::Pause, 0, None, 1000).unwrap();
+        sessions.insert(String::from("paused"), paused);
+        let long_queue = (0..4).map(|_| song("Long", 40 * 60 * 1000)).collect();
+        sessions.insert(String::from("playing"), playing(long_queue));
+
+        settle(&mut sessions, 1000 + SESSION_TIMEOUT_MS - 1);
+        assert_eq!(sessions.len(), 2);
+        settle(&mut sessions, 1000 + SESSION_TIMEOUT_MS);
+        assert!(sessions.contains_key("playing"));
+        assert!(!sessions.contains_key("paused"));
+
+        let ended_at = 4 * 40 * 60 * 1000;
+        settle(&mut sessions, ended_at + SESSION_TIMEOUT_MS);
+        assert!(sessions.is_empty());
+    }
+}
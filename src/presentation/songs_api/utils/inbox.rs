@@ -0,0 +1,227 @@
+use crate::config::CONFIG;
+use crate::core::data::entity::song::Song;
+use crate::core::repository::songs_repository::SONGS_REPOSITORY;
+use crate::core::utils::audio_format::{
+    detect_audio_format, matches_extension, AUDIO_HEADER_LENGTH,
+};
+use crate::core::utils::audio_tags;
+use crate::core::utils::filename_templates::match_templates;
+use crate::presentation::songs_api::utils::clock;
+use crate::presentation::songs_api::utils::library_changes;
+use hotwatch::blocking::{Flow, Hotwatch};
+use hotwatch::notify::DebouncedEvent;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+const SETTLE_DELAY: Duration = Duration::from_secs(2);
+
+lazy_static! {
+    // Watcher events carry the canonical form of the folder
+    static ref INBOX_FOLDER: PathBuf = Path::new(&CONFIG.inbox_folder_path)
+        .canonicalize()
+        .unwrap_or_else(|_| PathBuf::from(&CONFIG.inbox_folder_path));
+}
+
+pub fn start() -> bool {
+    for folder in [&CONFIG.inbox_folder_path, &CONFIG.rejected_folder_path] {
+        if let Err(e) = fs::create_dir_all(folder) {
+            eprintln!("Could not create inbox folder:\n{}", e);
+            return false;
+        }
+    }
+    process_folder(&INBOX_FOLDER);
+
+    thread::spawn(|| {
+        let mut hotwatch = Hotwatch::new().expect("Could not start inbox");
+        hotwatch
+            .watch(INBOX_FOLDER.as_path(), |event| {
+                match event {
+                    DebouncedEvent::Create(path) | DebouncedEvent::Write(path) => process(&path),
+                    _ => (),
+                };
+                Flow::Continue
+            })
+            .expect("Could not start inbox");
+        hotwatch.run();
+    });
+
+    true
+}
+
+fn process(path: &Path) {
+    if path.is_dir() {
+        process_folder(path);
+    } else if path.is_file() {
+        process_file(path);
+    }
+}
+
+fn process_folder(folder: &Path) {
+    let read_dir = match folder.read_dir() {
+        Ok(iterator) => iterator,
+        Err(_) => return,
+    };
+    for entry in read_dir.flatten() {
+        process(&entry.path());
+    }
+    remove_empty_folders(folder);
+}
+
+fn process_file(path: &Path) {
+    let file_name = path.file_name().and_then(OsStr::to_str).unwrap_or_default();
+    // Hidden files are left alone, some clients download into them first
+    if file_name.starts_with('.') {
+        return;
+    }
+    wait_until_settled(path);
+
+    match ingest(path) {
+        Ok(song) => println!("Added song from inbox: {}-{}", song.name, song.artist),
+        Err(reason) => reject(path, &reason),
+    }
+    if let Some(folder) = path.parent() {
+        remove_empty_folders(folder);
+    }
+}
+
+fn ingest(path: &Path) -> Result<Song, String> {
+    let extension = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+    if !CONFIG.audio_formats.contains(&extension) {
+        return Err(format!(
+            "Format not accepted, expected one of: {}",
+            CONFIG.audio_formats.join(", ")
+        ));
+    }
+    let mut header = vec![0; AUDIO_HEADER_LENGTH];
+    let header_length = File::open(path)
+        .and_then(|mut file| file.read(&mut header))
+        .map_err(|e| format!("File could not be read: {}", e))?;
+    match detect_audio_format(&header[..header_length]) {
+        Some(format) if matches_extension(format, &extension) => {}
+        _ => return Err(format!("File is not a valid {}", extension)),
+    }
+
+    let mut tags = audio_tags::read_tags(path).unwrap_or_default();
+    if tags.title.is_none() || tags.artist.is_none() {
+        let relative_path = path.strip_prefix(INBOX_FOLDER.as_path()).unwrap_or(path);
+        if let Some((_, mut parsed)) = match_templates(&CONFIG.filename_templates, relative_path) {
+            parsed.apply(&tags);
+            tags = parsed;
+        }
+    }
+    let title = non_empty(&tags.title).ok_or("No title in the tags or the file name")?;
+    let artist = non_empty(&tags.artist).ok_or("No artist in the tags or the file name")?;
+    if SONGS_REPOSITORY.find_song(&title, &artist).is_some() {
+        return Err(format!("Song already exists: {}-{}", title, artist));
+    }
+
+    let relative_path = CONFIG.library_layout.format(&tags, &extension);
+    let file_path = relative_path
+        .to_str()
+        .map(String::from)
+        .ok_or("Layout produced an illegal path")?;
+    let library_path = Path::new(&CONFIG.files_folder_path).join(&relative_path);
+    if library_path.exists() {
+        return Err(format!("A file already exists at {}", file_path));
+    }
+    if let Some(folder) = library_path.parent() {
+        fs::create_dir_all(folder).map_err(|e| format!("Could not create folder: {}", e))?;
+    }
+
+    library_changes::record(&library_path);
+    move_file(path, &library_path).map_err(|e| format!("Could not move into library: {}", e))?;
+
+    let mut song = Song::new(title, artist, None, file_path);
+    song.parsed = Some(tags);
+    if !SONGS_REPOSITORY.insert_song(song.clone()) {
+        if let Err(e) = move_file(&library_path, path) {
+            eprintln!("Could not return song to the inbox:\n{}", e);
+        }
+        return Err(String::from("Song could not be registered"));
+    }
+    Ok(song)
+}
+
+fn reject(path: &Path, reason: &str) {
+    let relative_path = path
+        .strip_prefix(INBOX_FOLDER.as_path())
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|_| PathBuf::from(path.file_name().unwrap_or_default()));
+    let mut rejected_path = Path::new(&CONFIG.rejected_folder_path).join(&relative_path);
+    if rejected_path.exists() {
+        let file_name = rejected_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let file_name = format!("{}-{}", clock::now_millis(), file_name);
+        rejected_path.set_file_name(file_name);
+    }
+
+    eprintln!(
+        "Rejected {} from inbox: {}",
+        relative_path.display(),
+        reason
+    );
+
+    let result = rejected_path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| move_file(path, &rejected_path))
+        .and_then(|_| {
+            let mut reason_path = rejected_path.into_os_string();
+            reason_path.push(".reason.txt");
+            fs::write(reason_path, format!("{}\n", reason))
+        });
+    if let Err(e) = result {
+        eprintln!("Could not move rejected file:\n{}", e);
+    }
+}
+
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    fs::remove_file(from)
+}
+
+fn remove_empty_folders(folder: &Path) {
+    let mut folder = Some(folder);
+    while let Some(current) = folder {
+        if !current.starts_with(INBOX_FOLDER.as_path()) || current == INBOX_FOLDER.as_path() {
+            break;
+        }
+        if fs::remove_dir(current).is_err() {
+            break;
+        }
+        folder = current.parent();
+    }
+}
+
+pub fn wait_until_settled(path: &Path) {
+    while let Ok(age) = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map(|modified| modified.elapsed().unwrap_or_default())
+    {
+        if age >= SETTLE_DELAY {
+            break;
+        }
+        thread::sleep(SETTLE_DELAY - age);
+    }
+}
+
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(String::from)
+}
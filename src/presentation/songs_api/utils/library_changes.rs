@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Longer than the watcher debounce delay, so the delayed events still match
+const OWN_CHANGE_WINDOW: Duration = Duration::from_secs(10);
+
+lazy_static! {
+    static ref OWN_CHANGES: Mutex<HashMap<PathBuf, Instant>> = Mutex::new(HashMap::new());
+}
+
+pub fn record(path: &Path) {
+    let mut own_changes = match OWN_CHANGES.lock() {
+        Ok(lock) => lock,
+        Err(_) => return,
+    };
+    let now = Instant::now();
+    own_changes.retain(|_, recorded_at| now.duration_since(*recorded_at) < OWN_CHANGE_WINDOW);
+    own_changes.insert(normalize(path), now);
+}
+
+pub fn is_own_change(path: &Path) -> bool {
+    let own_changes = match OWN_CHANGES.lock() {
+        Ok(lock) => lock,
+        Err(_) => return false,
+    };
+    match own_changes.get(&normalize(path)) {
+        Some(recorded_at) => recorded_at.elapsed() < OWN_CHANGE_WINDOW,
+        None => false,
+    }
+}
+
+// The file itself may be gone already, so only its folder is canonicalized
+fn normalize(path: &Path) -> PathBuf {
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(file_name)) => match parent.canonicalize() {
+            Ok(parent) => parent.join(file_name),
+            Err(_) => path.to_path_buf(),
+        },
+        _ => path.to_path_buf(),
+    }
+}
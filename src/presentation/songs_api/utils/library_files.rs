@@ -0,0 +1,204 @@
+use crate::config::CONFIG;
+use crate::core::data::entity::song::Song;
+use crate::core::utils::storage;
+use crate::presentation::songs_api::utils::clock;
+use crate::presentation::songs_api::utils::library_changes;
+use crate::presentation::songs_api::utils::query_threads::query_songs;
+use crate::presentation::songs_api::utils::upload_library::is_valid_name_part;
+use std::ffi::OsStr;
+use std::io::{self, ErrorKind};
+use std::path::{Component, Path, PathBuf};
+use tokio::{fs, task};
+use tonic::Status;
+
+pub async fn delete_song(song: &Song) -> Result<(), Status> {
+    let song_path = library_file_path(&song.file_path).await?;
+    let file_name = song_path
+        .file_name()
+        .and_then(OsStr::to_str)
+        .unwrap_or_default();
+    let staged_path = Path::new(&CONFIG.uploads_folder_path).join(format!(
+        "{}-{}.deleting",
+        clock::now_millis(),
+        file_name
+    ));
+
+    library_changes::record(&song_path);
+    rename_file(&song_path, &staged_path).await?;
+
+    let deleted = Song::new(
+        song.name.clone(),
+        song.artist.clone(),
+        None,
+        song.file_path.clone(),
+    );
+    if !query_songs(move |songs| songs.delete_song(deleted)).await {
+        restore_file(&staged_path, &song_path).await;
+        return Err(Status::internal("Song could not be deleted"));
+    }
+
+    if let Err(e) = fs::remove_file(&staged_path).await {
+        eprintln!("Could not remove deleted song file:\n{}", e);
+    }
+    Ok(())
+}
+
+pub async fn rename_song(song: &Song, name: &str, artist: &str) -> Result<Song, Status> {
+    if !is_valid_name_part(name) || !is_valid_name_part(artist) {
+        return Err(Status::invalid_argument("Song name or artist is illegal"));
+    }
+    let existing = {
+        let (name, artist) = (name.to_owned(), artist.to_owned());
+        query_songs(move |songs| songs.find_song(&name, &artist)).await
+    };
+    if existing.is_some() {
+        return Err(Status::already_exists("Song already exists"));
+    }
+
+    let file_path = Path::new(&song.file_path);
+    let extension = file_path
+        .extension()
+        .and_then(OsStr::to_str)
+        .unwrap_or_default();
+    let new_file_path = file_path.with_file_name(format!("{} - {}.{}", artist, name, extension));
+
+    let renamed = Song::new(
+        name.to_string(),
+        artist.to_string(),
+        song.image_path.clone(),
+        path_to_string(&new_file_path).ok_or_else(illegal_path)?,
+    );
+    relocate(song, &renamed).await?;
+    Ok(renamed)
+}
+
+pub async fn move_song(song: &Song, folder: &str) -> Result<Song, Status> {
+    let folder = Path::new(folder);
+    if !folder
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(Status::invalid_argument(
+            "Folder must be relative to the files folder",
+        ));
+    }
+
+    let file_name = Path::new(&song.file_path)
+        .file_name()
+        .ok_or_else(|| Status::internal("Song file path is illegal"))?;
+    let moved = Song::new(
+        song.name.clone(),
+        song.artist.clone(),
+        song.image_path.clone(),
+        path_to_string(&folder.join(file_name)).ok_or_else(illegal_path)?,
+    );
+    if moved.file_path == song.file_path {
+        return Ok(moved);
+    }
+
+    let target_folder = full_path(&moved.file_path)
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let created = if storage::has_events(&target_folder) {
+        fs::create_dir_all(&target_folder).await
+    } else {
+        Ok(())
+    };
+    if let Err(e) = created {
+        eprintln!("Could not create folder:\n{}", e);
+        return Err(Status::internal("Folder could not be created"));
+    }
+
+    relocate(song, &moved).await?;
+    Ok(moved)
+}
+
+async fn relocate(song: &Song, relocated: &Song) -> Result<(), Status> {
+    let song_path = library_file_path(&song.file_path).await?;
+    let relocated_path = full_path(&relocated.file_path);
+    if storage::stat(&relocated_path).is_ok() {
+        return Err(Status::already_exists(
+            "A file with that name already exists",
+        ));
+    }
+
+    library_changes::record(&song_path);
+    library_changes::record(&relocated_path);
+    rename_file(&song_path, &relocated_path).await?;
+
+    let (name, artist, updated) = (song.name.clone(), song.artist.clone(), relocated.clone());
+    if !query_songs(move |songs| songs.update_song(&name, &artist, &updated)).await {
+        restore_file(&relocated_path, &song_path).await;
+        return Err(Status::internal("Song could not be updated"));
+    }
+    Ok(())
+}
+
+async fn rename_file(from: &Path, to: &Path) -> Result<(), Status> {
+    match move_file(from, to).await {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            Err(Status::not_found("Song file could not be found"))
+        }
+        Err(e) => {
+            eprintln!("Could not move song file:\n{}", e);
+            Err(Status::internal("Song file could not be moved"))
+        }
+    }
+}
+
+async fn restore_file(from: &Path, to: &Path) {
+    library_changes::record(to);
+    if let Err(e) = move_file(from, to).await {
+        eprintln!(
+            "Could not restore {} to {}:\n{}",
+            from.display(),
+            to.display(),
+            e
+        );
+    }
+}
+
+async fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    let from = from.to_path_buf();
+    let to = to.to_path_buf();
+    task::spawn_blocking(move || storage::rename(&from, &to))
+        .await
+        .unwrap_or_else(|e| Err(io::Error::other(e)))
+}
+
+pub fn full_path(file_path: &str) -> PathBuf {
+    CONFIG.library_roots.full_path(file_path)
+}
+
+pub async fn song_file_path(file_path: &str) -> Result<PathBuf, Status> {
+    CONFIG
+        .library_roots
+        .served_path(file_path, CONFIG.symlink_policy)
+        .map_err(|e| {
+            eprintln!("{}", e);
+            path_status(&e)
+        })
+}
+
+pub async fn library_file_path(file_path: &str) -> Result<PathBuf, Status> {
+    song_file_path(file_path).await?;
+    Ok(full_path(file_path))
+}
+
+pub fn path_status(e: &io::Error) -> Status {
+    match e.kind() {
+        ErrorKind::PermissionDenied => Status::permission_denied("Path is outside of the library"),
+        ErrorKind::NotFound => Status::not_found("Song file could not be found"),
+        _ => Status::internal("Song file could not be read"),
+    }
+}
+
+fn path_to_string(path: &Path) -> Option<String> {
+    path.to_str().map(String::from)
+}
+
+fn illegal_path() -> Status {
+    Status::invalid_argument("Path is not valid UTF-8")
+}
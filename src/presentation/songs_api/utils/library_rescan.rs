@@ -0,0 +1,81 @@
+use crate::core::repository::songs_repository::SONGS_REPOSITORY;
+use crate::core::utils::scan_progress::ScanProgress;
+use crate::presentation::songs_api::utils::clock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::Instant;
+
+lazy_static! {
+    static ref RUNNING: Mutex<Option<Arc<RunningScan>>> = Mutex::new(None);
+}
+
+pub struct RunningScan {
+    pub force: bool,
+    pub started_at: i64,
+    pub started: Instant,
+    pub progress: ScanProgress,
+    finished: AtomicBool,
+    succeeded: AtomicBool,
+}
+
+impl RunningScan {
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Acquire)
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.succeeded.load(Ordering::Acquire)
+    }
+}
+
+pub fn start(force: bool) -> Arc<RunningScan> {
+    let mut running = RUNNING.lock().expect("Running scan poisoned");
+    if let Some(scan) = running.as_ref() {
+        return Arc::clone(scan);
+    }
+
+    let scan = Arc::new(RunningScan {
+        force,
+        started_at: clock::now_millis(),
+        started: Instant::now(),
+        progress: ScanProgress::default(),
+        finished: AtomicBool::new(false),
+        succeeded: AtomicBool::new(false),
+    });
+    *running = Some(Arc::clone(&scan));
+    drop(running);
+
+    let scan_end = ScanEnd(Arc::clone(&scan));
+    let spawned = thread::Builder::new()
+        .name(String::from("library-scan"))
+        .spawn(move || {
+            let scan_end = scan_end;
+            let worker_scan = &scan_end.0;
+            let succeeded = SONGS_REPOSITORY.rescan(
+                worker_scan.force,
+                worker_scan.started_at,
+                &worker_scan.progress,
+            );
+            let counts = worker_scan.progress.counts();
+            println!(
+                "Library scan finished: {} added, {} updated, {} removed, {} failed",
+                counts.added, counts.updated, counts.removed, counts.failed
+            );
+            worker_scan.succeeded.store(succeeded, Ordering::Release);
+        });
+    if let Err(e) = spawned {
+        eprintln!("Could not start library scan:\n{}", e);
+    }
+
+    scan
+}
+
+struct ScanEnd(Arc<RunningScan>);
+
+impl Drop for ScanEnd {
+    fn drop(&mut self) {
+        *RUNNING.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        self.0.finished.store(true, Ordering::Release);
+    }
+}
@@ -1,2 +1,16 @@
+pub mod admin_token;
+pub mod archive_import;
 pub mod async_file_reader;
 pub mod auto_updater;
+pub mod clock;
+pub mod group_sessions;
+pub mod inbox;
+pub mod library_changes;
+pub mod library_files;
+pub mod library_rescan;
+pub mod query_threads;
+pub mod remote_sessions;
+pub mod tag_editor;
+pub mod trash_cleaner;
+pub mod upload_cleaner;
+pub mod upload_library;
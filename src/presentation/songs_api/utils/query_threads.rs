@@ -0,0 +1,15 @@
+use crate::core::repository::songs_repository::{SongsRepository, SONGS_REPOSITORY};
+use std::panic;
+use tokio::task;
+
+pub async fn query_songs<T, F>(query: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce(&SongsRepository) -> T + Send + 'static,
+{
+    match task::spawn_blocking(move || query(&SONGS_REPOSITORY)).await {
+        Ok(output) => output,
+        Err(e) if e.is_panic() => panic::resume_unwind(e.into_panic()),
+        Err(e) => panic!("Database query was cancelled: {}", e),
+    }
+}
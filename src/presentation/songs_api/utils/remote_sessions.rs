@@ -0,0 +1,195 @@
+use crate::remote_control::{server_message::Message, Membership, ServerMessage};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::Sender;
+use tonic::Status;
+
+pub type MemberSender = Sender<Result<ServerMessage, Status>>;
+
+#[derive(Debug)]
+struct RemoteSession {
+    user: String,
+    owner: String,
+    // Kept in joining order, the oldest member inherits the ownership
+    members: Vec<(String, MemberSender)>,
+}
+
+impl RemoteSession {
+    fn senders_except(&self, device: &str) -> Vec<MemberSender> {
+        self.members
+            .iter()
+            .filter(|(member, _)| member != device)
+            .map(|(_, sender)| sender.clone())
+            .collect()
+    }
+
+    fn contains(&self, device: &str) -> bool {
+        self.members.iter().any(|(member, _)| member == device)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RemoteSessions {
+    sessions: Arc<Mutex<HashMap<String, RemoteSession>>>,
+}
+
+impl RemoteSessions {
+    pub fn join(
+        &self,
+        session: &str,
+        user: &str,
+        device: &str,
+        sender: MemberSender,
+    ) -> Result<(), String> {
+        let mut sessions = self.sessions.lock().map_err(|_| "Sessions poisoned")?;
+        let remote_session = sessions
+            .entry(session.to_string())
+            .or_insert_with(|| RemoteSession {
+                user: user.to_string(),
+                owner: device.to_string(),
+                members: Vec::new(),
+            });
+        if remote_session.user != user {
+            return Err(format!("Session {} belongs to another user", session));
+        }
+        match remote_session
+            .members
+            .iter_mut()
+            .find(|(member, _)| member == device)
+        {
+            Some((_, previous)) => *previous = sender,
+            None => remote_session.members.push((device.to_string(), sender)),
+        }
+        Ok(())
+    }
+
+    // Only while the device is still joined through this connection
+    pub fn leave(&self, session: &str, sender: &MemberSender) {
+        self.remove_member(session, sender);
+    }
+
+    pub fn remove_member(&self, session: &str, sender: &MemberSender) -> Option<String> {
+        let mut sessions = self.sessions.lock().ok()?;
+        let remote_session = sessions.get_mut(session)?;
+        let index = remote_session
+            .members
+            .iter()
+            .position(|(_, member_sender)| member_sender.same_channel(sender))?;
+        let (device, _) = remote_session.members.remove(index);
+        match remote_session.members.first() {
+            None => {
+                sessions.remove(session);
+            }
+            Some((oldest, _)) => {
+                if remote_session.owner == device {
+                    remote_session.owner = oldest.clone();
+                }
+            }
+        }
+        Some(device)
+    }
+
+    pub fn is_member(&self, session: &str, sender: &MemberSender) -> bool {
+        let sessions = match self.sessions.lock() {
+            Ok(lock) => lock,
+            Err(_) => return false,
+        };
+        match sessions.get(session) {
+            Some(remote_session) => remote_session
+                .members
+                .iter()
+                .any(|(_, member_sender)| member_sender.same_channel(sender)),
+            None => false,
+        }
+    }
+
+    pub fn transfer(&self, session: &str, from: &str, to: &str) -> Result<(), String> {
+        let mut sessions = self.sessions.lock().map_err(|_| "Sessions poisoned")?;
+        let remote_session = sessions.get_mut(session).ok_or("Session not found")?;
+        if remote_session.owner != from {
+            return Err(String::from("Only the owner can transfer the session"));
+        }
+        if !remote_session.contains(to) {
+            return Err(format!("Device {} is not in the session", to));
+        }
+        remote_session.owner = to.to_string();
+        Ok(())
+    }
+
+    pub fn command_recipient(
+        &self,
+        session: &str,
+        from: &str,
+        target: &str,
+    ) -> Result<MemberSender, String> {
+        let sessions = self.sessions.lock().map_err(|_| "Sessions poisoned")?;
+        let remote_session = sessions.get(session).ok_or("Session not found")?;
+        let target = if target.is_empty() {
+            &remote_session.owner
+        } else {
+            target
+        };
+        if target == from {
+            return Err(String::from("Cannot send a command to the same device"));
+        }
+        remote_session
+            .members
+            .iter()
+            .find(|(member, _)| member == target)
+            .map(|(_, sender)| sender.clone())
+            .ok_or_else(|| format!("Device {} is not in the session", target))
+    }
+
+    pub fn state_recipients(&self, session: &str, from: &str) -> Result<Vec<MemberSender>, String> {
+        let sessions = self.sessions.lock().map_err(|_| "Sessions poisoned")?;
+        let remote_session = sessions.get(session).ok_or("Session not found")?;
+        if remote_session.owner != from {
+            return Err(String::from("Only the owner can report the player state"));
+        }
+        Ok(remote_session.senders_except(from))
+    }
+
+    pub fn membership(&self, session: &str) -> Option<(Message, Vec<MemberSender>)> {
+        let sessions = self.sessions.lock().ok()?;
+        let remote_session = sessions.get(session)?;
+        let membership = Membership {
+            session: session.to_string(),
+            owner: remote_session.owner.clone(),
+            devices: remote_session
+                .members
+                .iter()
+                .map(|(member, _)| member.clone())
+                .collect(),
+        };
+        Some((
+            Message::Membership(membership),
+            remote_session.senders_except(""),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn reconnecting_devices_replace_their_previous_connection() {
+        let sessions = RemoteSessions::default();
+        let (previous, _previous_rx) = mpsc::channel(1);
+        let (current, _current_rx) = mpsc::channel(1);
+        sessions
+            .join("living-room", "user", "phone", previous.clone())
+            .unwrap();
+        sessions
+            .join("living-room", "user", "phone", current.clone())
+            .unwrap();
+        assert!(!sessions.is_member("living-room", &previous));
+
+        // The previous connection ending does not take the device out
+        sessions.leave("living-room", &previous);
+        assert!(sessions.is_member("living-room", &current));
+        let (_, recipients) = sessions.membership("living-room").unwrap();
+        assert_eq!(recipients.len(), 1);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::config::CONFIG;
+use crate::core::data::entity::song::Song;
+use crate::core::utils::audio_tags::{self, AudioTags, Cover};
+use crate::presentation::songs_api::utils::clock;
+use crate::presentation::songs_api::utils::library_changes;
+use crate::presentation::songs_api::utils::library_files::{
+    library_file_path, rename_song, song_file_path,
+};
+use crate::presentation::songs_api::utils::query_threads::query_songs;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use tokio::fs;
+use tokio::task;
+use tonic::Status;
+
+pub enum TagChange {
+    Edit,
+    Undo(i64),
+}
+
+pub async fn read_song_tags(song: &Song) -> Result<AudioTags, Status> {
+    let path = song_file_path(&song.file_path).await?;
+    let mut tags =
+        task::spawn_blocking(move || audio_tags::read_tags(&path).map_err(|e| e.to_string()))
+            .await
+            .map_err(|_| Status::internal("Tags could not be read"))?
+            .map_err(Status::failed_precondition)?;
+
+    tags.title.get_or_insert_with(|| song.name.clone());
+    tags.artist.get_or_insert_with(|| song.artist.clone());
+    Ok(tags)
+}
+
+pub async fn apply_tags(
+    song: &Song,
+    previous: &AudioTags,
+    tags: &AudioTags,
+    change: TagChange,
+) -> Result<Song, Status> {
+    let name = tags.title.as_deref().unwrap_or(&song.name);
+    let artist = tags.artist.as_deref().unwrap_or(&song.artist);
+
+    // Songs are only renamed by edits of their title or artist, so overridden names are kept
+    let renaming = tags.title != previous.title || tags.artist != previous.artist;
+    let mut tagged = if renaming && (name != song.name || artist != song.artist) {
+        rename_song(song, name, artist).await?
+    } else {
+        Song::new(
+            song.name.clone(),
+            song.artist.clone(),
+            song.image_path.clone(),
+            song.file_path.clone(),
+        )
+    };
+
+    let image_path = match save_cover(&tags.cover).await {
+        Ok(image_path) => image_path,
+        Err(status) => {
+            revert(song, &tagged, None).await;
+            return Err(status);
+        }
+    };
+    tagged.image_path = image_path;
+
+    if let Err(e) = write_file_tags(&tagged, tags).await {
+        revert(song, &tagged, None).await;
+        return Err(Status::failed_precondition(e));
+    }
+
+    let saved = {
+        let (song, tags, previous) = (tagged.clone(), tags.clone(), previous.clone());
+        let edited_at = clock::now_millis();
+        query_songs(move |songs| match change {
+            TagChange::Edit => songs.save_song_tags(&song, &tags, &previous, edited_at),
+            TagChange::Undo(edit_id) => songs.undo_song_tags(edit_id, &song, &tags),
+        })
+        .await
+    };
+    if !saved {
+        revert(song, &tagged, Some(previous)).await;
+        return Err(Status::internal("Tags could not be saved"));
+    }
+    Ok(tagged)
+}
+
+async fn write_file_tags(song: &Song, tags: &AudioTags) -> Result<(), String> {
+    let path = library_file_path(&song.file_path)
+        .await
+        .map_err(|status| status.message().to_string())?;
+    library_changes::record(&path);
+    let tags = tags.clone();
+    task::spawn_blocking(move || audio_tags::write_tags(&path, &tags).map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+async fn revert(song: &Song, tagged: &Song, previous: Option<&AudioTags>) {
+    if let Some(previous) = previous {
+        if let Err(e) = write_file_tags(tagged, previous).await {
+            eprintln!("Could not restore the tags of {}:\n{}", tagged.file_path, e);
+        }
+    }
+    if tagged.name != song.name || tagged.artist != song.artist {
+        if let Err(status) = rename_song(tagged, &song.name, &song.artist).await {
+            eprintln!(
+                "Could not restore the name of {}:\n{}",
+                tagged.file_path,
+                status.message()
+            );
+        }
+    }
+}
+
+async fn save_cover(cover: &Option<Cover>) -> Result<Option<String>, Status> {
+    let cover = match cover {
+        Some(cover) => cover,
+        None => return Ok(None),
+    };
+    let extension = if cover.mime_type == "image/png" {
+        "png"
+    } else {
+        "jpg"
+    };
+    let file_name = format!("{}.{}", hex::encode(Sha256::digest(&cover.data)), extension);
+    let path = Path::new(&CONFIG.covers_folder_path).join(file_name);
+
+    if !path.exists() {
+        if let Err(e) = fs::write(&path, &cover.data).await {
+            eprintln!("Could not save cover:\n{}", e);
+            return Err(Status::internal("Cover could not be saved"));
+        }
+    }
+    path.to_str()
+        .map(|path| Some(path.to_string()))
+        .ok_or_else(|| Status::internal("Cover path is not valid UTF-8"))
+}
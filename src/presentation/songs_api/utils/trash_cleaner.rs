@@ -0,0 +1,21 @@
+use crate::config::CONFIG;
+use crate::presentation::songs_api::utils::clock;
+use crate::presentation::songs_api::utils::query_threads::query_songs;
+use std::time::Duration;
+use tokio::time::interval;
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub fn start() {
+    tokio::spawn(async {
+        let mut ticker = interval(PURGE_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let missing_before = clock::now_millis() - CONFIG.missing_grace_ms;
+            let purged = query_songs(move |songs| songs.purge_missing_songs(missing_before)).await;
+            if purged > 0 {
+                println!("Purged {} missing songs", purged);
+            }
+        }
+    });
+}
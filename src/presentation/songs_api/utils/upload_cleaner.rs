@@ -0,0 +1,68 @@
+use crate::config::CONFIG;
+use crate::presentation::songs_api::utils::clock;
+use crate::presentation::songs_api::utils::query_threads::query_songs;
+use crate::presentation::songs_api::utils::upload_library::remove_session;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tokio::fs;
+use tokio::time::interval;
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub fn start() {
+    tokio::spawn(async {
+        let mut ticker = interval(CLEANUP_INTERVAL);
+        loop {
+            ticker.tick().await;
+            clean_up().await;
+        }
+    });
+}
+
+async fn clean_up() {
+    let now = clock::now_millis();
+    let expired = query_songs(move |songs| songs.find_expired_upload_sessions(now)).await;
+    for id in expired {
+        if remove_session(&id).await {
+            println!("Removed expired upload session {}", id);
+        }
+    }
+
+    // Single request uploads and deletions interrupted by a crash leave their files behind
+    let mut read_dir = match fs::read_dir(&CONFIG.uploads_folder_path).await {
+        Ok(read_dir) => read_dir,
+        Err(_) => return,
+    };
+    let expiry = Duration::from_millis(CONFIG.upload_expiry_ms as u64);
+    while let Ok(Some(entry)) = read_dir.next_entry().await {
+        let path = entry.path();
+        let leftover = matches!(path.extension(), Some(ext) if ext == "part" || ext == "deleting");
+        // So do archive imports, which extract into folders
+        let leftover_folder = matches!(path.extension(), Some(ext) if ext == "extracting");
+        if !(leftover || leftover_folder) || !is_older_than(&path, expiry).await {
+            continue;
+        }
+        let result = if leftover_folder {
+            fs::remove_dir_all(&path).await
+        } else {
+            fs::remove_file(&path).await
+        };
+        if let Err(e) = result {
+            eprintln!("Could not remove leftover file:\n{}", e);
+        }
+    }
+}
+
+async fn is_older_than(path: &Path, age: Duration) -> bool {
+    let modified = match fs::metadata(path)
+        .await
+        .and_then(|metadata| metadata.modified())
+    {
+        Ok(modified) => modified,
+        Err(_) => return false,
+    };
+    SystemTime::now()
+        .duration_since(modified)
+        .map(|elapsed| elapsed > age)
+        .unwrap_or(false)
+}
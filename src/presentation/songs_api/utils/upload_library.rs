@@ -0,0 +1,133 @@
+use crate::config::CONFIG;
+use crate::core::utils::audio_format::{
+    detect_audio_format, matches_extension, AUDIO_HEADER_LENGTH,
+};
+use crate::presentation::songs_api::utils::library_changes;
+use crate::presentation::songs_api::utils::query_threads::query_songs;
+use crate::upload::Metadata;
+use sha2::{Digest, Sha256};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use tokio::fs::{self, File};
+use tokio::io::AsyncReadExt;
+use tonic::Status;
+
+pub fn validate_metadata(metadata: &Metadata) -> Result<(), String> {
+    if !is_valid_name_part(&metadata.name) || !is_valid_name_part(&metadata.artist) {
+        return Err(String::from("Song name or artist is illegal"));
+    }
+    if !CONFIG.audio_formats.contains(&metadata.format) {
+        return Err(format!(
+            "Format not accepted, expected one of: {}",
+            CONFIG.audio_formats.join(", ")
+        ));
+    }
+    if metadata.size == 0 || metadata.size > CONFIG.max_upload_size {
+        return Err(format!(
+            "Size must be between 1 and {} bytes",
+            CONFIG.max_upload_size
+        ));
+    }
+    if metadata.sha256.len() != 64 || hex::decode(&metadata.sha256).is_err() {
+        return Err(String::from("Hash must be a hex encoded SHA-256"));
+    }
+    Ok(())
+}
+
+pub fn is_valid_name_part(part: &str) -> bool {
+    !part.trim().is_empty()
+        && !part.starts_with('.')
+        && !part.contains(" - ")
+        && !part
+            .chars()
+            .any(|c| c == '/' || c == '\\' || c.is_control())
+}
+
+pub fn library_path(metadata: &Metadata) -> PathBuf {
+    let file_name = format!(
+        "{} - {}.{}",
+        metadata.artist, metadata.name, metadata.format
+    );
+    Path::new(&CONFIG.files_folder_path).join(file_name)
+}
+
+pub fn check_format(header: &[u8], format: &str) -> Result<(), String> {
+    match detect_audio_format(header) {
+        Some(detected) if matches_extension(detected, format) => Ok(()),
+        _ => Err(format!("File is not a valid {}", format)),
+    }
+}
+
+pub async fn file_sha256(path: &Path) -> std::io::Result<String> {
+    let mut file = File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 65536];
+    loop {
+        let read_size = file.read(&mut buffer).await?;
+        if read_size == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read_size]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+pub async fn add_to_library(temp_path: &Path, metadata: &Metadata) -> Result<(), Status> {
+    let mut header = vec![0; AUDIO_HEADER_LENGTH];
+    let header_length = match File::open(temp_path).await {
+        Ok(mut file) => file.read(&mut header).await.unwrap_or(0),
+        Err(_) => 0,
+    };
+    check_format(&header[..header_length], &metadata.format).map_err(Status::invalid_argument)?;
+
+    let library_path = library_path(metadata);
+    library_changes::record(&library_path);
+    move_into_library(temp_path, &library_path).await?;
+
+    let registered = {
+        let library_path = library_path.clone();
+        query_songs(move |songs| {
+            songs
+                .fetch_song_from_path(library_path)
+                .map(|song| songs.insert_song(song))
+                .unwrap_or(false)
+        })
+        .await
+    };
+    if !registered {
+        if let Err(e) = fs::remove_file(&library_path).await {
+            eprintln!("Could not remove unregistered upload:\n{}", e);
+        }
+        return Err(Status::internal("Song could not be registered"));
+    }
+    Ok(())
+}
+
+async fn move_into_library(temp_path: &Path, library_path: &Path) -> Result<(), Status> {
+    let result = match fs::hard_link(temp_path, library_path).await {
+        Ok(_) => fs::remove_file(temp_path).await,
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            return Err(Status::already_exists("Song already exists"));
+        }
+        Err(_) => fs::rename(temp_path, library_path).await,
+    };
+    result.map_err(|e| {
+        eprintln!("Could not move upload into the library:\n{}", e);
+        Status::internal("Could not move upload into the library")
+    })
+}
+
+pub fn session_path(id: &str) -> PathBuf {
+    Path::new(&CONFIG.uploads_folder_path).join(format!("{}.session", id))
+}
+
+pub async fn remove_session(id: &str) -> bool {
+    if let Err(e) = fs::remove_file(session_path(id)).await {
+        if e.kind() != ErrorKind::NotFound {
+            eprintln!("Could not remove upload file:\n{}", e);
+            return false;
+        }
+    }
+    let id = id.to_owned();
+    query_songs(move |songs| songs.close_upload_session(&id)).await
+}